@@ -0,0 +1,74 @@
+//! Per-frame cost of streaming the config to an overlay, comparing the old
+//! serialize-and-compare approach with generation-based dirty tracking.
+//!
+//! Each iteration simulates one second of panel frames at 144 Hz.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use std::hint::black_box;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use aimx::config::Config;
+use aimx::panel::ipc::{ConfigStream, SendDecision};
+
+const FRAMES: u32 = 144;
+const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / 144);
+
+/// The previous approach: serialize every frame and compare against the last sent string.
+fn string_compare(config: &mut Config, dragging: bool, sink: &mut Vec<u8>) {
+    let mut prev: Option<String> = None;
+    for frame in 0..FRAMES {
+        if dragging {
+            config.offset_x = frame as f32;
+        }
+        let json = serde_json::to_string(&*config).unwrap();
+        if prev.as_deref() == Some(&json) {
+            continue;
+        }
+        writeln!(sink, "{json}").unwrap();
+        prev = Some(json);
+    }
+}
+
+fn generation(config: &mut Config, dragging: bool, sink: &mut Vec<u8>) {
+    let start = Instant::now();
+    let mut stream = ConfigStream::default();
+    for frame in 0..FRAMES {
+        if dragging {
+            config.offset_x = frame as f32;
+            stream.mark_dirty();
+        }
+        let now = start + FRAME_TIME * frame;
+        if stream.poll(now, dragging) == SendDecision::Send {
+            writeln!(sink, "{}", serde_json::to_string(&*config).unwrap()).unwrap();
+            stream.mark_sent(now);
+        }
+    }
+}
+
+fn bench(c: &mut Criterion) {
+    let mut sink = Vec::with_capacity(1 << 20);
+    for (name, dragging) in [("idle", false), ("drag", true)] {
+        let mut group = c.benchmark_group(format!("config_stream/{name}"));
+        group.bench_function("string_compare", |b| {
+            let mut config = Config::default();
+            b.iter(|| {
+                sink.clear();
+                string_compare(&mut config, dragging, &mut sink);
+                black_box(sink.len())
+            })
+        });
+        group.bench_function("generation", |b| {
+            let mut config = Config::default();
+            b.iter(|| {
+                sink.clear();
+                generation(&mut config, dragging, &mut sink);
+                black_box(sink.len())
+            })
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
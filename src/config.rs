@@ -1,8 +1,24 @@
 use log::{debug, info, warn};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
 
-#[derive(Serialize, Deserialize, Clone)]
+use crate::hotkey::{self, Action, Bindings, Trigger};
+
+/// Limits of the panel's sliders. Drag handles, calibration and nudge hotkeys
+/// keep to them too, so nothing takes a setting where a slider can't show it.
+pub const OFFSET_RANGE: RangeInclusive<f32> = -500.0..=500.0;
+pub const RADIUS_RANGE: RangeInclusive<f32> = 0.5..=50.0;
+pub const STROKE_RANGE: RangeInclusive<f32> = 0.1..=10.0;
+
+/// `value` moved into `range`.
+pub fn clamp_to(value: f32, range: RangeInclusive<f32>) -> f32 {
+    value.clamp(*range.start(), *range.end())
+}
+
+/// Crosshair settings. Offsets, radii and stroke width are physical pixels,
+/// so the crosshair keeps its size whatever the desktop scale factor.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
 pub struct Config {
     pub offset_x: f32,
     pub offset_y: f32,
@@ -13,6 +29,170 @@ pub struct Config {
     pub stroke_color: [u8; 3],
     #[serde(default = "default_close_action")]
     pub close_action: String,
+    /// Name of the monitor to center on; the primary monitor when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monitor: Option<String>,
+    /// Center on this window's client area instead of the monitor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<WindowMatch>,
+    /// When the crosshair is drawn, judged by the foreground window
+    #[serde(default, skip_serializing_if = "ShowWhen::is_always")]
+    pub show_when: ShowWhen,
+    /// Whether holding a key or mouse button shows, hides or swaps the crosshair
+    #[serde(default, skip_serializing_if = "Activation::is_always")]
+    pub activation: Activation,
+    /// Global hotkeys handled by the panel. Like `close_action`, these belong
+    /// to the app rather than a profile.
+    #[serde(default = "hotkey::default_bindings", skip_serializing_if = "hotkey::is_default")]
+    pub hotkeys: Bindings,
+    /// Additional overlays shown alongside this one, each with its own crosshair.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub overlays: Vec<OverlayConfig>,
+}
+
+/// One of the additional overlays: its crosshair and where it goes. Window
+/// behaviour, hotkeys and the activation mode come from the top-level [`Config`].
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct OverlayConfig {
+    pub offset_x: f32,
+    pub offset_y: f32,
+    pub color: [u8; 3],
+    pub inner_radius: f32,
+    pub outer_radius: f32,
+    pub stroke_width: f32,
+    pub stroke_color: [u8; 3],
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monitor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<WindowMatch>,
+    #[serde(default, skip_serializing_if = "ShowWhen::is_always")]
+    pub show_when: ShowWhen,
+}
+
+/// Which window a crosshair is anchored to. Both forms ignore ASCII case.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum WindowMatch {
+    /// Executable name, with or without `.exe`, e.g. `game.exe`
+    Process(String),
+    /// Part of the window title
+    Title(String),
+}
+
+impl WindowMatch {
+    pub fn matches(&self, process: &str, title: &str) -> bool {
+        match self {
+            Self::Process(name) => {
+                let strip = |s: &str| {
+                    let lower = s.to_ascii_lowercase();
+                    lower.strip_suffix(".exe").map(str::to_string).unwrap_or(lower)
+                };
+                !name.is_empty() && strip(process) == strip(name)
+            }
+            Self::Title(part) => {
+                !part.is_empty() && title.to_ascii_lowercase().contains(&part.to_ascii_lowercase())
+            }
+        }
+    }
+}
+
+/// Visibility rule, checked against whichever window has focus.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub enum ShowWhen {
+    #[default]
+    Always,
+    /// While one of these executables has focus, matched like [`WindowMatch::Process`]
+    Focused(Vec<String>),
+    /// While the focused window covers its whole monitor
+    Fullscreen,
+}
+
+impl ShowWhen {
+    pub fn is_always(&self) -> bool {
+        *self == Self::Always
+    }
+
+    /// Whether to draw while `process` has focus, or no window at all for `None`.
+    pub fn allows(&self, process: Option<&str>, fullscreen: bool) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Focused(names) => process.is_some_and(|process| {
+                names
+                    .iter()
+                    .any(|name| WindowMatch::Process(name.trim().to_string()).matches(process, ""))
+            }),
+            Self::Fullscreen => process.is_some() && fullscreen,
+        }
+    }
+}
+
+/// The look of a crosshair, without its placement.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Style {
+    pub color: [u8; 3],
+    pub inner_radius: f32,
+    pub outer_radius: f32,
+    pub stroke_width: f32,
+    pub stroke_color: [u8; 3],
+}
+
+/// How a held key or mouse button changes the crosshair. The panel watches
+/// the trigger and tells the overlay when it is pressed or released.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub enum Activation {
+    #[default]
+    Always,
+    /// Drawn only while the trigger is held, e.g. for hip fire in games without a crosshair
+    HoldToShow(Trigger),
+    /// Hidden while the trigger is held, e.g. while aiming down sights
+    HoldToHide(Trigger),
+    /// Drawn with the alternate style while the trigger is held
+    HoldToSwap(Trigger, Style),
+}
+
+impl Activation {
+    pub fn is_always(&self) -> bool {
+        *self == Self::Always
+    }
+
+    pub fn trigger(&self) -> Option<Trigger> {
+        match self {
+            Self::Always => None,
+            Self::HoldToShow(t) | Self::HoldToHide(t) | Self::HoldToSwap(t, _) => Some(*t),
+        }
+    }
+
+    /// Whether the crosshair is drawn with the trigger `held` or released.
+    pub fn visible(&self, held: bool) -> bool {
+        match self {
+            Self::HoldToShow(_) => held,
+            Self::HoldToHide(_) => !held,
+            Self::Always | Self::HoldToSwap(..) => true,
+        }
+    }
+}
+
+impl Default for OverlayConfig {
+    fn default() -> Self {
+        Self::from_config(&Config::default())
+    }
+}
+
+impl OverlayConfig {
+    /// The per-overlay settings of `config`.
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            offset_x: config.offset_x,
+            offset_y: config.offset_y,
+            color: config.color,
+            inner_radius: config.inner_radius,
+            outer_radius: config.outer_radius,
+            stroke_width: config.stroke_width,
+            stroke_color: config.stroke_color,
+            monitor: config.monitor.clone(),
+            anchor: config.anchor.clone(),
+            show_when: config.show_when.clone(),
+        }
+    }
 }
 
 fn default_close_action() -> String {
@@ -30,22 +210,107 @@ impl Default for Config {
             stroke_width: 1.0,
             stroke_color: [0, 0, 0],
             close_action: default_close_action(),
+            monitor: None,
+            anchor: None,
+            show_when: ShowWhen::Always,
+            activation: Activation::Always,
+            hotkeys: hotkey::default_bindings(),
+            overlays: Vec::new(),
         }
     }
 }
 
-fn config_path() -> PathBuf {
+fn config_dir() -> PathBuf {
     std::env::current_exe()
         .unwrap_or_default()
         .parent()
         .unwrap_or(&PathBuf::from("."))
-        .join("aimx_config.json")
+        .to_path_buf()
+}
+
+pub fn config_path() -> PathBuf {
+    config_dir().join("aimx_config.json")
+}
+
+/// Directory holding named profiles, one `<name>.json` per profile.
+fn profiles_dir() -> PathBuf {
+    config_dir().join("profiles")
+}
+
+/// Directory the panel writes exported crosshair images to.
+pub fn exports_dir() -> PathBuf {
+    config_dir().join("exports")
+}
+
+/// Path of the named profile's file.
+pub fn profile_path(name: &str) -> Result<PathBuf, String> {
+    if !valid_profile_name(name) {
+        return Err(format!("invalid profile name {name:?}"));
+    }
+    Ok(profiles_dir().join(format!("{name}.json")))
+}
+
+/// Profile names become file names, so keep them to a safe character set.
+pub fn valid_profile_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ' '))
+}
+
+/// Names of all saved profiles, sorted alphabetically.
+pub fn list_profiles() -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(profiles_dir())
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            if path.extension()? != "json" {
+                return None;
+            }
+            path.file_stem()?.to_str().map(str::to_string)
+        })
+        .filter(|name| valid_profile_name(name))
+        .collect();
+    names.sort();
+    names
+}
+
+/// Parse `#rrggbb` (the `#` is optional) into an RGB triple.
+pub fn parse_hex_color(s: &str) -> Option<[u8; 3]> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
 }
 
 impl Config {
     pub fn load() -> Self {
-        let path = config_path();
-        match std::fs::read_to_string(&path) {
+        Self::load_from(&config_path())
+    }
+
+    /// Load the named profile from the profiles directory.
+    pub fn load_profile(name: &str) -> Result<Self, String> {
+        let path = profile_path(name)?;
+        let cfg = Self::read(&path)?;
+        info!("loaded profile {name:?} from {}", path.display());
+        Ok(cfg)
+    }
+
+    /// Read a config file, reporting why it could not be used.
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let s = std::fs::read_to_string(path)
+            .map_err(|e| format!("cannot read {}: {e}", path.display()))?;
+        serde_json::from_str(&s).map_err(|e| format!("corrupt config at {}: {e}", path.display()))
+    }
+
+    /// Load a config file, falling back to defaults if it is missing or corrupt.
+    pub fn load_from(path: &Path) -> Self {
+        match std::fs::read_to_string(path) {
             Ok(s) => match serde_json::from_str(&s) {
                 Ok(cfg) => {
                     info!("loaded config from {}", path.display());
@@ -63,11 +328,97 @@ impl Config {
         }
     }
 
-    /// Minimum window size needed to fully contain the crosshair.
-    pub fn window_size(&self) -> f32 {
+    /// The config overlay `index` runs with: this one for 0, otherwise the
+    /// additional overlay with the app-level settings from here. Never lists overlays.
+    /// `index` must be at most `overlays.len()`, as the panel's overlay list keeps it.
+    pub fn overlay(&self, index: usize) -> Config {
+        self.check_overlay_index(index);
+        let Some(index) = index.checked_sub(1) else {
+            return Config {
+                overlays: Vec::new(),
+                ..self.clone()
+            };
+        };
+        let overlay = &self.overlays[index];
+        Config {
+            offset_x: overlay.offset_x,
+            offset_y: overlay.offset_y,
+            color: overlay.color,
+            inner_radius: overlay.inner_radius,
+            outer_radius: overlay.outer_radius,
+            stroke_width: overlay.stroke_width,
+            stroke_color: overlay.stroke_color,
+            close_action: self.close_action.clone(),
+            monitor: overlay.monitor.clone(),
+            anchor: overlay.anchor.clone(),
+            show_when: overlay.show_when.clone(),
+            activation: self.activation_for(index + 1),
+            hotkeys: self.hotkeys.clone(),
+            overlays: Vec::new(),
+        }
+    }
+
+    /// The activation mode of overlay `index`. Additional overlays share the
+    /// trigger, but only the first one has an alternate style to swap to.
+    pub fn activation_for(&self, index: usize) -> Activation {
+        match &self.activation {
+            Activation::HoldToSwap(..) if index > 0 => Activation::Always,
+            activation => activation.clone(),
+        }
+    }
+
+    /// Edit overlay `index` as a whole [`Config`], as [`Config::overlay`]
+    /// returns it. For additional overlays, only the per-overlay settings are kept.
+    /// `index` must be at most `overlays.len()`.
+    pub fn edit_overlay<R>(&mut self, index: usize, edit: impl FnOnce(&mut Config) -> R) -> R {
+        self.check_overlay_index(index);
+        let Some(index) = index.checked_sub(1) else {
+            return edit(self);
+        };
+        let mut config = self.overlay(index + 1);
+        let result = edit(&mut config);
+        self.overlays[index] = OverlayConfig::from_config(&config);
+        result
+    }
+
+    fn check_overlay_index(&self, index: usize) {
+        debug_assert!(
+            index <= self.overlays.len(),
+            "overlay {index} out of range: there are {} additional overlays",
+            self.overlays.len()
+        );
+    }
+
+    /// Minimum window side in physical pixels needed to fully contain the crosshair.
+    pub fn window_size_px(&self) -> u32 {
         let r = self.outer_radius.max(self.inner_radius) + self.stroke_width;
         // diameter + padding
-        (r * 2.0 + 4.0).ceil().max(16.0)
+        (r * 2.0 + 4.0).ceil().max(16.0) as u32
+    }
+
+    pub fn style(&self) -> Style {
+        Style {
+            color: self.color,
+            inner_radius: self.inner_radius,
+            outer_radius: self.outer_radius,
+            stroke_width: self.stroke_width,
+            stroke_color: self.stroke_color,
+        }
+    }
+
+    /// The config as drawn while the activation trigger is `held` or released:
+    /// a held swap trigger replaces the style with the alternate one.
+    pub fn for_hold(&self, held: bool) -> Config {
+        let mut config = self.clone();
+        if let (Activation::HoldToSwap(_, alternate), true) = (&self.activation, held) {
+            let alternate = alternate.clone();
+            config.color = alternate.color;
+            config.inner_radius = alternate.inner_radius;
+            config.outer_radius = alternate.outer_radius;
+            config.stroke_width = alternate.stroke_width;
+            config.stroke_color = alternate.stroke_color;
+        }
+        config
     }
 
     pub fn save(&self) {
@@ -80,4 +431,281 @@ impl Config {
             Err(e) => warn!("failed to serialize config: {e}"),
         }
     }
+
+    /// Save as a named profile in the profiles directory.
+    pub fn save_profile(&self, name: &str) -> Result<(), String> {
+        let path = profile_path(name)?;
+        let dir = profiles_dir();
+        std::fs::create_dir_all(&dir).map_err(|e| format!("cannot create {}: {e}", dir.display()))?;
+        let json = serde_json::to_string_pretty(self).map_err(|e| format!("failed to serialize config: {e}"))?;
+        std::fs::write(&path, json).map_err(|e| format!("failed to write {}: {e}", path.display()))?;
+        info!("saved profile {name:?} to {}", path.display());
+        Ok(())
+    }
+
+    /// Set a single field from its `key=value` text form, as used by `aimx ctl set`.
+    pub fn set_field(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let number = || {
+            value
+                .parse::<f32>()
+                .ok()
+                .filter(|v| v.is_finite())
+                .ok_or_else(|| format!("{key}: expected a number, got {value:?}"))
+        };
+        let color = || parse_hex_color(value).ok_or_else(|| format!("{key}: expected #rrggbb, got {value:?}"));
+        let positive = |v: f32| {
+            if v > 0.0 {
+                Ok(v)
+            } else {
+                Err(format!("{key}: must be greater than zero"))
+            }
+        };
+
+        match key {
+            "offset_x" => self.offset_x = number()?,
+            "offset_y" => self.offset_y = number()?,
+            "color" => self.color = color()?,
+            "inner_radius" => self.inner_radius = positive(number()?)?,
+            "outer_radius" => self.outer_radius = positive(number()?)?,
+            "stroke_width" => self.stroke_width = positive(number()?)?,
+            "stroke_color" => self.stroke_color = color()?,
+            // An empty name goes back to the primary monitor
+            "monitor" => self.monitor = Some(value.to_string()).filter(|v| !v.is_empty()),
+            // Anchoring to an empty name goes back to centering on the monitor
+            "anchor_process" => {
+                self.anchor = Some(WindowMatch::Process(value.to_string())).filter(|_| !value.is_empty())
+            }
+            "anchor_title" => {
+                self.anchor = Some(WindowMatch::Title(value.to_string())).filter(|_| !value.is_empty())
+            }
+            // "always", "fullscreen", or a comma-separated list of executables
+            "show_when" => {
+                self.show_when = match value.trim() {
+                    "" | "always" => ShowWhen::Always,
+                    "fullscreen" => ShowWhen::Fullscreen,
+                    list => ShowWhen::Focused(
+                        list.split(',')
+                            .map(str::trim)
+                            .filter(|s| !s.is_empty())
+                            .map(str::to_string)
+                            .collect(),
+                    ),
+                }
+            }
+            // "always", or show:, hide: or swap: followed by a key or mouse button.
+            // Swapping starts from the current style, which alternate_* then edits.
+            "activation" => {
+                let mode = value.trim();
+                self.activation = match mode.split_once(':') {
+                    _ if mode.is_empty() || mode == "always" => Activation::Always,
+                    Some((kind, trigger)) => {
+                        let trigger: Trigger = trigger.parse().map_err(|e| format!("{key}: {e}"))?;
+                        match kind.trim() {
+                            "show" => Activation::HoldToShow(trigger),
+                            "hide" => Activation::HoldToHide(trigger),
+                            "swap" => match &self.activation {
+                                Activation::HoldToSwap(_, alternate) => Activation::HoldToSwap(trigger, alternate.clone()),
+                                _ => Activation::HoldToSwap(trigger, self.style()),
+                            },
+                            _ => return Err(format!("{key}: expected always, show:, hide: or swap:, got {value:?}")),
+                        }
+                    }
+                    None => return Err(format!("{key}: expected always, show:, hide: or swap:, got {value:?}")),
+                };
+            }
+            _ if key.starts_with("alternate_") => {
+                let Activation::HoldToSwap(_, alternate) = &mut self.activation else {
+                    return Err(format!("{key}: only used with activation=swap:..."));
+                };
+                match &key["alternate_".len()..] {
+                    "color" => alternate.color = color()?,
+                    "inner_radius" => alternate.inner_radius = positive(number()?)?,
+                    "outer_radius" => alternate.outer_radius = positive(number()?)?,
+                    "stroke_width" => alternate.stroke_width = positive(number()?)?,
+                    "stroke_color" => alternate.stroke_color = color()?,
+                    _ => return Err(format!("unknown setting {key:?}")),
+                }
+            }
+            // e.g. hotkey_toggle=Ctrl+Alt+H; an empty value unbinds the action
+            _ if key.starts_with("hotkey_") => {
+                let action = Action::from_name(&key["hotkey_".len()..]).ok_or_else(|| format!("unknown setting {key:?}"))?;
+                match value.trim() {
+                    "" => self.hotkeys.remove(&action),
+                    combo => self.hotkeys.insert(action, combo.parse()?),
+                };
+            }
+            _ => return Err(format!("unknown setting {key:?}")),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_colors() {
+        assert_eq!(parse_hex_color("#00ff80"), Some([0, 255, 128]));
+        assert_eq!(parse_hex_color("FFa0b1"), Some([255, 160, 177]));
+        for bad in ["", "#", "#fff", "#00ff8", "#00ff800", "#00gg00", "#+1ff00", "#0é0ff"] {
+            assert_eq!(parse_hex_color(bad), None, "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn profile_names_stay_in_the_profiles_directory() {
+        for good in ["cs2", "Apex Legends", "tarkov_v2.1"] {
+            assert!(valid_profile_name(good), "{good:?}");
+        }
+        for bad in ["", ".", "..", ".hidden", "../config", "a/b", "a\\b", "C:evil", "name\0"] {
+            assert!(!valid_profile_name(bad), "{bad:?}");
+            assert!(profile_path(bad).is_err());
+        }
+    }
+
+    #[test]
+    fn set_field_validates_values() {
+        let mut config = Config::default();
+        config.set_field("offset_x", "-12.5").unwrap();
+        config.set_field("color", "#ff0000").unwrap();
+        config.set_field("monitor", "DP-1").unwrap();
+        assert_eq!((config.offset_x, config.color), (-12.5, [255, 0, 0]));
+        assert_eq!(config.monitor.as_deref(), Some("DP-1"));
+        config.set_field("monitor", "").unwrap();
+        assert_eq!(config.monitor, None);
+
+        let before = config.clone();
+        assert!(config.set_field("color", "red").is_err());
+        assert!(config.set_field("offset_y", "NaN").is_err());
+        assert!(config.set_field("inner_radius", "0").is_err());
+        assert!(config.set_field("stroke_width", "wide").is_err());
+        assert!(config.set_field("alternate_color", "#00ff00").is_err(), "no swap mode set");
+        assert!(config.set_field("radius", "3").unwrap_err().contains("unknown setting"));
+        assert!(config.set_field("close_action", "quit").is_err(), "not settable remotely");
+        assert!(config == before, "rejected values leave the config alone");
+    }
+
+    #[test]
+    fn process_match_ignores_case_and_exe_suffix() {
+        let m = WindowMatch::Process("Game.exe".into());
+        assert!(m.matches("game.exe", ""));
+        assert!(m.matches("GAME", ""));
+        assert!(!m.matches("game2.exe", "game"));
+        assert!(WindowMatch::Process("game".into()).matches("game.exe", ""));
+    }
+
+    #[test]
+    fn title_match_is_case_insensitive_substring() {
+        let m = WindowMatch::Title("counter-strike".into());
+        assert!(m.matches("cs2.exe", "Counter-Strike 2"));
+        assert!(!m.matches("counter-strike", "Steam"));
+        assert!(!WindowMatch::Title(String::new()).matches("x", "anything"));
+    }
+
+    #[test]
+    fn show_rules() {
+        let mut config = Config::default();
+        assert!(config.show_when.allows(None, false));
+
+        config.set_field("show_when", "game.exe, cs2").unwrap();
+        assert!(config.show_when.allows(Some("CS2.exe"), false));
+        assert!(!config.show_when.allows(Some("discord.exe"), true));
+        assert!(!config.show_when.allows(None, false));
+
+        config.set_field("show_when", "fullscreen").unwrap();
+        assert!(config.show_when.allows(Some("anything"), true));
+        assert!(!config.show_when.allows(Some("anything"), false));
+
+        config.set_field("show_when", "always").unwrap();
+        assert!(config.show_when.is_always());
+    }
+
+    #[test]
+    fn activation_modes() {
+        let mut config = Config::default();
+        assert!(config.activation.visible(false) && config.activation.visible(true));
+
+        config.set_field("activation", "show:MouseRight").unwrap();
+        assert_eq!(config.activation.trigger(), Some(Trigger::MouseRight));
+        assert!(!config.activation.visible(false));
+        assert!(config.activation.visible(true));
+
+        config.set_field("activation", "hide: shift").unwrap();
+        assert!(config.activation.visible(false));
+        assert!(!config.activation.visible(true));
+        assert!(config.set_field("alternate_color", "#00ff00").is_err());
+
+        config.set_field("activation", "swap:Mouse4").unwrap();
+        config.set_field("alternate_color", "#00ff00").unwrap();
+        config.set_field("alternate_outer_radius", "12").unwrap();
+        assert!(config.activation.visible(true));
+        let held = config.for_hold(true);
+        assert_eq!((held.color, held.outer_radius), ([0, 255, 0], 12.0));
+        assert_eq!(held.offset_x, config.offset_x);
+        assert!(held.window_size_px() > config.window_size_px());
+        assert_eq!(config.for_hold(false).style(), config.style());
+
+        // Changing the trigger keeps the alternate style
+        config.set_field("activation", "swap:E").unwrap();
+        assert_eq!(config.for_hold(true).color, [0, 255, 0]);
+        let json = serde_json::to_string(&config).unwrap();
+        let back: Config = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.activation, config.activation);
+
+        assert!(config.set_field("activation", "toggle:E").is_err());
+        assert!(config.set_field("activation", "show:Mouse9").is_err());
+        config.set_field("activation", "always").unwrap();
+        assert!(config.activation.is_always());
+    }
+
+    #[test]
+    fn additional_overlays_take_app_settings_from_the_top_level() {
+        let mut config = Config {
+            activation: Activation::HoldToShow(Trigger::MouseRight),
+            overlays: vec![OverlayConfig::default()],
+            ..Config::default()
+        };
+        config.edit_overlay(1, |cfg| {
+            cfg.offset_x = 12.0;
+            cfg.monitor = Some("DP-2".into());
+            // Not per overlay, so dropped
+            cfg.close_action = "quit".into();
+        });
+        let second = config.overlay(1);
+        assert_eq!((second.offset_x, second.monitor.as_deref()), (12.0, Some("DP-2")));
+        assert_eq!(second.close_action, "ask");
+        assert_eq!(second.activation, config.activation);
+        assert!(config.overlay(0).overlays.is_empty(), "overlays aren't sent to the first one");
+
+        // Only the first overlay has an alternate style
+        config.set_field("activation", "swap:Mouse4").unwrap();
+        assert!(config.overlay(1).activation.is_always());
+
+        // Files from before the split listed whole configs
+        let old = r#"{"offset_x":0,"offset_y":0,"color":[255,0,0],"inner_radius":3,"outer_radius":3.5,
+            "stroke_width":1,"stroke_color":[0,0,0],"overlays":[{"offset_x":5,"offset_y":0,"color":[0,255,0],
+            "inner_radius":2,"outer_radius":4,"stroke_width":1,"stroke_color":[0,0,0],"close_action":"quit","overlays":[]}]}"#;
+        let read: Config = serde_json::from_str(old).unwrap();
+        assert_eq!(read.overlays[0].offset_x, 5.0);
+        let json = serde_json::to_string(&read).unwrap();
+        assert_eq!(json.matches("close_action").count(), 1, "{json}");
+    }
+
+    #[test]
+    fn hotkey_settings() {
+        let mut config = Config::default();
+        config.set_field("hotkey_toggle", "ctrl+shift+x").unwrap();
+        assert_eq!(config.hotkeys[&Action::Toggle].to_string(), "Ctrl+Shift+X");
+        config.set_field("hotkey_reset_offset", "").unwrap();
+        assert!(!config.hotkeys.contains_key(&Action::ResetOffset));
+        assert!(config.set_field("hotkey_toggle", "X").is_err());
+        assert!(config.set_field("hotkey_jump", "Ctrl+J").is_err());
+
+        let json = serde_json::to_string(&config).unwrap();
+        let back: Config = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.hotkeys, config.hotkeys);
+        let json = serde_json::to_string(&Config::default()).unwrap();
+        assert!(!json.contains("hotkeys"), "{json}");
+    }
 }
@@ -0,0 +1,431 @@
+use clap::Subcommand;
+use crossbeam_channel::{Receiver, Sender};
+use eframe::egui;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+
+/// Exit codes of `aimx ctl`, so launchers and scripts can tell failures apart.
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_REJECTED: i32 = 1;
+pub const EXIT_NOT_RUNNING: i32 = 3;
+pub const EXIT_IO_ERROR: i32 = 4;
+
+/// Time to connect, and for a client to send its whole request line.
+const IO_TIMEOUT: Duration = Duration::from_secs(2);
+/// Longest request line accepted, newline included; a `set` of every field fits easily.
+const MAX_REQUEST: u64 = 4096;
+/// Connections served at once; more are closed straight away.
+const MAX_CONNECTIONS: usize = 4;
+/// How long a connection waits for the panel to act on a request.
+/// The panel may be asleep in the tray, so allow more than one frame.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Subcommand)]
+pub enum CtlCommand {
+    /// Toggle the overlay on or off
+    Toggle,
+    /// Show the overlay
+    Show,
+    /// Hide the overlay
+    Hide,
+    /// Switch to a saved profile
+    Profile { name: String },
+    /// Change settings, e.g. `color=#00ff00 inner_radius=4`
+    Set {
+        #[arg(required = true, value_name = "KEY=VALUE")]
+        assignments: Vec<String>,
+    },
+    /// Print the state of the running instance
+    Status {
+        /// Print machine-readable JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum Request {
+    Toggle,
+    Show,
+    Hide,
+    Profile(String),
+    Set(Vec<(String, String)>),
+    Status,
+    /// Sent by a second launch: bring the panel to the front
+    Activate,
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum Response {
+    Ok,
+    Status(Box<Status>),
+    Rejected(String),
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Status {
+    pub overlay_visible: bool,
+    pub profile: Option<String>,
+    pub config: Config,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    token: String,
+    request: Request,
+}
+
+/// Where a running instance listens, published in the per-user runtime directory.
+#[derive(Serialize, Deserialize)]
+struct Endpoint {
+    pid: u32,
+    port: u16,
+    token: String,
+}
+
+/// Per-user directory for the control endpoint and instance lock.
+pub fn runtime_dir() -> PathBuf {
+    crate::platform::current().runtime_dir()
+}
+
+fn endpoint_path() -> PathBuf {
+    runtime_dir().join("aimx.endpoint")
+}
+
+pub fn create_runtime_dir() -> std::io::Result<()> {
+    let platform = crate::platform::current();
+    platform.create_private_dir(&platform.runtime_dir())
+}
+
+/// Unpredictable per-run token so other local programs can't drive the panel blindly.
+fn random_token() -> std::io::Result<String> {
+    let mut bytes = [0u8; 16];
+    crate::platform::current().random_bytes(&mut bytes)?;
+    Ok(bytes.iter().map(|b| format!("{b:02x}")).collect())
+}
+
+impl Endpoint {
+    fn read() -> Option<Self> {
+        let s = std::fs::read_to_string(endpoint_path()).ok()?;
+        serde_json::from_str(&s).ok()
+    }
+}
+
+/// A request from `aimx ctl` waiting for the panel to act on it.
+pub struct Pending {
+    pub request: Request,
+    reply: Sender<Response>,
+}
+
+impl Pending {
+    pub fn reply(self, response: Response) {
+        let _ = self.reply.send(response);
+    }
+}
+
+/// Accepts `aimx ctl` connections and hands their requests to the panel.
+/// Removes the published endpoint when dropped.
+pub struct Server {
+    pub requests: Receiver<Pending>,
+    token: String,
+}
+
+impl Server {
+    pub fn start(ctx: egui::Context) -> Option<Self> {
+        let listener = match TcpListener::bind((Ipv4Addr::LOCALHOST, 0)) {
+            Ok(l) => l,
+            Err(e) => {
+                warn!("failed to bind control socket: {e}");
+                return None;
+            }
+        };
+        let port = listener.local_addr().ok()?.port();
+        let token = match random_token() {
+            Ok(token) => token,
+            Err(e) => {
+                warn!("cannot generate control token: {e}");
+                return None;
+            }
+        };
+
+        let endpoint = Endpoint {
+            pid: std::process::id(),
+            port,
+            token: token.clone(),
+        };
+        let published = create_runtime_dir().and_then(|_| {
+            let json = serde_json::to_string(&endpoint).map_err(std::io::Error::other)?;
+            std::fs::write(endpoint_path(), json)
+        });
+        if let Err(e) = published {
+            warn!("failed to publish control endpoint: {e}");
+            return None;
+        }
+        info!("control server listening on port {port}");
+
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let expected_token = token.clone();
+        let serving = Arc::new(AtomicUsize::new(0));
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        if serving.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                            serving.fetch_sub(1, Ordering::SeqCst);
+                            debug!("control server: too many connections, closing one");
+                            continue;
+                        }
+                        let tx = tx.clone();
+                        let ctx = ctx.clone();
+                        let token = expected_token.clone();
+                        let serving = serving.clone();
+                        std::thread::spawn(move || {
+                            serve_connection(stream, &token, &tx, &ctx);
+                            serving.fetch_sub(1, Ordering::SeqCst);
+                        });
+                    }
+                    Err(e) => warn!("control server: accept failed: {e}"),
+                }
+            }
+        });
+
+        Some(Self {
+            requests: rx,
+            token,
+        })
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        // Only remove the endpoint if it is still ours
+        if Endpoint::read().is_some_and(|e| e.token == self.token) {
+            let _ = std::fs::remove_file(endpoint_path());
+        }
+    }
+}
+
+/// Reads from a stream until a fixed point in time, however the reads are split up.
+struct Deadline {
+    stream: TcpStream,
+    until: Instant,
+}
+
+impl Read for Deadline {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let left = self.until.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(std::io::ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(buf)
+    }
+}
+
+/// One newline-terminated line of at most [`MAX_REQUEST`] bytes.
+fn read_request(reader: impl Read) -> Result<String, String> {
+    let mut line = String::new();
+    BufReader::new(reader.take(MAX_REQUEST))
+        .read_line(&mut line)
+        .map_err(|e| format!("cannot read request: {e}"))?;
+    if !line.ends_with('\n') {
+        return Err(format!("request is incomplete or longer than {MAX_REQUEST} bytes"));
+    }
+    Ok(line)
+}
+
+fn serve_connection(stream: TcpStream, token: &str, tx: &Sender<Pending>, ctx: &egui::Context) {
+    let mut writer = match stream.try_clone() {
+        Ok(w) => w,
+        Err(_) => return,
+    };
+
+    let reader = Deadline {
+        stream,
+        until: Instant::now() + IO_TIMEOUT,
+    };
+    let line = match read_request(reader) {
+        Ok(line) => line,
+        Err(e) => {
+            debug!("control server: {e}");
+            return;
+        }
+    };
+
+    let response = match serde_json::from_str::<Envelope>(&line) {
+        Ok(envelope) if envelope.token == token => {
+            debug!("control request: {:?}", envelope.request);
+            let (reply_tx, reply_rx) = crossbeam_channel::bounded(1);
+            let pending = Pending {
+                request: envelope.request,
+                reply: reply_tx,
+            };
+            if tx.send(pending).is_err() {
+                return;
+            }
+            ctx.request_repaint();
+            reply_rx
+                .recv_timeout(REPLY_TIMEOUT)
+                .unwrap_or_else(|_| Response::Rejected("panel did not respond".to_string()))
+        }
+        Ok(_) => Response::Rejected("bad token".to_string()),
+        Err(e) => Response::Rejected(format!("malformed request: {e}")),
+    };
+
+    if let Ok(json) = serde_json::to_string(&response) {
+        let _ = writer.write_all(format!("{json}\n").as_bytes());
+    }
+}
+
+fn to_request(command: &CtlCommand) -> Result<Request, String> {
+    Ok(match command {
+        CtlCommand::Toggle => Request::Toggle,
+        CtlCommand::Show => Request::Show,
+        CtlCommand::Hide => Request::Hide,
+        CtlCommand::Profile { name } => Request::Profile(name.clone()),
+        CtlCommand::Set { assignments } => Request::Set(
+            assignments
+                .iter()
+                .map(|a| {
+                    a.split_once('=')
+                        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+                        .ok_or_else(|| format!("expected KEY=VALUE, got {a:?}"))
+                })
+                .collect::<Result<_, _>>()?,
+        ),
+        CtlCommand::Status { .. } => Request::Status,
+    })
+}
+
+pub enum SendError {
+    NotRunning,
+    Io(std::io::Error),
+}
+
+pub fn send(request: Request) -> Result<Response, SendError> {
+    let endpoint = Endpoint::read().ok_or(SendError::NotRunning)?;
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, endpoint.port));
+    // Nothing listening means the endpoint was left behind by a dead instance
+    let stream = TcpStream::connect_timeout(&addr, IO_TIMEOUT).map_err(|_| SendError::NotRunning)?;
+    stream.set_read_timeout(Some(REPLY_TIMEOUT + IO_TIMEOUT)).map_err(SendError::Io)?;
+
+    let envelope = Envelope {
+        token: endpoint.token,
+        request,
+    };
+    let json = serde_json::to_string(&envelope).map_err(|e| SendError::Io(e.into()))?;
+    (&stream)
+        .write_all(format!("{json}\n").as_bytes())
+        .map_err(SendError::Io)?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line).map_err(SendError::Io)?;
+    serde_json::from_str(&line).map_err(|e| SendError::Io(e.into()))
+}
+
+fn print_status(status: &Status) {
+    let hex = |c: [u8; 3]| format!("#{:02x}{:02x}{:02x}", c[0], c[1], c[2]);
+    let cfg = &status.config;
+    println!("overlay:      {}", if status.overlay_visible { "visible" } else { "hidden" });
+    println!("profile:      {}", status.profile.as_deref().unwrap_or("(none)"));
+    println!("monitor:      {}", cfg.monitor.as_deref().unwrap_or("(primary)"));
+    println!("offset:       {}, {}", cfg.offset_x, cfg.offset_y);
+    println!("inner radius: {}", cfg.inner_radius);
+    println!("outer radius: {}", cfg.outer_radius);
+    println!("stroke width: {}", cfg.stroke_width);
+    println!("color:        {}", hex(cfg.color));
+    println!("stroke color: {}", hex(cfg.stroke_color));
+}
+
+/// Run an `aimx ctl` command against the running instance and return the process exit code.
+pub fn run(command: CtlCommand) -> i32 {
+    crate::platform::current().attach_parent_console();
+
+    let request = match to_request(&command) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("aimx: {e}");
+            return EXIT_REJECTED;
+        }
+    };
+
+    match send(request) {
+        Ok(Response::Ok) => EXIT_OK,
+        Ok(Response::Status(status)) => {
+            if matches!(command, CtlCommand::Status { json: true }) {
+                match serde_json::to_string_pretty(&status) {
+                    Ok(json) => println!("{json}"),
+                    Err(e) => {
+                        eprintln!("aimx: {e}");
+                        return EXIT_IO_ERROR;
+                    }
+                }
+            } else {
+                print_status(&status);
+            }
+            EXIT_OK
+        }
+        Ok(Response::Rejected(reason)) => {
+            eprintln!("aimx: {reason}");
+            EXIT_REJECTED
+        }
+        Err(SendError::NotRunning) => {
+            eprintln!("aimx: no running instance");
+            EXIT_NOT_RUNNING
+        }
+        Err(SendError::Io(e)) => {
+            eprintln!("aimx: failed to talk to running instance: {e}");
+            EXIT_IO_ERROR
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_splits_assignments() {
+        let command = CtlCommand::Set {
+            assignments: vec!["color = #00ff00".into(), "show_when=a.exe,b.exe".into()],
+        };
+        let Ok(Request::Set(pairs)) = to_request(&command) else {
+            panic!("expected a set request");
+        };
+        assert_eq!(pairs, [("color".into(), "#00ff00".into()), ("show_when".into(), "a.exe,b.exe".into())]);
+
+        let command = CtlCommand::Set {
+            assignments: vec!["color=#00ff00".into(), "inner_radius".into()],
+        };
+        assert!(to_request(&command).unwrap_err().contains("KEY=VALUE"));
+    }
+
+    #[test]
+    fn tokens_are_random_hex() {
+        let (a, b) = (random_token().unwrap(), random_token().unwrap());
+        assert_eq!(a.len(), 32);
+        assert!(a.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn requests_are_single_bounded_lines() {
+        let read = |bytes: &[u8]| read_request(std::io::Cursor::new(bytes.to_vec()));
+        assert_eq!(read(b"{\"a\":1}\nmore").unwrap(), "{\"a\":1}\n");
+        assert!(read(b"{\"a\":1}").is_err(), "no newline");
+        assert!(read(b"").is_err());
+
+        let longest = format!("{}\n", "x".repeat(MAX_REQUEST as usize - 1));
+        assert_eq!(read(longest.as_bytes()).unwrap(), longest);
+        let too_long = format!("{}\n", "x".repeat(MAX_REQUEST as usize));
+        assert!(read(too_long.as_bytes()).is_err());
+    }
+}
@@ -2,10 +2,13 @@ use eframe::egui;
 
 use crate::config::Config;
 
+/// Draw the crosshair at `center` (points). The config's lengths are physical
+/// pixels, so they are divided by the painter's pixels per point.
 pub fn draw(painter: &egui::Painter, center: egui::Pos2, config: &Config) {
+    let pt = |px: f32| crate::geometry::to_points(px, painter.pixels_per_point());
     let fill = egui::Color32::from_rgb(config.color[0], config.color[1], config.color[2]);
     let stroke_color = egui::Color32::from_rgb(config.stroke_color[0], config.stroke_color[1], config.stroke_color[2]);
 
-    painter.circle_filled(center, config.inner_radius, fill);
-    painter.circle_stroke(center, config.outer_radius, egui::Stroke::new(config.stroke_width, stroke_color));
+    painter.circle_filled(center, pt(config.inner_radius), fill);
+    painter.circle_stroke(center, pt(config.outer_radius), egui::Stroke::new(pt(config.stroke_width), stroke_color));
 }
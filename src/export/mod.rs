@@ -0,0 +1,356 @@
+//! Crosshair images without a window: `aimx render` and anything else that
+//! needs a crosshair as pixels, like previews and image tests.
+
+pub mod svg;
+
+use eframe::egui::{Color32, ColorImage};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::config::{self, Config};
+use crate::raster::Renderer;
+
+/// Largest `--scale`; a 64x crosshair is already several thousand pixels wide
+pub const MAX_SCALE: u32 = 64;
+
+#[derive(clap::Args)]
+pub struct RenderArgs {
+    /// Saved profile to render
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+    /// Config file to render instead of a profile
+    #[arg(long, value_name = "PATH", conflicts_with = "profile")]
+    pub config: Option<PathBuf>,
+    /// Magnify each pixel into a SCALE x SCALE block
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=MAX_SCALE as i64))]
+    pub scale: u32,
+    /// Outline every source pixel when magnifying
+    #[arg(long)]
+    pub grid: bool,
+    /// `transparent`, `checkerboard`, a color like `#202020`, or an image to draw over (its center is used)
+    #[arg(long, default_value = "transparent")]
+    pub background: Background,
+    /// PNG file to write, or SVG if it ends in `.svg`
+    #[arg(long, value_name = "FILE")]
+    pub out: PathBuf,
+}
+
+/// What is behind the crosshair in an exported image.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Background {
+    Transparent,
+    Color(Color32),
+    /// Gray squares [`CHECKER_SIZE`] output pixels wide, to show what is transparent
+    Checkerboard,
+    /// An image file, e.g. a game screenshot
+    Image(PathBuf),
+}
+
+/// Side of one checkerboard square, in output pixels whatever the magnification
+pub const CHECKER_SIZE: usize = 8;
+
+impl FromStr for Background {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        if s.eq_ignore_ascii_case("transparent") || s.eq_ignore_ascii_case("none") {
+            return Ok(Self::Transparent);
+        }
+        if s.eq_ignore_ascii_case("checkerboard") {
+            return Ok(Self::Checkerboard);
+        }
+        if let Some([r, g, b]) = config::parse_hex_color(s) {
+            return Ok(Self::Color(Color32::from_rgb(r, g, b)));
+        }
+        if s.starts_with('#') {
+            return Err(format!("invalid color {s:?}, expected #rrggbb"));
+        }
+        Ok(Self::Image(PathBuf::from(s)))
+    }
+}
+
+/// Options for [`render`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderOptions {
+    pub scale: u32,
+    pub grid: bool,
+    pub background: Background,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            scale: 1,
+            grid: false,
+            background: Background::Transparent,
+        }
+    }
+}
+
+/// Rasterize `config` exactly as the overlay draws it at 100% scale, on a
+/// canvas the size of the overlay window. Colors are premultiplied.
+pub fn render(config: &Config, options: &RenderOptions) -> Result<ColorImage, String> {
+    let backdrop = match &options.background {
+        Background::Image(path) => Some(load_png(path)?),
+        _ => None,
+    };
+    let crosshair = Renderer::new().render_crosshair(config, 1.0);
+    Ok(compose(&crosshair, options, backdrop.as_ref()))
+}
+
+/// Put a rendered crosshair over its background and magnify it. `backdrop`
+/// is the loaded [`Background::Image`]; without one that background is transparent.
+pub fn compose(crosshair: &ColorImage, options: &RenderOptions, backdrop: Option<&ColorImage>) -> ColorImage {
+    let behind = match (&options.background, backdrop) {
+        (Background::Color(color), _) => Some(ColorImage::filled(crosshair.size, *color)),
+        (Background::Image(_), Some(backdrop)) => Some(crop_center(backdrop, crosshair.size)),
+        _ => None,
+    };
+    let image = match behind {
+        Some(mut image) => {
+            blend(&mut image, crosshair, [0, 0]);
+            image
+        }
+        None => crosshair.clone(),
+    };
+    let mut image = magnify(&image, options.scale, false);
+    if options.background == Background::Checkerboard {
+        let mut board = checkerboard(image.size);
+        blend(&mut board, &image, [0, 0]);
+        image = board;
+    }
+    if options.grid {
+        draw_grid(&mut image, options.scale);
+    }
+    image
+}
+
+/// Light and dark gray squares of [`CHECKER_SIZE`] pixels.
+pub fn checkerboard(size: [usize; 2]) -> ColorImage {
+    let (light, dark) = (Color32::from_gray(204), Color32::from_gray(153));
+    let mut image = ColorImage::filled(size, light);
+    for y in 0..size[1] {
+        for x in 0..size[0] {
+            if (x / CHECKER_SIZE + y / CHECKER_SIZE) % 2 == 1 {
+                image.pixels[y * size[0] + x] = dark;
+            }
+        }
+    }
+    image
+}
+
+/// Draw premultiplied `src` over `dst` with its top-left corner at `at`.
+pub fn blend(dst: &mut ColorImage, src: &ColorImage, at: [usize; 2]) {
+    let [w, h] = dst.size;
+    for y in 0..src.size[1].min(h.saturating_sub(at[1])) {
+        for x in 0..src.size[0].min(w.saturating_sub(at[0])) {
+            let s = src.pixels[y * src.size[0] + x];
+            let d = &mut dst.pixels[(at[1] + y) * w + at[0] + x];
+            let keep = 255 - s.a() as u32;
+            let mix = |s: u8, d: u8| (s as u32 + (d as u32 * keep + 127) / 255).min(255) as u8;
+            *d = Color32::from_rgba_premultiplied(
+                mix(s.r(), d.r()),
+                mix(s.g(), d.g()),
+                mix(s.b(), d.b()),
+                mix(s.a(), d.a()),
+            );
+        }
+    }
+}
+
+/// The `size` region around the middle of `image`, padded with transparency
+/// if the image is smaller.
+pub fn crop_center(image: &ColorImage, size: [usize; 2]) -> ColorImage {
+    let mut out = ColorImage::filled(size, Color32::TRANSPARENT);
+    let offset = |axis: usize| image.size[axis] as isize / 2 - size[axis] as isize / 2;
+    let (ox, oy) = (offset(0), offset(1));
+    for y in 0..size[1] {
+        for x in 0..size[0] {
+            let (sx, sy) = (x as isize + ox, y as isize + oy);
+            if (0..image.size[0] as isize).contains(&sx) && (0..image.size[1] as isize).contains(&sy) {
+                out.pixels[y * size[0] + x] = image.pixels[sy as usize * image.size[0] + sx as usize];
+            }
+        }
+    }
+    out
+}
+
+/// Blow every pixel up into a `scale` x `scale` block, optionally with a
+/// grid between the blocks (see [`draw_grid`]).
+pub fn magnify(image: &ColorImage, scale: u32, grid: bool) -> ColorImage {
+    let scale_px = scale.max(1) as usize;
+    let [w, h] = image.size;
+    let (out_w, out_h) = (w * scale_px, h * scale_px);
+    let mut out = ColorImage::filled([out_w, out_h], Color32::TRANSPARENT);
+    for y in 0..out_h {
+        for x in 0..out_w {
+            out.pixels[y * out_w + x] = image.pixels[(y / scale_px) * w + x / scale_px];
+        }
+    }
+    if grid {
+        draw_grid(&mut out, scale);
+    }
+    out
+}
+
+/// Mark the top and left edge of every `scale` x `scale` block with a
+/// translucent gray line, so magnified pixels can be counted. Does nothing
+/// below 2x, where the lines would cover everything.
+pub fn draw_grid(image: &mut ColorImage, scale: u32) {
+    let scale = scale as usize;
+    if scale < 2 {
+        return;
+    }
+    let line = ColorImage::filled([1, 1], Color32::from_rgba_unmultiplied(128, 128, 128, 96));
+    let [w, h] = image.size;
+    for y in 0..h {
+        for x in 0..w {
+            if x % scale == 0 || y % scale == 0 {
+                blend(image, &line, [x, y]);
+            }
+        }
+    }
+}
+
+/// Read a PNG into premultiplied colors.
+pub fn load_png(path: &Path) -> Result<ColorImage, String> {
+    let rgba = image::open(path)
+        .map_err(|e| format!("cannot read {}: {e}", path.display()))?
+        .into_rgba8();
+    let size = [rgba.width() as usize, rgba.height() as usize];
+    Ok(ColorImage::from_rgba_unmultiplied(size, rgba.as_raw()))
+}
+
+/// Write premultiplied `image` as a straight-alpha RGBA PNG.
+pub fn save_png(image: &ColorImage, path: &Path) -> Result<(), String> {
+    let bytes: Vec<u8> = image.pixels.iter().flat_map(|p| p.to_srgba_unmultiplied()).collect();
+    image::save_buffer_with_format(
+        path,
+        &bytes,
+        image.size[0] as u32,
+        image.size[1] as u32,
+        image::ExtendedColorType::Rgba8,
+        image::ImageFormat::Png,
+    )
+    .map_err(|e| format!("cannot write {}: {e}", path.display()))
+}
+
+/// Write `config` as an SVG file; see [`svg::to_svg`]. Returns the image size.
+pub fn save_svg(config: &Config, options: &RenderOptions, path: &Path) -> Result<[usize; 2], String> {
+    let background = match &options.background {
+        Background::Transparent => None,
+        Background::Color(color) => Some([color.r(), color.g(), color.b()]),
+        Background::Checkerboard | Background::Image(_) => {
+            return Err("only color backgrounds work for SVG output".into());
+        }
+    };
+    if options.grid {
+        return Err("--grid only works for PNG output".into());
+    }
+    let side = (config.window_size_px() * options.scale.max(1)) as usize;
+    std::fs::write(path, svg::to_svg(config, options.scale, background))
+        .map_err(|e| format!("cannot write {}: {e}", path.display()))?;
+    Ok([side, side])
+}
+
+/// Run `aimx render` and return the process exit code.
+pub fn run(args: RenderArgs) -> i32 {
+    crate::platform::current().attach_parent_console();
+
+    let config = match (&args.config, &args.profile) {
+        (Some(path), _) => Config::read(path),
+        (None, Some(name)) => Config::load_profile(name),
+        (None, None) => Ok(Config::load()),
+    };
+    let options = RenderOptions {
+        scale: args.scale,
+        grid: args.grid,
+        background: args.background,
+    };
+    let is_svg = args.out.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("svg"));
+    let result = config.and_then(|config| {
+        if is_svg {
+            save_svg(&config, &options, &args.out)
+        } else {
+            let image = render(&config, &options)?;
+            save_png(&image, &args.out).map(|_| image.size)
+        }
+    });
+    match result {
+        Ok([w, h]) => {
+            println!("wrote {}x{} image to {}", w, h, args.out.display());
+            0
+        }
+        Err(e) => {
+            eprintln!("aimx: {e}");
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_backgrounds() {
+        assert_eq!("transparent".parse(), Ok(Background::Transparent));
+        assert_eq!("checkerboard".parse(), Ok(Background::Checkerboard));
+        assert_eq!("#102030".parse(), Ok(Background::Color(Color32::from_rgb(16, 32, 48))));
+        assert_eq!("shot.png".parse(), Ok(Background::Image("shot.png".into())));
+        assert!("#12345".parse::<Background>().is_err());
+    }
+
+    #[test]
+    fn magnifies_and_composites() {
+        let config = Config::default();
+        let plain = render(&config, &RenderOptions::default()).unwrap();
+        let side = config.window_size_px() as usize;
+        assert_eq!(plain.size, [side, side]);
+        assert_eq!(plain.pixels[0], Color32::TRANSPARENT, "corners stay transparent");
+
+        let options = RenderOptions {
+            scale: 4,
+            background: Background::Color(Color32::from_rgb(32, 32, 32)),
+            ..Default::default()
+        };
+        let big = render(&config, &options).unwrap();
+        assert_eq!(big.size, [side * 4, side * 4]);
+        assert!(big.pixels.iter().all(|p| p.a() == 255), "opaque background");
+        let center = side / 2;
+        let block = |x: usize, y: usize| big.pixels[y * side * 4 + x];
+        assert_eq!(block(center * 4, center * 4), block(center * 4 + 3, center * 4 + 3));
+    }
+
+    #[test]
+    fn checkerboard_shows_through_transparent_pixels() {
+        let config = Config::default();
+        let options = RenderOptions {
+            scale: 2,
+            grid: true,
+            background: Background::Checkerboard,
+        };
+        let image = render(&config, &options).unwrap();
+        let side = config.window_size_px() as usize * 2;
+        assert_eq!(image.size, [side, side]);
+        assert!(image.pixels.iter().all(|p| p.a() == 255));
+        // Corner squares alternate, under the grid line on the first row and column
+        let at = |x: usize, y: usize| image.pixels[y * side + x];
+        assert_ne!(at(1, 1), at(CHECKER_SIZE + 1, 1));
+        assert_ne!(at(1, 1), at(2, 2), "grid line");
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let path = std::env::temp_dir().join(format!("aimx-export-{}.png", std::process::id()));
+        let image = render(&Config::default(), &RenderOptions::default()).unwrap();
+        save_png(&image, &path).unwrap();
+        let loaded = load_png(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(loaded.size, image.size);
+        // Unmultiplying and premultiplying again may round faint pixels by one step
+        for (a, b) in loaded.pixels.iter().zip(&image.pixels) {
+            assert!(a.to_array().iter().zip(b.to_array()).all(|(x, y)| x.abs_diff(y) <= 1), "{a:?} vs {b:?}");
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! Crosshairs as SVG, for editing in a vector tool and loading back.
+//!
+//! A crosshair is a filled `<circle id="dot">` and a stroked
+//! `<circle id="ring">`, centered on a canvas the size of the overlay window,
+//! in physical pixels. Parts the overlay wouldn't draw (zero radius or width)
+//! are left out. The crosshair model has no lines or paths, and its colors
+//! are opaque, so no `<line>`, `<path>` or opacity attributes are written.
+
+use crate::config::{self, Config};
+
+/// The crosshair as a standalone SVG document, `scale` times its pixel size.
+/// `background`, if any, fills the canvas behind it.
+pub fn to_svg(config: &Config, scale: u32, background: Option<[u8; 3]>) -> String {
+    let side = config.window_size_px();
+    let center = side as f32 / 2.0;
+    let display = side * scale.max(1);
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{display}\" height=\"{display}\" viewBox=\"0 0 {side} {side}\">\n"
+    );
+    if let Some(rgb) = background {
+        svg += &format!("  <rect id=\"background\" width=\"{side}\" height=\"{side}\" fill=\"{}\"/>\n", hex(rgb));
+    }
+    if config.inner_radius > 0.0 {
+        svg += &format!(
+            "  <circle id=\"dot\" cx=\"{center}\" cy=\"{center}\" r=\"{}\" fill=\"{}\"/>\n",
+            config.inner_radius,
+            hex(config.color)
+        );
+    }
+    if config.outer_radius > 0.0 && config.stroke_width > 0.0 {
+        svg += &format!(
+            "  <circle id=\"ring\" cx=\"{center}\" cy=\"{center}\" r=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\"/>\n",
+            config.outer_radius,
+            hex(config.stroke_color),
+            config.stroke_width
+        );
+    }
+    svg + "</svg>\n"
+}
+
+/// Read the crosshair back from an SVG written by [`to_svg`], possibly edited
+/// since. Settings SVG can't express, like offsets and hotkeys, come from `base`.
+/// A missing dot or ring reads as zero size.
+pub fn from_svg(svg: &str, base: &Config) -> Result<Config, String> {
+    let mut config = Config {
+        inner_radius: 0.0,
+        outer_radius: 0.0,
+        stroke_width: 0.0,
+        ..base.clone()
+    };
+    for element in svg.split('<').skip(1) {
+        let Some(circle) = element.strip_prefix("circle") else {
+            continue;
+        };
+        let attrs = attributes(circle.split('>').next().unwrap_or_default());
+        let get = |name: &str| attrs.iter().find(|(n, _)| *n == name).map(|(_, v)| *v);
+        let number = |name: &str| -> Result<f32, String> {
+            let value = get(name).ok_or_else(|| format!("<circle> without {name}"))?;
+            value
+                .trim_end_matches("px")
+                .parse()
+                .map_err(|_| format!("invalid {name} {value:?}"))
+        };
+        let color = |name: &str| -> Result<[u8; 3], String> {
+            let value = get(name).unwrap_or_default();
+            config::parse_hex_color(value).ok_or_else(|| format!("invalid {name} {value:?}, expected #rrggbb"))
+        };
+        for opacity in ["opacity", "fill-opacity", "stroke-opacity"] {
+            if get(opacity).is_some_and(|v| v.parse::<f32>() != Ok(1.0)) {
+                return Err(format!("{opacity} is not supported, crosshair colors are opaque"));
+            }
+        }
+
+        let ring = match get("id") {
+            Some("ring") => true,
+            Some("dot") => false,
+            _ => get("fill") == Some("none"),
+        };
+        if ring {
+            config.outer_radius = number("r")?;
+            config.stroke_width = number("stroke-width")?;
+            config.stroke_color = color("stroke")?;
+        } else {
+            config.inner_radius = number("r")?;
+            config.color = color("fill")?;
+        }
+    }
+    Ok(config)
+}
+
+fn hex([r, g, b]: [u8; 3]) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+/// `name="value"` pairs of one tag, single or double quoted.
+fn attributes(tag: &str) -> Vec<(&str, &str)> {
+    let mut attrs = Vec::new();
+    let mut rest = tag;
+    while let Some(eq) = rest.find('=') {
+        let name = rest[..eq].trim();
+        let value = rest[eq + 1..].trim_start();
+        let Some(quote) = value.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            break;
+        };
+        let Some(end) = value[1..].find(quote) else {
+            break;
+        };
+        attrs.push((name, &value[1..end + 1]));
+        rest = &value[end + 2..];
+    }
+    attrs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{RenderOptions, render};
+
+    fn config(inner: f32, outer: f32, width: f32) -> Config {
+        Config {
+            color: [0, 200, 255],
+            inner_radius: inner,
+            outer_radius: outer,
+            stroke_width: width,
+            stroke_color: [255, 255, 0],
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn writes_circles_with_exact_values() {
+        let svg = to_svg(&config(2.5, 7.5, 1.5), 4, Some([32, 32, 32]));
+        let side = config(2.5, 7.5, 1.5).window_size_px();
+        assert!(svg.contains(&format!("width=\"{}\"", side * 4)));
+        assert!(svg.contains("<rect id=\"background\""));
+        assert!(svg.contains("r=\"2.5\" fill=\"#00c8ff\""));
+        assert!(svg.contains("r=\"7.5\" fill=\"none\" stroke=\"#ffff00\" stroke-width=\"1.5\""));
+        assert!(!to_svg(&config(0.0, 7.5, 0.0), 1, None).contains("<circle"));
+    }
+
+    #[test]
+    fn round_trips_through_the_renderer() {
+        for original in [Config::default(), config(2.5, 7.5, 1.5), config(1.0, 0.0, 0.0), config(0.0, 12.0, 0.5)] {
+            let svg = to_svg(&original, 1, None);
+            let read = from_svg(&svg, &Config::default()).unwrap();
+            let expected = render(&original, &RenderOptions::default()).unwrap();
+            let actual = render(&read, &RenderOptions::default()).unwrap();
+            assert!(expected == actual, "{svg}");
+        }
+    }
+
+    #[test]
+    fn reads_edited_files() {
+        let svg = "<svg><circle fill='#ff0000' r='4px' cx='9' cy='9' />\n\
+                   <circle cx=\"9\" cy=\"9\" r=\"8\" fill=\"none\" stroke=\"#000000\" stroke-width=\"2\" stroke-opacity=\"1\"/></svg>";
+        let read = from_svg(svg, &Config::default()).unwrap();
+        assert_eq!((read.inner_radius, read.outer_radius, read.stroke_width), (4.0, 8.0, 2.0));
+        assert_eq!(read.color, [255, 0, 0]);
+
+        let faded = svg.replace("stroke-opacity=\"1\"", "stroke-opacity=\"0.5\"");
+        assert!(from_svg(&faded, &Config::default()).is_err());
+    }
+}
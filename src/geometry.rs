@@ -0,0 +1,206 @@
+//! Overlay placement across desktop scale factors.
+//!
+//! Config lengths (radii, stroke width, offsets) and monitor rectangles are
+//! physical pixels, so a crosshair covers the same pixels at 100% and 150%.
+//! Window-system and egui APIs take logical points; convert at that boundary
+//! with [`to_points`] and nowhere else.
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::platform::window::ClientArea;
+use crate::platform::{self, Monitor};
+
+/// Physical pixels to logical points at `scale` (pixels per point).
+pub fn to_points(px: f32, scale: f32) -> f32 {
+    px / scale
+}
+
+/// Logical points to physical pixels at `scale` (pixels per point).
+pub fn to_px(points: f32, scale: f32) -> f32 {
+    points * scale
+}
+
+/// Where an overlay window goes, in whole physical pixels of the virtual desktop.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OverlayRect {
+    pub x: i32,
+    pub y: i32,
+    /// Side length; the window is square
+    pub size: u32,
+}
+
+impl OverlayRect {
+    /// Square window sized for `config`'s crosshair, centered on `monitor`
+    /// plus the configured offset.
+    pub fn new(config: &Config, monitor: &Monitor) -> Self {
+        Self::centered_at(config, monitor.center())
+    }
+
+    /// Square window sized for `config`'s crosshair, centered on `(cx, cy)`
+    /// plus the configured offset.
+    pub fn centered_at(config: &Config, (cx, cy): (f32, f32)) -> Self {
+        let size = config.window_size_px();
+        // Whole pixels keep the crosshair's edges on the same pixels after a move
+        let x = (cx + config.offset_x - size as f32 / 2.0).round() as i32;
+        let y = (cy + config.offset_y - size as f32 / 2.0).round() as i32;
+        Self { x, y, size }
+    }
+
+    /// Top-left corner in logical points at `scale`.
+    pub fn position_points(&self, scale: f32) -> (f32, f32) {
+        (to_points(self.x as f32, scale), to_points(self.y as f32, scale))
+    }
+
+    /// Side length in logical points at `scale`.
+    pub fn size_points(&self, scale: f32) -> f32 {
+        to_points(self.size as f32, scale)
+    }
+}
+
+/// The monitor an overlay landed on and its window rect there.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Placement {
+    pub monitor: String,
+    pub scale: f32,
+    pub rect: OverlayRect,
+}
+
+impl Placement {
+    /// Place `config`'s crosshair on its target monitor, or the primary one
+    /// if that isn't connected. `None` only for an empty monitor list.
+    pub fn new(config: &Config, monitors: &[Monitor]) -> Option<Self> {
+        let monitor = platform::select_monitor(monitors, config.monitor.as_deref())?;
+        Some(Self {
+            monitor: monitor.name.clone(),
+            scale: monitor.scale,
+            rect: OverlayRect::new(config, monitor),
+        })
+    }
+
+    /// Place `config`'s crosshair on the center of a window's client area,
+    /// taking the scale of the monitor the window is mostly on.
+    pub fn on_window(config: &Config, area: &ClientArea, monitors: &[Monitor]) -> Self {
+        let (cx, cy) = area.center();
+        let contains = |m: &&Monitor| {
+            cx >= m.x as f32
+                && cy >= m.y as f32
+                && cx < (m.x + m.width as i32) as f32
+                && cy < (m.y + m.height as i32) as f32
+        };
+        let monitor = monitors
+            .iter()
+            .find(contains)
+            .or_else(|| platform::select_monitor(monitors, None));
+        Self {
+            monitor: monitor.map(|m| m.name.clone()).unwrap_or_default(),
+            scale: monitor.map_or(1.0, |m| m.scale),
+            rect: OverlayRect::centered_at(config, (cx, cy)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCALES: [f32; 5] = [1.0, 1.25, 1.5, 1.75, 2.0];
+
+    fn monitor(x: i32, scale: f32) -> Monitor {
+        Monitor {
+            name: "test".into(),
+            x,
+            y: 0,
+            width: 2560,
+            height: 1440,
+            scale,
+            primary: true,
+        }
+    }
+
+    #[test]
+    fn physical_size_does_not_depend_on_scale() {
+        let config = Config::default();
+        let base = OverlayRect::new(&config, &monitor(0, 1.0));
+        for scale in SCALES {
+            let rect = OverlayRect::new(&config, &monitor(0, scale));
+            assert_eq!(rect, base, "scale {scale}");
+            // Converting to points and back lands on the same pixels
+            assert!((to_px(rect.size_points(scale), scale) - rect.size as f32).abs() < 1e-3);
+            let (x, y) = rect.position_points(scale);
+            assert!((to_px(x, scale) - rect.x as f32).abs() < 1e-3);
+            assert!((to_px(y, scale) - rect.y as f32).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn centered_on_monitor_in_pixels() {
+        let config = Config::default();
+        let size = config.window_size_px() as i32;
+        for scale in SCALES {
+            let rect = OverlayRect::new(&config, &monitor(-2560, scale));
+            assert_eq!(rect.x + size / 2, -2560 + 1280, "scale {scale}");
+            assert_eq!(rect.y + size / 2, 720, "scale {scale}");
+        }
+    }
+
+    #[test]
+    fn offsets_are_pixels() {
+        let config = Config {
+            offset_x: 10.0,
+            offset_y: -3.0,
+            ..Config::default()
+        };
+        let centered = OverlayRect::new(&Config::default(), &monitor(0, 1.5));
+        let rect = OverlayRect::new(&config, &monitor(0, 1.5));
+        assert_eq!((rect.x - centered.x, rect.y - centered.y), (10, -3));
+    }
+
+    #[test]
+    fn unplugged_monitor_falls_back_to_primary() {
+        let config = Config {
+            monitor: Some("side".into()),
+            ..Config::default()
+        };
+        let side = Monitor {
+            name: "side".into(),
+            primary: false,
+            ..monitor(2560, 1.0)
+        };
+        let both = [monitor(0, 1.5), side];
+        assert_eq!(Placement::new(&config, &both).unwrap().monitor, "side");
+        let placement = Placement::new(&config, &both[..1]).unwrap();
+        assert_eq!(placement.monitor, "test");
+        assert_eq!(placement.scale, 1.5);
+    }
+
+    #[test]
+    fn anchored_to_window_client_area() {
+        let config = Config {
+            offset_x: 4.0,
+            ..Config::default()
+        };
+        let size = config.window_size_px() as i32;
+        // Left half of an ultrawide next to a 1440p monitor
+        let monitors = [monitor(0, 1.0), monitor(2560, 1.25)];
+        let area = ClientArea {
+            x: 2560,
+            y: 0,
+            width: 1720,
+            height: 1440,
+        };
+        let placement = Placement::on_window(&config, &area, &monitors);
+        assert_eq!(placement.scale, 1.25);
+        assert_eq!(placement.rect.x + size / 2, 2560 + 860 + 4);
+        assert_eq!(placement.rect.y + size / 2, 720);
+    }
+
+    #[test]
+    fn points_at_common_scales() {
+        assert_eq!(to_points(1920.0, 1.0), 1920.0);
+        assert_eq!(to_points(1920.0, 1.25), 1536.0);
+        assert_eq!(to_points(1920.0, 1.5), 1280.0);
+        assert_eq!(to_points(1920.0, 2.0), 960.0);
+        assert_eq!(to_px(1280.0, 1.5), 1920.0);
+    }
+}
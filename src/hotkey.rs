@@ -0,0 +1,392 @@
+//! Keyboard and mouse input the panel reacts to: global hotkey bindings,
+//! written like `Ctrl+Alt+H`, and the keys or buttons held for activation modes.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Something a global hotkey does in the panel.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Toggle,
+    NextProfile,
+    PreviousProfile,
+    NudgeLeft,
+    NudgeRight,
+    NudgeUp,
+    NudgeDown,
+    ResetOffset,
+}
+
+impl Action {
+    pub const ALL: [Action; 8] = [
+        Action::Toggle,
+        Action::NextProfile,
+        Action::PreviousProfile,
+        Action::NudgeLeft,
+        Action::NudgeRight,
+        Action::NudgeUp,
+        Action::NudgeDown,
+        Action::ResetOffset,
+    ];
+
+    /// Name used in the config file and `aimx ctl set hotkey_<name>=...`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Toggle => "toggle",
+            Action::NextProfile => "next_profile",
+            Action::PreviousProfile => "previous_profile",
+            Action::NudgeLeft => "nudge_left",
+            Action::NudgeRight => "nudge_right",
+            Action::NudgeUp => "nudge_up",
+            Action::NudgeDown => "nudge_down",
+            Action::ResetOffset => "reset_offset",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|a| a.name() == name)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Toggle => "Toggle overlay",
+            Action::NextProfile => "Next profile",
+            Action::PreviousProfile => "Previous profile",
+            Action::NudgeLeft => "Nudge left",
+            Action::NudgeRight => "Nudge right",
+            Action::NudgeUp => "Nudge up",
+            Action::NudgeDown => "Nudge down",
+            Action::ResetOffset => "Reset offset",
+        }
+    }
+
+    /// Offset change in physical pixels, for the nudge actions.
+    pub fn nudge(self) -> Option<(f32, f32)> {
+        match self {
+            Action::NudgeLeft => Some((-1.0, 0.0)),
+            Action::NudgeRight => Some((1.0, 0.0)),
+            Action::NudgeUp => Some((0.0, -1.0)),
+            Action::NudgeDown => Some((0.0, 1.0)),
+            _ => None,
+        }
+    }
+}
+
+/// A key that can be bound, independent of keyboard layout and platform.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Key {
+    /// `A` to `Z`
+    Letter(char),
+    /// `0` to `9` on the main row
+    Digit(u8),
+    /// `F1` to `F24`
+    F(u8),
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Delete,
+    Space,
+}
+
+impl Key {
+    const NAMED: [(Key, &'static str); 11] = [
+        (Key::Left, "Left"),
+        (Key::Right, "Right"),
+        (Key::Up, "Up"),
+        (Key::Down, "Down"),
+        (Key::Home, "Home"),
+        (Key::End, "End"),
+        (Key::PageUp, "PageUp"),
+        (Key::PageDown, "PageDown"),
+        (Key::Insert, "Insert"),
+        (Key::Delete, "Delete"),
+        (Key::Space, "Space"),
+    ];
+
+    /// Parse a key name like `H`, `7`, `F5` or `PageUp`, ignoring case.
+    /// Matches the names egui gives its keys.
+    pub fn from_name(s: &str) -> Option<Self> {
+        let mut chars = s.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return match c {
+                'a'..='z' | 'A'..='Z' => Some(Key::Letter(c.to_ascii_uppercase())),
+                '0'..='9' => Some(Key::Digit(c as u8 - b'0')),
+                _ => None,
+            };
+        }
+        if let Some(n) = s.strip_prefix(['F', 'f']).and_then(|n| n.parse().ok()) {
+            return (1..=24).contains(&n).then_some(Key::F(n));
+        }
+        Self::NAMED
+            .iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(s))
+            .map(|(key, _)| *key)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Key::Letter(c) => write!(f, "{c}"),
+            Key::Digit(d) => write!(f, "{d}"),
+            Key::F(n) => write!(f, "F{n}"),
+            other => {
+                let name = Self::NAMED.iter().find(|(k, _)| k == other).map_or("?", |(_, n)| n);
+                f.write_str(name)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    /// The Windows or Super key
+    pub logo: bool,
+}
+
+/// A key combination. Written `Ctrl+Alt+Shift+Super+Key`, modifiers in any
+/// order and case; serialized the same way.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub struct Hotkey {
+    pub modifiers: Modifiers,
+    pub key: Key,
+}
+
+impl FromStr for Hotkey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let mut modifiers = Modifiers::default();
+        let mut parts: Vec<&str> = s.split('+').map(str::trim).collect();
+        let key = parts.pop().filter(|k| !k.is_empty()).ok_or_else(|| format!("{s:?}: missing key"))?;
+        for part in parts {
+            let flag = match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => &mut modifiers.ctrl,
+                "alt" => &mut modifiers.alt,
+                "shift" => &mut modifiers.shift,
+                "super" | "win" | "meta" => &mut modifiers.logo,
+                _ => return Err(format!("{s:?}: unknown modifier {part:?}")),
+            };
+            *flag = true;
+        }
+        let key = Key::from_name(key).ok_or_else(|| format!("{s:?}: unknown key {key:?}"))?;
+        Self::new(modifiers, key)
+    }
+}
+
+impl Hotkey {
+    pub fn new(modifiers: Modifiers, key: Key) -> Result<Self, String> {
+        let hotkey = Self { modifiers, key };
+        // A bare letter would be taken from every other application
+        if modifiers == Modifiers::default() && !matches!(key, Key::F(_)) {
+            return Err(format!("{hotkey}: needs Ctrl, Alt, Shift or Super unless it is a function key"));
+        }
+        Ok(hotkey)
+    }
+}
+
+impl fmt::Display for Hotkey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let m = self.modifiers;
+        for (on, name) in [(m.ctrl, "Ctrl"), (m.alt, "Alt"), (m.shift, "Shift"), (m.logo, "Super")] {
+            if on {
+                write!(f, "{name}+")?;
+            }
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+impl TryFrom<String> for Hotkey {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, String> {
+        s.parse()
+    }
+}
+
+impl From<Hotkey> for String {
+    fn from(hotkey: Hotkey) -> Self {
+        hotkey.to_string()
+    }
+}
+
+/// A key or mouse button that is held down, for hold-to-show and similar modes.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub enum Trigger {
+    MouseLeft,
+    MouseRight,
+    MouseMiddle,
+    /// The back side button
+    Mouse4,
+    /// The forward side button
+    Mouse5,
+    /// Either Shift key
+    Shift,
+    Ctrl,
+    Alt,
+    Key(Key),
+}
+
+impl Trigger {
+    /// The mouse buttons and modifiers, which is what games usually aim with.
+    pub const COMMON: [Trigger; 8] = [
+        Trigger::MouseRight,
+        Trigger::MouseLeft,
+        Trigger::MouseMiddle,
+        Trigger::Mouse4,
+        Trigger::Mouse5,
+        Trigger::Shift,
+        Trigger::Ctrl,
+        Trigger::Alt,
+    ];
+
+    fn name(self) -> Option<&'static str> {
+        Some(match self {
+            Trigger::MouseLeft => "MouseLeft",
+            Trigger::MouseRight => "MouseRight",
+            Trigger::MouseMiddle => "MouseMiddle",
+            Trigger::Mouse4 => "Mouse4",
+            Trigger::Mouse5 => "Mouse5",
+            Trigger::Shift => "Shift",
+            Trigger::Ctrl => "Ctrl",
+            Trigger::Alt => "Alt",
+            Trigger::Key(_) => return None,
+        })
+    }
+}
+
+impl FromStr for Trigger {
+    type Err = String;
+
+    /// `MouseRight`, `Shift`, or any key name [`Key::from_name`] accepts.
+    fn from_str(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        Self::COMMON
+            .into_iter()
+            .find(|t| t.name().is_some_and(|n| n.eq_ignore_ascii_case(s)))
+            .or_else(|| Key::from_name(s).map(Trigger::Key))
+            .ok_or_else(|| format!("unknown key or mouse button {s:?}"))
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Trigger::Key(key) => write!(f, "{key}"),
+            other => f.write_str(other.name().unwrap_or_default()),
+        }
+    }
+}
+
+impl TryFrom<String> for Trigger {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, String> {
+        s.parse()
+    }
+}
+
+impl From<Trigger> for String {
+    fn from(trigger: Trigger) -> Self {
+        trigger.to_string()
+    }
+}
+
+/// Every bound action. Unbound actions are absent.
+pub type Bindings = BTreeMap<Action, Hotkey>;
+
+/// Toggle and profiles on Ctrl+Alt; nudges take Shift too, since plain
+/// Ctrl+Alt+arrows switch workspaces on many Linux desktops.
+pub fn default_bindings() -> Bindings {
+    [
+        (Action::Toggle, "Ctrl+Alt+H"),
+        (Action::NextProfile, "Ctrl+Alt+PageDown"),
+        (Action::PreviousProfile, "Ctrl+Alt+PageUp"),
+        (Action::NudgeLeft, "Ctrl+Alt+Shift+Left"),
+        (Action::NudgeRight, "Ctrl+Alt+Shift+Right"),
+        (Action::NudgeUp, "Ctrl+Alt+Shift+Up"),
+        (Action::NudgeDown, "Ctrl+Alt+Shift+Down"),
+        (Action::ResetOffset, "Ctrl+Alt+Home"),
+    ]
+    .into_iter()
+    .map(|(action, key)| (action, key.parse().expect("valid default hotkey")))
+    .collect()
+}
+
+pub fn is_default(bindings: &Bindings) -> bool {
+    *bindings == default_bindings()
+}
+
+/// Actions sharing a key combination with an earlier one, with an error for
+/// each. Only the first action keeps the combination.
+pub fn duplicates(bindings: &Bindings) -> Vec<(Action, String)> {
+    let mut errors = Vec::new();
+    for (i, (action, hotkey)) in bindings.iter().enumerate() {
+        if let Some((first, _)) = bindings.iter().take(i).find(|(_, h)| *h == hotkey) {
+            errors.push((*action, format!("{hotkey} is already bound to {}", first.label())));
+        }
+    }
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_prints() {
+        let hotkey: Hotkey = "alt + ctrl+pageup".parse().unwrap();
+        assert_eq!(hotkey.to_string(), "Ctrl+Alt+PageUp");
+        assert_eq!("Super+F13".parse::<Hotkey>().unwrap().key, Key::F(13));
+        assert_eq!("F9".parse::<Hotkey>().unwrap().to_string(), "F9");
+        for hotkey in default_bindings().values() {
+            assert_eq!(hotkey.to_string().parse::<Hotkey>().unwrap(), *hotkey);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_combinations() {
+        assert!("H".parse::<Hotkey>().unwrap_err().contains("needs Ctrl"));
+        assert!("Ctrl+Hyper+H".parse::<Hotkey>().unwrap_err().contains("unknown modifier"));
+        assert!("Ctrl+Enter".parse::<Hotkey>().unwrap_err().contains("unknown key"));
+        assert!("Ctrl+F25".parse::<Hotkey>().is_err());
+        assert!("Ctrl+".parse::<Hotkey>().is_err());
+    }
+
+    #[test]
+    fn parses_triggers() {
+        assert_eq!("mouseright".parse::<Trigger>(), Ok(Trigger::MouseRight));
+        assert_eq!("Shift".parse::<Trigger>(), Ok(Trigger::Shift));
+        assert_eq!("e".parse::<Trigger>(), Ok(Trigger::Key(Key::Letter('E'))));
+        assert!("Mouse9".parse::<Trigger>().is_err());
+        for trigger in Trigger::COMMON {
+            assert_eq!(trigger.to_string().parse::<Trigger>(), Ok(trigger));
+        }
+    }
+
+    #[test]
+    fn reports_duplicate_bindings() {
+        let mut bindings = default_bindings();
+        assert!(duplicates(&bindings).is_empty());
+        bindings.insert(Action::ResetOffset, "ctrl+alt+h".parse().unwrap());
+        let errors = duplicates(&bindings);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, Action::ResetOffset);
+        assert!(errors[0].1.contains("Toggle overlay"), "{}", errors[0].1);
+    }
+}
@@ -0,0 +1,138 @@
+use log::{info, warn};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::control::{self, Request, Response, SendError};
+
+/// How long a second launch keeps retrying while the first one is still starting up.
+const HANDOFF_ATTEMPTS: u32 = 15;
+const HANDOFF_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+fn lock_path() -> PathBuf {
+    control::runtime_dir().join("aimx.lock")
+}
+
+/// Per-user lock held by the primary instance for its lifetime.
+/// Removed on drop; a crash leaves it behind for the next launch to clean up.
+pub struct InstanceLock {
+    path: PathBuf,
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        if read_owner(&self.path) == Some(std::process::id()) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+pub enum Acquired {
+    /// We are the only instance. `None` if the lock could not be created at all,
+    /// in which case we run unguarded rather than refuse to start.
+    Primary(Option<InstanceLock>),
+    /// Another live instance holds the lock.
+    Secondary { pid: u32 },
+}
+
+fn read_owner(path: &std::path::Path) -> Option<u32> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Create the lock file with our pid already in it. The pid is written to a
+/// private file first and then hard-linked into place, so other launches never
+/// see a lock without an owner.
+fn try_create(path: &std::path::Path) -> std::io::Result<()> {
+    let pid = std::process::id();
+    let tmp = path.with_extension(format!("lock.{pid}"));
+    let result = std::fs::File::create(&tmp)
+        .and_then(|mut f| f.write_all(pid.to_string().as_bytes()))
+        .and_then(|_| std::fs::hard_link(&tmp, path));
+    let _ = std::fs::remove_file(&tmp);
+    result
+}
+
+pub fn acquire() -> Acquired {
+    let path = lock_path();
+    if let Err(e) = control::create_runtime_dir() {
+        warn!("cannot create runtime dir: {e}, running without instance lock");
+        return Acquired::Primary(None);
+    }
+
+    // Retry once after clearing a stale lock; more means something else is racing us
+    for _ in 0..2 {
+        match try_create(&path) {
+            Ok(()) => {
+                info!("acquired instance lock {}", path.display());
+                return Acquired::Primary(Some(InstanceLock { path }));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                let owner = read_owner(&path);
+                match owner {
+                    Some(pid) if pid != std::process::id() && crate::platform::current().process_alive(pid) => {
+                        return Acquired::Secondary { pid };
+                    }
+                    _ => {
+                        warn!("removing stale instance lock (owner {owner:?} is gone)");
+                        // Re-check so we don't delete a lock someone else just took
+                        if read_owner(&path) == owner {
+                            let _ = std::fs::remove_file(&path);
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                warn!("cannot create instance lock: {e}, running without it");
+                return Acquired::Primary(None);
+            }
+        }
+    }
+    warn!("could not take over instance lock, running without it");
+    Acquired::Primary(None)
+}
+
+/// What became of asking the running instance to come to the front.
+pub enum HandOff {
+    Done,
+    /// The instance answered but refused, or the connection broke
+    Failed,
+    /// Nothing answers on the control endpoint. The lock's pid may have been
+    /// reused by an unrelated process after a crash.
+    NoAnswer,
+}
+
+/// Ask the running instance to bring its panel to the front.
+pub fn hand_off(pid: u32) -> HandOff {
+    crate::platform::current().allow_set_foreground_window(pid);
+
+    for _ in 0..HANDOFF_ATTEMPTS {
+        match control::send(Request::Activate) {
+            Ok(Response::Rejected(reason)) => {
+                warn!("running instance rejected activation: {reason}");
+                return HandOff::Failed;
+            }
+            Ok(_) => {
+                info!("handed off to running instance (pid {pid})");
+                return HandOff::Done;
+            }
+            // The other instance may not have published its endpoint yet
+            Err(SendError::NotRunning) => std::thread::sleep(HANDOFF_RETRY_DELAY),
+            Err(SendError::Io(e)) => {
+                warn!("failed to hand off to running instance: {e}");
+                return HandOff::Failed;
+            }
+        }
+    }
+    warn!("running instance (pid {pid}) is not answering");
+    HandOff::NoAnswer
+}
+
+/// Remove a lock whose owner `pid` never answered a hand-off, then acquire again.
+pub fn take_over(pid: u32) -> Acquired {
+    let path = lock_path();
+    if read_owner(&path) == Some(pid) {
+        warn!("removing instance lock of unresponsive pid {pid}");
+        let _ = std::fs::remove_file(&path);
+    }
+    acquire()
+}
@@ -0,0 +1,11 @@
+pub mod config;
+pub mod control;
+pub mod crosshair;
+pub mod export;
+pub mod geometry;
+pub mod hotkey;
+pub mod instance;
+pub mod overlay;
+pub mod panel;
+pub mod platform;
+pub mod raster;
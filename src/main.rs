@@ -1,11 +1,6 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-mod config;
-mod crosshair;
-mod overlay;
-mod panel;
-mod platform;
-
+use aimx::{control, export, instance, overlay, panel};
 use clap::{Parser, Subcommand};
 use log::info;
 
@@ -18,36 +13,47 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Command {
-    /// Run the overlay process (used internally by the panel)
-    Overlay,
-}
-
-#[cfg(target_os = "windows")]
-fn set_app_user_model_id() {
-    unsafe extern "system" {
-        fn SetCurrentProcessExplicitAppUserModelID(app_id: *const u16) -> i32;
-    }
-    let id: Vec<u16> = "AIMX.Panel.1\0".encode_utf16().collect();
-    unsafe {
-        SetCurrentProcessExplicitAppUserModelID(id.as_ptr());
-    }
+    /// Run the overlay on its own, or as the panel's child process
+    Overlay(overlay::OverlayArgs),
+    /// Control the running instance, e.g. from launcher hotkeys
+    Ctl {
+        #[command(subcommand)]
+        command: control::CtlCommand,
+    },
+    /// Export a crosshair as a PNG image, without a display
+    Render(export::RenderArgs),
 }
 
-#[cfg(not(target_os = "windows"))]
-fn set_app_user_model_id() {}
-
 fn main() -> eframe::Result<()> {
     env_logger::init();
-    set_app_user_model_id();
+    aimx::platform::current().set_app_identity();
 
     let cli = Cli::parse();
 
     match cli.command {
-        Some(Command::Overlay) => {
+        Some(Command::Overlay(args)) => {
             info!("starting aimx in overlay mode");
-            overlay::run()
+            overlay::run(args)
         }
+        Some(Command::Ctl { command }) => std::process::exit(control::run(command)),
+        Some(Command::Render(args)) => std::process::exit(export::run(args)),
         None => {
+            let mut acquired = instance::acquire();
+            if let instance::Acquired::Secondary { pid } = acquired {
+                info!("aimx is already running (pid {pid}), activating it instead");
+                match instance::hand_off(pid) {
+                    instance::HandOff::Done => return Ok(()),
+                    instance::HandOff::Failed => std::process::exit(1),
+                    instance::HandOff::NoAnswer => acquired = instance::take_over(pid),
+                }
+            }
+            let _lock = match acquired {
+                instance::Acquired::Primary(lock) => lock,
+                instance::Acquired::Secondary { pid } => {
+                    info!("another launch (pid {pid}) took over meanwhile");
+                    return Ok(());
+                }
+            };
             info!("starting aimx in panel mode");
             panel::run()
         }
@@ -1,56 +1,303 @@
 use eframe::egui;
-use std::sync::{Arc, Mutex};
+use log::{info, warn};
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
 
-use crate::config::Config;
+use super::calibrate::{self, Calibration, LOUPE_SIZE, LOUPE_ZOOM};
+use super::{OverlayEvent, pattern};
+use super::source::Shared;
+use crate::geometry::{Placement, to_points};
+use crate::platform::{Monitor, Platform};
+use crate::raster::Renderer;
+
+/// How often the loupe's screen capture is refreshed
+const LOUPE_INTERVAL: Duration = Duration::from_millis(33);
+/// Behind the loupe and its instructions; not black, which Windows keys out
+const LOUPE_FILL: egui::Color32 = egui::Color32::from_rgb(24, 24, 24);
+/// Distance of the loupe from the monitor's corner, in points
+const LOUPE_MARGIN: f32 = 24.0;
+
+/// The window's top-left corner and size in physical pixels: the crosshair's
+/// square, or the whole monitor while the test pattern is on.
+type Area = (i32, i32, u32, u32);
+
+/// The overlay while it's being calibrated: the window covers the monitor
+/// the crosshair is on and takes pointer and keyboard input.
+struct Calibrating {
+    calibration: Calibration,
+    monitor: Monitor,
+    renderer: Renderer,
+    loupe: Option<egui::TextureHandle>,
+    /// Screen capture failed once already, so the warning isn't repeated every frame
+    capture_failed: bool,
+}
 
 pub struct OverlayApp {
-    config: Arc<Mutex<Config>>,
-    config_changed: Arc<std::sync::atomic::AtomicBool>,
-    screen_size: (f32, f32),
+    shared: Shared,
+    platform: Arc<dyn Platform>,
+    /// Last placement sent to the viewport, the window area it made and the
+    /// window scale it was converted at, so color-only edits don't resize or
+    /// move the window
+    placement: Option<(Placement, Area, f32)>,
+    /// False while an anchored window is minimized or missing
+    visible: bool,
+    /// Clear color the platform keys out or composites as transparent
+    background: egui::Color32,
+    calibrating: Option<Calibrating>,
 }
 
 impl OverlayApp {
-    pub fn new(
-        config: Arc<Mutex<Config>>,
-        config_changed: Arc<std::sync::atomic::AtomicBool>,
-        screen_size: (f32, f32),
-    ) -> Self {
+    pub fn new(shared: Shared, platform: Arc<dyn Platform>) -> Self {
+        shared.can_calibrate.store(true, Ordering::SeqCst);
         Self {
-            config,
-            config_changed,
-            screen_size,
+            shared,
+            background: platform.overlay_background(),
+            platform,
+            placement: None,
+            visible: true,
+            calibrating: None,
+        }
+    }
+
+    /// Cover the crosshair's monitor and stop passing input through.
+    fn start_calibration(&mut self, ctx: &egui::Context) {
+        let cfg = self.shared.current_config();
+        let monitor = self.shared.target(&cfg).and_then(|placement| self.shared.monitor(&placement.monitor));
+        let Some(monitor) = monitor else {
+            warn!("cannot calibrate while the crosshair is hidden");
+            self.shared.report(&OverlayEvent::Calibrated(None));
+            return;
+        };
+        info!("calibrating on {}", monitor.name);
+        let scale = ctx.input(|i| i.viewport().native_pixels_per_point).unwrap_or(monitor.scale);
+        let (x, y) = (to_points(monitor.x as f32, scale), to_points(monitor.y as f32, scale));
+        let (width, height) = (to_points(monitor.width as f32, scale), to_points(monitor.height as f32, scale));
+        ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(egui::pos2(x, y)));
+        ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(egui::vec2(width, height)));
+        ctx.send_viewport_cmd(egui::ViewportCommand::MousePassthrough(false));
+        ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+        self.platform.set_overlay_interactive(true);
+        self.calibrating = Some(Calibrating {
+            calibration: Calibration::new(&cfg),
+            monitor,
+            renderer: Renderer::new(),
+            loupe: None,
+            capture_failed: false,
+        });
+    }
+
+    /// Keep or revert the offsets and go back to a click-through crosshair window.
+    fn finish_calibration(&mut self, ctx: &egui::Context, confirm: bool) {
+        let Some(state) = self.calibrating.take() else {
+            return;
+        };
+        let (x, y) = state.calibration.finish(confirm);
+        if confirm {
+            let mut config = self.shared.config.lock().unwrap();
+            (config.offset_x, config.offset_y) = (x, y);
+        }
+        info!("calibration {}: offset {x}, {y}", if confirm { "confirmed" } else { "cancelled" });
+        self.shared.report(&OverlayEvent::Calibrated(confirm.then_some((x, y))));
+        ctx.send_viewport_cmd(egui::ViewportCommand::MousePassthrough(true));
+        self.platform.set_overlay_interactive(false);
+        // Shrink back around the crosshair
+        self.placement = None;
+        self.shared.changed.store(true, Ordering::SeqCst);
+        ctx.request_repaint();
+    }
+
+    /// One frame of calibration: drag and arrow-key input, the crosshair at
+    /// its calibrated spot and the loupe in the corner away from it.
+    fn calibrate(&mut self, ctx: &egui::Context) {
+        let Some(state) = self.calibrating.as_mut() else {
+            return;
+        };
+        let ppp = ctx.pixels_per_point();
+        let before = state.calibration.offset;
+        let (pressed, down, released) =
+            ctx.input(|i| (i.pointer.primary_pressed(), i.pointer.primary_down(), i.pointer.primary_released()));
+        if pressed {
+            state.calibration.begin_drag();
+        }
+        if down && let Some((origin, pos)) = ctx.input(|i| i.pointer.press_origin().zip(i.pointer.latest_pos())) {
+            state.calibration.drag((pos - origin) * ppp);
+        }
+        if released {
+            state.calibration.end_drag();
+        }
+        let step = if ctx.input(|i| i.modifiers.shift) { 10.0 } else { 1.0 };
+        for (key, dx, dy) in [
+            (egui::Key::ArrowLeft, -step, 0.0),
+            (egui::Key::ArrowRight, step, 0.0),
+            (egui::Key::ArrowUp, 0.0, -step),
+            (egui::Key::ArrowDown, 0.0, step),
+        ] {
+            if ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, key)) {
+                state.calibration.nudge(dx, dy);
+            }
+        }
+        let confirm = ctx.input(|i| {
+            if i.key_pressed(egui::Key::Enter) {
+                Some(true)
+            } else if i.key_pressed(egui::Key::Escape) {
+                Some(false)
+            } else {
+                None
+            }
+        });
+        let (x, y) = state.calibration.offset;
+        if (x, y) != before {
+            self.shared.report(&OverlayEvent::Calibrating(x, y));
+        }
+
+        let cfg = state.calibration.apply(&self.shared.current_config());
+        let Some(placement) = self.shared.target(&cfg) else {
+            warn!("crosshair hidden during calibration, cancelling");
+            return self.finish_calibration(ctx, false);
+        };
+        let rect = &placement.rect;
+        let monitor = &state.monitor;
+        let half = rect.size as f32 / 2.0;
+        let center = egui::pos2(
+            to_points((rect.x - monitor.x) as f32 + half, ppp),
+            to_points((rect.y - monitor.y) as f32 + half, ppp),
+        );
+
+        let origin = calibrate::loupe_origin(rect.size as usize) as i32;
+        let side = LOUPE_SIZE as u32;
+        let screen = self.platform.capture_screen(rect.x + origin, rect.y + origin, side, side);
+        if let Err(e) = &screen
+            && !std::mem::replace(&mut state.capture_failed, true)
+        {
+            warn!("{e}, the loupe shows a checkerboard instead");
+        }
+        let crosshair = state.renderer.render_crosshair(&cfg, placement.scale);
+        let image = calibrate::loupe(&crosshair, screen.as_ref().ok());
+        let texture = match &mut state.loupe {
+            Some(texture) => {
+                texture.set(image, egui::TextureOptions::NEAREST);
+                texture.clone()
+            }
+            None => state
+                .loupe
+                .insert(ctx.load_texture("calibration-loupe", image, egui::TextureOptions::NEAREST))
+                .clone(),
+        };
+
+        egui::CentralPanel::default()
+            .frame(egui::Frame::NONE.fill(self.background))
+            .show(ctx, |ui| crate::crosshair::draw(ui.painter(), center, &cfg));
+
+        // In the corner diagonally away from the crosshair
+        let area = egui::vec2(to_points(monitor.width as f32, ppp), to_points(monitor.height as f32, ppp));
+        let (right, below) = (center.x < area.x / 2.0, center.y < area.y / 2.0);
+        let corner = |far: bool, extent: f32| if far { extent - LOUPE_MARGIN } else { LOUPE_MARGIN };
+        let align = |far: bool| if far { egui::Align::Max } else { egui::Align::Min };
+        let size = to_points((LOUPE_SIZE * LOUPE_ZOOM as usize) as f32, ppp);
+        egui::Area::new(egui::Id::new("calibration-loupe"))
+            .fixed_pos(egui::pos2(corner(right, area.x), corner(below, area.y)))
+            .pivot(egui::Align2([align(right), align(below)]))
+            .show(ctx, |ui| {
+                egui::Frame::NONE.fill(LOUPE_FILL).inner_margin(8.0).show(ui, |ui| {
+                    ui.set_max_width(size);
+                    ui.image((texture.id(), egui::vec2(size, size)));
+                    ui.colored_label(egui::Color32::WHITE, format!("Offset {x}, {y} px"));
+                    ui.colored_label(
+                        egui::Color32::LIGHT_GRAY,
+                        "Drag or use the arrow keys, Shift for 10 px. Enter keeps, Esc cancels.",
+                    );
+                });
+            });
+        ctx.request_repaint_after(LOUPE_INTERVAL);
+
+        if let Some(confirm) = confirm {
+            self.finish_calibration(ctx, confirm);
         }
     }
 }
 
 impl eframe::App for OverlayApp {
     fn clear_color(&self, _visuals: &egui::Visuals) -> [f32; 4] {
-        [0.0, 0.0, 0.0, 1.0]
+        self.background.to_normalized_gamma_f32()
     }
 
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        let cfg = self.config.lock().unwrap().clone();
+        match self.shared.take_calibration_request() {
+            Some(true) if self.calibrating.is_none() => self.start_calibration(ctx),
+            Some(false) => self.finish_calibration(ctx, false),
+            _ => {}
+        }
+        if self.calibrating.is_some() {
+            return self.calibrate(ctx);
+        }
+        let cfg = self.shared.current_config();
+
+        // Window commands are in points at the window's current scale, which
+        // changes when it lands on a monitor with a different DPI
+        let scale = ctx.input(|i| i.viewport().native_pixels_per_point).unwrap_or(1.0);
+        let rescaled = self.placement.as_ref().is_some_and(|(_, _, s)| *s != scale);
 
-        if self.config_changed.swap(false, std::sync::atomic::Ordering::SeqCst) {
-            let win_size = cfg.window_size();
+        // Several updates may land between frames; only the latest is applied
+        let changed = self.shared.changed.swap(false, Ordering::SeqCst);
+        let pattern = self.shared.pattern.load(Ordering::SeqCst);
+        if changed || rescaled {
+            let target = self.shared.target(&cfg);
+            if target.is_none() && self.visible {
+                info!("anchored window is minimized or gone, hiding crosshair");
+                self.shared.report(&OverlayEvent::Hidden);
+                // Force a full re-place once it comes back
+                self.placement = None;
+            }
+            self.visible = target.is_some();
 
-            ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(
-                egui::vec2(win_size, win_size),
-            ));
+            if let Some(placement) = target {
+                let monitor = self.shared.monitor(&placement.monitor).filter(|_| pattern);
+                let rect = placement.rect;
+                let area = match &monitor {
+                    Some(m) => (m.x, m.y, m.width, m.height),
+                    None => (rect.x, rect.y, rect.size, rect.size),
+                };
+                let prev = self.placement.replace((placement.clone(), area, scale));
 
-            let x = (self.screen_size.0 - win_size) / 2.0 + cfg.offset_x;
-            let y = (self.screen_size.1 - win_size) / 2.0 + cfg.offset_y;
-            ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(
-                egui::pos2(x, y).to_vec2().to_pos2(),
-            ));
+                if prev.as_ref().is_none_or(|(p, _, _)| *p != placement) {
+                    info!("overlay placed on {}: {:?}", placement.monitor, rect);
+                    self.shared.report(&OverlayEvent::Placed(placement));
+                }
+                let (x, y, width, height) = area;
+                if prev.as_ref().is_none_or(|(_, a, s)| (a.2, a.3) != (width, height) || *s != scale) {
+                    let size = egui::vec2(to_points(width as f32, scale), to_points(height as f32, scale));
+                    ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(size));
+                }
+                if prev.as_ref().is_none_or(|(_, a, s)| (a.0, a.1) != (x, y) || *s != scale) {
+                    let position = egui::pos2(to_points(x as f32, scale), to_points(y as f32, scale));
+                    ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(position));
+                }
+            }
         }
 
         egui::CentralPanel::default()
-            .frame(egui::Frame::NONE.fill(egui::Color32::BLACK))
+            .frame(egui::Frame::NONE.fill(self.background))
             .show(ctx, |ui| {
-                let painter = ui.painter();
-                let center = ui.max_rect().center();
-                crate::crosshair::draw(painter, center, &cfg);
+                let Some((placement, (x, y, width, height), _)) = &self.placement else {
+                    return;
+                };
+                if !self.visible {
+                    return;
+                }
+                let ppp = ui.ctx().pixels_per_point();
+                let half = placement.rect.size as f32 / 2.0;
+                let (cx, cy) = ((placement.rect.x - x) as f32 + half, (placement.rect.y - y) as f32 + half);
+                if pattern {
+                    pattern::draw(ui.painter(), [*width, *height], (cx, cy));
+                }
+                // The show rule hides it for the focused window, or the
+                // activation trigger is (not) held; the pattern overrides both
+                if !pattern && (!self.shared.shown.load(Ordering::SeqCst) || !self.shared.activated()) {
+                    return;
+                }
+                let center = egui::pos2(to_points(cx, ppp), to_points(cy, ppp));
+                crate::crosshair::draw(ui.painter(), center, &cfg);
             });
     }
 }
@@ -0,0 +1,149 @@
+//! Calibration: the overlay stops passing the mouse through so the crosshair
+//! can be dragged or nudged onto a game's own reticle, with a loupe showing
+//! the pixels around its center.
+
+use eframe::egui::{ColorImage, Vec2};
+
+use crate::config::{Config, OFFSET_RANGE, clamp_to};
+use crate::export::{self, Background, RenderOptions};
+
+/// Side of the square the loupe magnifies, in physical pixels
+pub const LOUPE_SIZE: usize = 25;
+pub const LOUPE_ZOOM: u32 = 8;
+
+/// Offsets being calibrated, in physical pixels like the config's.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Calibration {
+    /// Offsets when calibration started, kept on cancel
+    original: (f32, f32),
+    pub offset: (f32, f32),
+    /// The offset when the current drag started
+    drag_start: Option<(f32, f32)>,
+}
+
+impl Calibration {
+    pub fn new(config: &Config) -> Self {
+        let original = (config.offset_x, config.offset_y);
+        Self {
+            original,
+            offset: original,
+            drag_start: None,
+        }
+    }
+
+    /// `config` with the calibrated offsets.
+    pub fn apply(&self, config: &Config) -> Config {
+        Config {
+            offset_x: self.offset.0,
+            offset_y: self.offset.1,
+            ..config.clone()
+        }
+    }
+
+    pub fn begin_drag(&mut self) {
+        self.drag_start = Some(self.offset);
+    }
+
+    /// Follow a drag that has moved `total` physical pixels since
+    /// [`Calibration::begin_drag`], snapped to whole pixels.
+    pub fn drag(&mut self, total: Vec2) {
+        if let Some((x, y)) = self.drag_start {
+            self.offset = (clamp(x + total.x.round()), clamp(y + total.y.round()));
+        }
+    }
+
+    pub fn end_drag(&mut self) {
+        self.drag_start = None;
+    }
+
+    /// Move by `(dx, dy)` pixels, as the arrow keys do.
+    pub fn nudge(&mut self, dx: f32, dy: f32) {
+        self.offset = (clamp(self.offset.0 + dx), clamp(self.offset.1 + dy));
+    }
+
+    /// The offsets to keep: the calibrated ones when confirmed, the original ones when cancelled.
+    pub fn finish(&self, confirm: bool) -> (f32, f32) {
+        if confirm { self.offset } else { self.original }
+    }
+}
+
+fn clamp(offset: f32) -> f32 {
+    clamp_to(offset, OFFSET_RANGE)
+}
+
+/// Where the loupe's square starts inside a crosshair window of `side`
+/// pixels, so the screen capture can be taken at the same spot.
+pub fn loupe_origin(side: usize) -> isize {
+    side as isize / 2 - LOUPE_SIZE as isize / 2
+}
+
+/// The middle of `crosshair` drawn over `screen`, a [`LOUPE_SIZE`] capture
+/// taken at [`loupe_origin`], magnified with a pixel grid. Without a capture
+/// the crosshair is shown on a checkerboard.
+pub fn loupe(crosshair: &ColorImage, screen: Option<&ColorImage>) -> ColorImage {
+    let middle = export::crop_center(crosshair, [LOUPE_SIZE, LOUPE_SIZE]);
+    match screen {
+        Some(screen) => {
+            let mut image = screen.clone();
+            export::blend(&mut image, &middle, [0, 0]);
+            export::magnify(&image, LOUPE_ZOOM, true)
+        }
+        None => {
+            let options = RenderOptions {
+                scale: LOUPE_ZOOM,
+                grid: true,
+                background: Background::Checkerboard,
+            };
+            export::compose(&middle, &options, None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raster::Renderer;
+    use eframe::egui::{Color32, vec2};
+
+    #[test]
+    fn confirm_keeps_and_cancel_reverts() {
+        let config = Config {
+            offset_x: 3.0,
+            offset_y: -2.0,
+            ..Config::default()
+        };
+        let mut calibration = Calibration::new(&config);
+        calibration.begin_drag();
+        calibration.drag(vec2(4.4, 1.6));
+        calibration.drag(vec2(5.2, -0.4));
+        calibration.end_drag();
+        assert_eq!(calibration.offset, (8.0, -2.0), "whole pixels from where the drag began");
+        calibration.nudge(0.0, 10.0);
+        calibration.drag(vec2(100.0, 0.0));
+        assert_eq!(calibration.offset, (8.0, 8.0), "no drag in progress");
+        calibration.nudge(-1000.0, 0.0);
+        assert_eq!(calibration.offset.0, *OFFSET_RANGE.start());
+
+        assert_eq!(calibration.finish(true), calibration.offset);
+        assert_eq!(calibration.finish(false), (3.0, -2.0));
+        let applied = calibration.apply(&config);
+        assert_eq!((applied.offset_x, applied.offset_y), calibration.offset);
+    }
+
+    #[test]
+    fn loupe_magnifies_the_center_over_the_screen() {
+        let config = Config::default();
+        let crosshair = Renderer::new().render_crosshair(&config, 1.0);
+        let screen = ColorImage::filled([LOUPE_SIZE, LOUPE_SIZE], Color32::from_rgb(0, 0, 255));
+        let image = loupe(&crosshair, Some(&screen));
+        let side = LOUPE_SIZE * LOUPE_ZOOM as usize;
+        assert_eq!(image.size, [side, side]);
+
+        // The dot sits in the middle, the capture shows in the corners
+        let at = |x: usize, y: usize| image.pixels[y * side + x];
+        let [r, g, b] = config.color;
+        assert_eq!(at(side / 2 + 1, side / 2 + 1), Color32::from_rgb(r, g, b));
+        assert_eq!(at(1, 1), Color32::from_rgb(0, 0, 255));
+        assert_ne!(loupe(&crosshair, None).pixels[1], Color32::from_rgb(0, 0, 255));
+    }
+}
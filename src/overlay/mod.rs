@@ -1,60 +1,197 @@
 mod app;
+mod calibrate;
+mod pattern;
+mod software;
+mod source;
+#[cfg(target_os = "linux")]
+mod wayland;
 
 use eframe::egui;
-use log::info;
-use std::sync::{Arc, Mutex};
+use log::{error, info, warn};
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
 
-use crate::config::Config;
+use crate::config::{self, Config};
+use crate::geometry::Placement;
 use app::OverlayApp;
 
-pub fn run() -> eframe::Result<()> {
-    crate::platform::set_app_user_model_id();
-    let config = Arc::new(Mutex::new(Config::load()));
-    let config_changed = Arc::new(std::sync::atomic::AtomicBool::new(true));
-    let (sw, sh) = crate::platform::screen_size();
-    info!("screen size: {sw}x{sh}");
-
-    let cfg = config.lock().unwrap().clone();
-    let win_size = cfg.window_size();
-    let start_x = (sw - win_size) / 2.0 + cfg.offset_x;
-    let start_y = (sh - win_size) / 2.0 + cfg.offset_y;
-    info!("overlay window: pos=({start_x}, {start_y}), size={win_size}x{win_size}");
-
-    // Shared egui context so the stdin reader can wake the overlay
-    let repaint_ctx: Arc<Mutex<Option<egui::Context>>> = Arc::new(Mutex::new(None));
-
-    // Background thread reads config updates from stdin
-    let config_reader = config.clone();
-    let changed_writer = config_changed.clone();
-    let repaint_ctx_reader = repaint_ctx.clone();
-    std::thread::spawn(move || {
-        use log::{debug, warn};
-        use std::io::BufRead;
-
-        let stdin = std::io::stdin();
-        for line in stdin.lock().lines() {
-            match line {
-                Ok(line) if !line.is_empty() => {
-                    match serde_json::from_str::<Config>(&line) {
-                        Ok(cfg) => {
-                            debug!("received config update via stdin");
-                            *config_reader.lock().unwrap() = cfg;
-                            changed_writer.store(true, std::sync::atomic::Ordering::SeqCst);
-                            if let Some(ctx) = repaint_ctx_reader.lock().unwrap().as_ref() {
-                                ctx.request_repaint();
-                            }
-                        }
-                        Err(e) => warn!("bad config from stdin: {e}"),
-                    }
-                }
-                Err(e) => {
-                    info!("stdin closed ({e}), overlay will stop receiving updates");
-                    break;
-                }
-                _ => {}
+/// Messages from an overlay to the panel, one JSON object per line on stdout.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub enum OverlayEvent {
+    /// The overlay moved to a new monitor, position or size
+    Placed(Placement),
+    /// The anchored window is minimized or not running, so nothing is drawn
+    Hidden,
+    /// Calibration moved the crosshair to these offsets, not yet committed
+    Calibrating(f32, f32),
+    /// Calibration ended with these offsets confirmed, or `None` when it was
+    /// cancelled or could not start
+    Calibrated(Option<(f32, f32)>),
+}
+
+/// Messages from the panel to an overlay, one JSON object per line on stdin.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub enum OverlayCommand {
+    /// Replace the whole config
+    Config(Box<Config>),
+    /// The config's activation trigger was pressed or released
+    Held(bool),
+    /// Start calibration, or cancel it with `false`
+    Calibrate(bool),
+    /// Show or hide the alignment test pattern
+    Pattern(bool),
+}
+
+/// How the overlay draws its crosshair.
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+pub enum RendererKind {
+    /// OpenGL, falling back to software if no GL context can be created
+    #[default]
+    Auto,
+    /// OpenGL only
+    Gl,
+    /// CPU rendering onto a layered or ARGB window
+    Software,
+}
+
+#[derive(clap::Args)]
+pub struct OverlayArgs {
+    /// Config file to load instead of aimx_config.json
+    #[arg(long, value_name = "PATH", conflicts_with = "profile")]
+    pub config: Option<PathBuf>,
+    /// Saved profile to load
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+    /// Reload the config whenever its file changes
+    #[arg(long)]
+    pub watch: bool,
+    /// Receive config updates and trigger state as JSON lines on stdin (used by the panel)
+    #[arg(long)]
+    pub stdin: bool,
+    /// How to draw the crosshair [default: $AIMX_RENDERER or auto]. The
+    /// Wayland layer-shell overlay always renders in software
+    #[arg(long, value_enum)]
+    pub renderer: Option<RendererKind>,
+    /// Start with the alignment test pattern across the monitor: center
+    /// lines, pixel rulers and the crosshair's offset from the true center
+    #[arg(long)]
+    pub pattern: bool,
+}
+
+impl OverlayArgs {
+    /// The config file this overlay reads from.
+    fn config_path(&self) -> Result<PathBuf, String> {
+        match (&self.config, &self.profile) {
+            (Some(path), _) => Ok(path.clone()),
+            (None, Some(name)) => config::profile_path(name),
+            (None, None) => Ok(config::config_path()),
+        }
+    }
+
+    /// The renderer from `--renderer`, else `AIMX_RENDERER`, else [`RendererKind::Auto`].
+    fn renderer(&self) -> RendererKind {
+        use clap::ValueEnum;
+        self.renderer.unwrap_or_else(|| match std::env::var("AIMX_RENDERER") {
+            Ok(name) => RendererKind::from_str(&name, true).unwrap_or_else(|_| {
+                warn!("unknown AIMX_RENDERER {name:?}, using auto");
+                RendererKind::Auto
+            }),
+            Err(_) => RendererKind::Auto,
+        })
+    }
+}
+
+/// Draw with the CPU renderer until the process is stopped.
+fn run_software(shared: source::Shared, platform: &dyn crate::platform::Platform) -> ! {
+    match platform.software_surface() {
+        Ok(surface) => software::SoftwareOverlay::new().run(shared, surface),
+        Err(e) => {
+            error!("cannot create software overlay window: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+pub fn run(args: OverlayArgs) -> eframe::Result<()> {
+    let path = match args.config_path() {
+        Ok(p) => p,
+        Err(e) => {
+            error!("{e}");
+            std::process::exit(1);
+        }
+    };
+    // An explicitly requested profile must exist; a plain config file may not yet
+    let initial = if args.profile.is_some() {
+        Config::read(&path).unwrap_or_else(|e| {
+            error!("{e}");
+            std::process::exit(1);
+        })
+    } else {
+        Config::load_from(&path)
+    };
+
+    // Under Wayland, prefer a layer-shell surface. Connect before any threads
+    // start so a failed attempt can still steer winit to X11/XWayland.
+    #[cfg(target_os = "linux")]
+    let wayland = if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        match wayland::WaylandOverlay::connect(initial.clone()) {
+            Ok(overlay) => Some(overlay),
+            Err(e) => {
+                warn!("{e}, falling back to X11");
+                // SAFETY: no other threads exist yet
+                unsafe { std::env::remove_var("WAYLAND_DISPLAY") };
+                None
             }
         }
-    });
+    } else {
+        None
+    };
+
+    // Only the panel reads stdout; standalone overlays keep it quiet
+    let platform = crate::platform::current();
+    let shared = source::Shared::new(initial, platform.monitors(), args.stdin);
+    shared.set_pattern(args.pattern);
+    if args.stdin {
+        source::spawn_stdin_reader(shared.clone());
+    }
+    if args.watch {
+        source::spawn_file_watcher(shared.clone(), path);
+    }
+    source::install_signal_handler(shared.clone());
+
+    #[cfg(target_os = "linux")]
+    if let Some(overlay) = wayland {
+        info!("using Wayland layer-shell overlay");
+        if let Err(e) = overlay.run(shared) {
+            error!("{e}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    source::spawn_display_watcher(shared.clone(), platform.clone());
+    source::spawn_window_tracker(shared.clone(), platform.window_finder());
+    let renderer = args.renderer();
+    if renderer == RendererKind::Software {
+        run_software(shared, platform.as_ref());
+    }
+    let monitors = shared.monitors.lock().unwrap().clone();
+    let cfg = shared.config.lock().unwrap().clone();
+    let monitor = crate::platform::select_monitor(&monitors, cfg.monitor.as_deref())
+        .expect("monitor list is never empty");
+    info!(
+        "target monitor {}: {}x{} at ({}, {})",
+        monitor.name, monitor.width, monitor.height, monitor.x, monitor.y
+    );
+
+    // The window is created on the target monitor, so its scale converts to points
+    let rect = crate::geometry::OverlayRect::new(&cfg, monitor);
+    let (start_x, start_y) = rect.position_points(monitor.scale);
+    let win_size = rect.size_points(monitor.scale);
+    info!(
+        "overlay window: {}px at ({}, {}) px, scale {}",
+        rect.size, rect.x, rect.y, monitor.scale
+    );
 
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
@@ -68,13 +205,23 @@ pub fn run() -> eframe::Result<()> {
         ..Default::default()
     };
 
-    eframe::run_native(
+    let (gl_shared, gl_platform) = (shared.clone(), platform.clone());
+    let result = eframe::run_native(
         "AIMX Overlay",
         options,
         Box::new(move |cc| {
-            crate::platform::apply_overlay_style(cc);
-            *repaint_ctx.lock().unwrap() = Some(cc.egui_ctx.clone());
-            Ok(Box::new(OverlayApp::new(config, config_changed, (sw, sh))))
+            gl_platform.apply_overlay_style(cc)?;
+            *gl_shared.repaint_ctx.lock().unwrap() = Some(cc.egui_ctx.clone());
+            Ok(Box::new(OverlayApp::new(gl_shared, gl_platform)))
         }),
-    )
+    );
+    match result {
+        Err(e) if renderer == RendererKind::Auto && shared.repaint_ctx.lock().unwrap().is_none() => {
+            warn!("{e}, falling back to the software renderer");
+            // The GL window never came up, so the first frame must place the surface
+            shared.changed.store(true, Ordering::SeqCst);
+            run_software(shared, platform.as_ref())
+        }
+        result => result,
+    }
 }
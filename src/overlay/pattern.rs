@@ -0,0 +1,161 @@
+//! The alignment test pattern: center lines across the whole monitor, pixel
+//! rulers around its center and a readout of how far the crosshair sits from
+//! it. Everything is laid out in physical pixels so lines stay crisp at any
+//! scale, and [`render`] draws it without a window for the software overlay
+//! and tests.
+
+use eframe::egui::{self, Color32, ColorImage, Painter, pos2, vec2};
+
+use crate::config::Config;
+use crate::geometry::{Placement, to_points};
+use crate::platform::Monitor;
+use crate::raster::Renderer;
+
+/// Center lines and ruler ticks
+const LINE: Color32 = Color32::from_rgb(255, 0, 255);
+/// Behind the readout; not black, which Windows keys out
+const READOUT_FILL: Color32 = Color32::from_rgb(24, 24, 24);
+/// How far the rulers reach from the center line, in physical pixels
+pub const RULER_LENGTH: u32 = 50;
+/// A tick every `TICK_STEP` pixels, and a longer, labelled one every `LABEL_STEP`
+const TICK_STEP: u32 = 5;
+const LABEL_STEP: u32 = 25;
+const TICK: u32 = 3;
+const LONG_TICK: u32 = 7;
+
+/// First pixel and width of the middle of `len` pixels: the center pixel, or
+/// the two either side of the center of an even length.
+pub fn middle(len: u32) -> (u32, u32) {
+    if len % 2 == 1 { (len / 2, 1) } else { (len.saturating_sub(2) / 2, 2) }
+}
+
+/// The crosshair's center in physical pixels from the top-left of `monitor`.
+pub fn crosshair_center(placement: &Placement, monitor: &Monitor) -> (f32, f32) {
+    let rect = &placement.rect;
+    let half = rect.size as f32 / 2.0;
+    ((rect.x - monitor.x) as f32 + half, (rect.y - monitor.y) as f32 + half)
+}
+
+/// How far `crosshair` is from the true center of a monitor of `size` pixels.
+pub fn readout(size: [u32; 2], crosshair: (f32, f32)) -> String {
+    // Adding zero turns -0 into 0, so a centered crosshair reads +0
+    let dx = crosshair.0 - size[0] as f32 / 2.0 + 0.0;
+    let dy = crosshair.1 - size[1] as f32 / 2.0 + 0.0;
+    format!("Offset from monitor center: x {dx:+} px, y {dy:+} px")
+}
+
+/// Draw the pattern for a monitor of `size` physical pixels whose top-left
+/// corner is the painter's origin, with the crosshair centered `crosshair`
+/// pixels from that corner. Tick `k` marks the `k`th pixel out from the center line.
+pub fn draw(painter: &Painter, size: [u32; 2], crosshair: (f32, f32)) {
+    let ppp = painter.pixels_per_point();
+    let pt = |px: u32| to_points(px as f32, ppp);
+    let fill = |x: u32, y: u32, w: u32, h: u32| {
+        let rect = egui::Rect::from_min_size(pos2(pt(x), pt(y)), vec2(pt(w), pt(h)));
+        painter.rect_filled(rect, 0.0, LINE);
+    };
+    let [width, height] = size;
+    let (mx, mw) = middle(width);
+    let (my, mh) = middle(height);
+    fill(mx, 0, mw, height);
+    fill(0, my, width, mh);
+
+    let font = egui::FontId::monospace(10.0);
+    for d in (TICK_STEP..=RULER_LENGTH).step_by(TICK_STEP as usize) {
+        let long = d % LABEL_STEP == 0;
+        let tick = if long { LONG_TICK } else { TICK };
+        // Left and right of the vertical line, above and below the horizontal one
+        for x in [mx.checked_sub(d), Some(mx + mw - 1 + d)].into_iter().flatten() {
+            fill(x, (my + mh / 2).saturating_sub(tick), 1, tick * 2);
+            if long {
+                let at = pos2(pt(x) + pt(1) / 2.0, pt(my + mh / 2 + tick + 2));
+                painter.text(at, egui::Align2::CENTER_TOP, d.to_string(), font.clone(), LINE);
+            }
+        }
+        for y in [my.checked_sub(d), Some(my + mh - 1 + d)].into_iter().flatten() {
+            fill((mx + mw / 2).saturating_sub(tick), y, tick * 2, 1);
+            if long {
+                let at = pos2(pt(mx + mw / 2 + tick + 2), pt(y) + pt(1) / 2.0);
+                painter.text(at, egui::Align2::LEFT_CENTER, d.to_string(), font.clone(), LINE);
+            }
+        }
+    }
+
+    let galley = painter.layout_no_wrap(
+        readout(size, crosshair),
+        egui::FontId::proportional(14.0),
+        Color32::WHITE,
+    );
+    let corner = pos2(pt(mx + mw + RULER_LENGTH + 16), pt(my + mh + RULER_LENGTH + 16));
+    let frame = egui::Rect::from_min_size(corner, galley.size()).expand(4.0);
+    painter.rect_filled(frame, 2.0, READOUT_FILL);
+    painter.galley(corner, galley, Color32::WHITE);
+}
+
+/// The pattern and the crosshair on a transparent canvas the size of the monitor.
+pub fn render(
+    renderer: &mut Renderer,
+    size: [u32; 2],
+    crosshair: (f32, f32),
+    config: &Config,
+    pixels_per_point: f32,
+) -> ColorImage {
+    let canvas = [size[0] as usize, size[1] as usize];
+    renderer.render(canvas, pixels_per_point, Color32::TRANSPARENT, |painter| {
+        draw(painter, size, crosshair);
+        let center = pos2(to_points(crosshair.0, pixels_per_point), to_points(crosshair.1, pixels_per_point));
+        crate::crosshair::draw(painter, center, config);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns(image: &ColorImage, y: usize) -> Vec<usize> {
+        (0..image.size[0])
+            .filter(|&x| image.pixels[y * image.size[0] + x] == LINE)
+            .collect()
+    }
+
+    #[test]
+    fn center_lines_are_crisp_and_symmetric() {
+        let config = Config::default();
+        for (size, ppp, expected) in [([101, 161], 1.0, vec![50]), ([100, 160], 1.0, vec![49, 50]), ([100, 160], 1.25, vec![49, 50])] {
+            let image = render(&mut Renderer::new(), size, (size[0] as f32 / 2.0, 30.0), &config, ppp);
+            assert_eq!(image.size, [size[0] as usize, size[1] as usize]);
+            // Top row, past the rulers: only the vertical line at full strength
+            assert_eq!(columns(&image, 0), expected, "{size:?} at {ppp}");
+            assert_eq!(image.pixels[expected[0] - 1].a(), 0);
+        }
+    }
+
+    #[test]
+    fn rulers_tick_every_few_pixels_near_the_center() {
+        let image = render(&mut Renderer::new(), [301, 201], (150.5, 100.5), &Config::default(), 1.0);
+        // Just above the horizontal line, clear of the crosshair
+        let row = columns(&image, 99);
+        assert!(row.contains(&(150 + RULER_LENGTH as usize)));
+        assert!(row.contains(&(150 - TICK_STEP as usize)));
+        assert!(!row.contains(&(150 + TICK_STEP as usize + 1)));
+        assert!(!row.contains(&(150 + RULER_LENGTH as usize + TICK_STEP as usize)));
+        // Long ticks reach further out
+        assert!(columns(&image, 100 - LONG_TICK as usize).contains(&(150 + LABEL_STEP as usize)));
+        assert!(!columns(&image, 100 - LONG_TICK as usize).contains(&(150 + TICK_STEP as usize)));
+    }
+
+    #[test]
+    fn readout_reports_the_physical_offset() {
+        assert_eq!(readout([1920, 1080], (960.0, 540.0)), "Offset from monitor center: x +0 px, y +0 px");
+        assert_eq!(readout([1920, 1080], (963.0, 539.5)), "Offset from monitor center: x +3 px, y -0.5 px");
+
+        // The readout is drawn, text included, below and right of the rulers
+        let image = render(&mut Renderer::new(), [400, 300], (200.0, 150.0), &Config::default(), 1.0);
+        let at = |x: usize, y: usize| image.pixels[y * 400 + x];
+        let ((mx, mw), (my, mh)) = (middle(400), middle(300));
+        let corner = ((mx + mw + RULER_LENGTH + 16) as usize, (my + mh + RULER_LENGTH + 16) as usize);
+        assert_eq!(at(corner.0 - 2, corner.1 - 2), READOUT_FILL);
+        let text = (corner.1..corner.1 + 14).any(|y| (corner.0..corner.0 + 100).any(|x| at(x, y).r() > 128));
+        assert!(text, "readout text drawn");
+    }
+}
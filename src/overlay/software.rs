@@ -0,0 +1,165 @@
+use eframe::egui::ColorImage;
+use log::{info, warn};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use super::source::Shared;
+use super::{OverlayEvent, pattern};
+use crate::geometry::Placement;
+use crate::platform::surface::Surface;
+use crate::raster::Renderer;
+
+/// How often config, window and focus changes are picked up; about a frame
+const FRAME_INTERVAL: Duration = Duration::from_millis(8);
+
+/// Draws the crosshair with [`Renderer`] and shows it on a platform
+/// [`Surface`], for systems where no GL context can be created.
+pub struct SoftwareOverlay {
+    renderer: Renderer,
+    /// Where the crosshair goes, the image's top-left corner and the image:
+    /// the crosshair alone, or the whole monitor with the test pattern.
+    /// `None` while an anchored window is minimized or missing
+    frame: Option<(Placement, (i32, i32), ColorImage)>,
+    /// The surface is on screen
+    presented: bool,
+    /// `frame` changed since it was last presented
+    stale: bool,
+}
+
+impl Default for SoftwareOverlay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SoftwareOverlay {
+    pub fn new() -> Self {
+        Self {
+            renderer: Renderer::new(),
+            frame: None,
+            presented: false,
+            stale: true,
+        }
+    }
+
+    /// Apply pending changes to the surface, like one frame of the GL overlay.
+    pub fn step(&mut self, shared: &Shared, surface: &mut dyn Surface) {
+        if shared.changed.swap(false, Ordering::SeqCst) {
+            let cfg = shared.current_config();
+            let target = shared.target(&cfg);
+            if target.is_none() && self.frame.is_some() {
+                info!("anchored window is minimized or gone, hiding crosshair");
+                shared.report(&OverlayEvent::Hidden);
+            }
+            let previous = self.frame.take();
+            let pattern_on = shared.pattern.load(Ordering::SeqCst);
+            self.frame = target.map(|placement| {
+                let monitor = shared.monitor(&placement.monitor).filter(|_| pattern_on);
+                let (origin, image) = match monitor {
+                    Some(m) => {
+                        let center = pattern::crosshair_center(&placement, &m);
+                        let image = pattern::render(&mut self.renderer, [m.width, m.height], center, &cfg, placement.scale);
+                        ((m.x, m.y), image)
+                    }
+                    None => {
+                        let image = self.renderer.render_crosshair(&cfg, placement.scale);
+                        ((placement.rect.x, placement.rect.y), image)
+                    }
+                };
+                (placement, origin, image)
+            });
+            if let Some(frame @ (placement, _, _)) = &self.frame {
+                if previous.as_ref().is_none_or(|(p, _, _)| p != placement) {
+                    info!("overlay placed on {}: {:?}", placement.monitor, placement.rect);
+                    shared.report(&OverlayEvent::Placed(placement.clone()));
+                }
+                // Only a new image or position is worth re-uploading
+                if previous.as_ref() != Some(frame) {
+                    self.stale = true;
+                }
+            }
+        }
+
+        // The pattern is for checking alignment, so it shows regardless of the show rule and activation
+        let show = self.frame.is_some()
+            && (shared.pattern.load(Ordering::SeqCst) || shared.shown.load(Ordering::SeqCst) && shared.activated());
+        match &self.frame {
+            Some((_, (x, y), image)) if show && (self.stale || !self.presented) => {
+                match surface.present(*x, *y, image) {
+                    Ok(()) => (self.presented, self.stale) = (true, false),
+                    Err(e) => warn!("cannot show crosshair: {e}"),
+                }
+            }
+            Some(_) if show => {}
+            _ if self.presented => {
+                surface.hide();
+                self.presented = false;
+            }
+            _ => {}
+        }
+    }
+
+    /// Keep the surface up to date until the process is stopped.
+    pub fn run(mut self, shared: Shared, mut surface: Box<dyn Surface>) -> ! {
+        info!("using software overlay renderer");
+        loop {
+            surface.dispatch();
+            self.step(&shared, surface.as_mut());
+            std::thread::sleep(FRAME_INTERVAL);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::platform::headless::Headless;
+    use crate::platform::Platform;
+
+    #[test]
+    fn presents_crosshair_and_hides_it_when_deactivated() {
+        let platform = Headless::default();
+        let mut config = Config::default();
+        config.set_field("activation", "show:Shift").unwrap();
+        let shared = Shared::new(config, platform.monitors(), false);
+        let mut surface = platform.software_surface().unwrap();
+        let mut overlay = SoftwareOverlay::new();
+
+        overlay.step(&shared, surface.as_mut());
+        assert!(platform.presented().is_none(), "hold-to-show starts hidden");
+
+        shared.set_held(true);
+        overlay.step(&shared, surface.as_mut());
+        let (x, y, image) = platform.presented().expect("crosshair shown");
+        let (placement, _, _) = overlay.frame.as_ref().unwrap();
+        assert_eq!((x, y), (placement.rect.x, placement.rect.y));
+        assert_eq!(image.size, [placement.rect.size as usize; 2]);
+        assert!(image.pixels.iter().any(|p| p.a() > 0));
+
+        shared.set_held(false);
+        overlay.step(&shared, surface.as_mut());
+        assert!(platform.presented().is_none());
+    }
+
+    #[test]
+    fn pattern_covers_the_monitor() {
+        let platform = Headless::default();
+        let mut config = Config::default();
+        config.set_field("activation", "show:Shift").unwrap();
+        let shared = Shared::new(config, platform.monitors(), false);
+        let mut surface = platform.software_surface().unwrap();
+        let mut overlay = SoftwareOverlay::new();
+        let monitor = &platform.monitors()[0];
+
+        shared.set_pattern(true);
+        overlay.step(&shared, surface.as_mut());
+        let (x, y, image) = platform.presented().expect("shown even though the trigger isn't held");
+        assert_eq!((x, y), (monitor.x, monitor.y));
+        assert_eq!(image.size, [monitor.width as usize, monitor.height as usize]);
+
+        shared.set_pattern(false);
+        overlay.step(&shared, surface.as_mut());
+        assert!(platform.presented().is_none(), "back to hold-to-show");
+    }
+}
@@ -0,0 +1,553 @@
+use eframe::egui;
+use log::{debug, info, warn};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+
+use super::{OverlayCommand, OverlayEvent};
+use crate::config::{Config, WindowMatch};
+use crate::geometry::Placement;
+use crate::platform::window::{Foreground, WindowFinder, WindowState};
+use crate::platform::{Monitor, Platform};
+
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+/// How often the anchored and focused windows are looked up; about a frame,
+/// so a drag is followed smoothly and alt-tabbing hides the crosshair at once
+const TRACK_INTERVAL: Duration = Duration::from_millis(16);
+
+/// State shared between the overlay window and the threads that feed it config updates.
+#[derive(Clone)]
+pub struct Shared {
+    pub config: Arc<Mutex<Config>>,
+    /// Set by config updates and display changes alike
+    pub changed: Arc<AtomicBool>,
+    /// Connected monitors, replaced when the display configuration changes
+    pub monitors: Arc<Mutex<Vec<Monitor>>>,
+    /// The window the crosshair is anchored to; `None` when it isn't running
+    /// or the crosshair isn't anchored
+    pub window: Arc<Mutex<Option<WindowState>>>,
+    /// The config's show rule allows drawing for the focused window
+    pub shown: Arc<AtomicBool>,
+    /// The panel reports the activation trigger held down
+    pub held: Arc<AtomicBool>,
+    /// Show the alignment test pattern across the crosshair's monitor
+    pub pattern: Arc<AtomicBool>,
+    /// Set once the window exists, so background threads can wake it
+    pub repaint_ctx: Arc<Mutex<Option<egui::Context>>>,
+    /// A calibration start or cancel the window hasn't picked up yet
+    calibration: Arc<Mutex<Option<bool>>>,
+    /// Set by overlays that can take input for calibration
+    pub can_calibrate: Arc<AtomicBool>,
+    /// Write [`OverlayEvent`]s to stdout for the panel
+    report: bool,
+    /// The window tracker thread, unparked when a new config may need it
+    tracker: Arc<OnceLock<std::thread::Thread>>,
+}
+
+impl Shared {
+    pub fn new(config: Config, monitors: Vec<Monitor>, report: bool) -> Self {
+        Self {
+            config: Arc::new(Mutex::new(config)),
+            changed: Arc::new(AtomicBool::new(true)),
+            monitors: Arc::new(Mutex::new(monitors)),
+            window: Arc::new(Mutex::new(None)),
+            shown: Arc::new(AtomicBool::new(true)),
+            held: Arc::new(AtomicBool::new(false)),
+            pattern: Arc::new(AtomicBool::new(false)),
+            repaint_ctx: Arc::new(Mutex::new(None)),
+            calibration: Arc::new(Mutex::new(None)),
+            can_calibrate: Arc::new(AtomicBool::new(false)),
+            report,
+            tracker: Arc::new(OnceLock::new()),
+        }
+    }
+
+    fn wake(&self) {
+        self.changed.store(true, Ordering::SeqCst);
+        self.repaint();
+    }
+
+    /// Redraw without re-placing the window.
+    fn repaint(&self) {
+        if let Some(ctx) = self.repaint_ctx.lock().unwrap().as_ref() {
+            ctx.request_repaint();
+        }
+    }
+
+    pub fn set_config(&self, config: Config) {
+        *self.config.lock().unwrap() = config;
+        self.wake();
+        if let Some(tracker) = self.tracker.get() {
+            tracker.unpark();
+        }
+    }
+
+    /// Whether the config anchors to a window or has a show rule, so the
+    /// tracker has something to look up.
+    fn tracks_windows(&self) -> bool {
+        let config = self.config.lock().unwrap();
+        config.anchor.is_some() || !config.show_when.is_always()
+    }
+
+    /// Record the activation trigger's state. A swap can change the window
+    /// size, so this re-places the overlay like a config update.
+    pub fn set_held(&self, held: bool) {
+        if self.held.swap(held, Ordering::SeqCst) != held {
+            debug!("activation trigger {}", if held { "held" } else { "released" });
+            self.wake();
+        }
+    }
+
+    /// Turn the test pattern on or off. The window grows to cover the
+    /// monitor or shrinks back, so this re-places the overlay.
+    pub fn set_pattern(&self, on: bool) {
+        if self.pattern.swap(on, Ordering::SeqCst) != on {
+            info!("test pattern {}", if on { "on" } else { "off" });
+            self.wake();
+        }
+    }
+
+    /// The config as it should be drawn now: the alternate style while a swap trigger is held.
+    pub fn current_config(&self) -> Config {
+        let held = self.held.load(Ordering::SeqCst);
+        self.config.lock().unwrap().for_hold(held)
+    }
+
+    /// Whether the activation mode lets the crosshair be drawn now.
+    pub fn activated(&self) -> bool {
+        let held = self.held.load(Ordering::SeqCst);
+        self.config.lock().unwrap().activation.visible(held)
+    }
+
+    /// Where a crosshair drawn with `config` goes now, or `None` while its
+    /// anchored window is minimized or missing.
+    pub fn target(&self, config: &Config) -> Option<Placement> {
+        let monitors = self.monitors.lock().unwrap();
+        if config.anchor.is_none() {
+            return Placement::new(config, &monitors);
+        }
+        match *self.window.lock().unwrap() {
+            Some(WindowState::Visible(area)) => Some(Placement::on_window(config, &area, &monitors)),
+            Some(WindowState::Minimized) | None => None,
+        }
+    }
+
+    /// The connected monitor called `name`.
+    pub fn monitor(&self, name: &str) -> Option<Monitor> {
+        self.monitors.lock().unwrap().iter().find(|m| m.name == name).cloned()
+    }
+
+    /// Take a fresh monitor list after a display change. Returns whether it
+    /// differs from the previous one, in which case the overlay re-places itself.
+    pub fn display_changed(&self, monitors: Vec<Monitor>) -> bool {
+        let mut current = self.monitors.lock().unwrap();
+        if *current == monitors {
+            return false;
+        }
+        info!("display configuration changed: {monitors:?}");
+        *current = monitors;
+        drop(current);
+        self.wake();
+        true
+    }
+
+    /// Ask the window to start calibrating, or to cancel. Overlays that can't
+    /// take input refuse at once with [`OverlayEvent::Calibrated`]`(None)`.
+    pub fn request_calibration(&self, start: bool) {
+        if !self.can_calibrate.load(Ordering::SeqCst) {
+            if start {
+                warn!("calibration needs the GL overlay on X11 or Windows");
+                self.report(&OverlayEvent::Calibrated(None));
+            }
+            return;
+        }
+        *self.calibration.lock().unwrap() = Some(start);
+        self.repaint();
+    }
+
+    /// The latest calibration request, once.
+    pub fn take_calibration_request(&self) -> Option<bool> {
+        self.calibration.lock().unwrap().take()
+    }
+
+    /// Tell the panel about something that happened, if a panel is listening.
+    pub fn report(&self, event: &OverlayEvent) {
+        if !self.report {
+            return;
+        }
+        use std::io::Write;
+        let Ok(json) = serde_json::to_string(event) else {
+            return;
+        };
+        let mut stdout = std::io::stdout().lock();
+        if writeln!(stdout, "{json}").and_then(|_| stdout.flush()).is_err() {
+            debug!("panel stopped reading overlay events");
+        }
+    }
+
+    /// Ask the window to close, or exit directly if it hasn't been created yet.
+    pub fn close(&self) {
+        match self.repaint_ctx.lock().unwrap().as_ref() {
+            Some(ctx) => ctx.send_viewport_cmd(egui::ViewportCommand::Close),
+            None => std::process::exit(0),
+        }
+    }
+}
+
+/// Read newline-delimited JSON [`OverlayCommand`]s from stdin, as sent by the panel.
+pub fn spawn_stdin_reader(shared: Shared) {
+    std::thread::spawn(move || {
+        use std::io::BufRead;
+
+        let stdin = std::io::stdin();
+        for line in stdin.lock().lines() {
+            match line {
+                Ok(line) if !line.is_empty() => match serde_json::from_str(&line) {
+                    Ok(OverlayCommand::Config(cfg)) => {
+                        debug!("received config update via stdin");
+                        shared.set_config(*cfg);
+                    }
+                    Ok(OverlayCommand::Held(held)) => shared.set_held(held),
+                    Ok(OverlayCommand::Calibrate(start)) => shared.request_calibration(start),
+                    Ok(OverlayCommand::Pattern(on)) => shared.set_pattern(on),
+                    Err(e) => warn!("bad command from stdin: {e}"),
+                },
+                Err(e) => {
+                    info!("stdin closed ({e}), overlay will stop receiving updates");
+                    break;
+                }
+                _ => {}
+            }
+        }
+    });
+}
+
+fn modified(path: &PathBuf) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Poll a config file and reload it whenever its modification time changes.
+/// A file that fails to parse (e.g. caught mid-write) keeps the previous config.
+pub fn spawn_file_watcher(shared: Shared, path: PathBuf) {
+    std::thread::spawn(move || {
+        info!("watching {} for changes", path.display());
+        let mut last = modified(&path);
+        loop {
+            std::thread::sleep(WATCH_INTERVAL);
+            let current = modified(&path);
+            if current == last {
+                continue;
+            }
+            last = current;
+            match Config::read(&path) {
+                Ok(cfg) => {
+                    info!("reloaded {}", path.display());
+                    shared.set_config(cfg);
+                }
+                Err(e) => warn!("{e}, keeping previous config"),
+            }
+        }
+    });
+}
+
+/// Re-read the monitor list whenever the platform reports a display change.
+pub fn spawn_display_watcher(shared: Shared, platform: Arc<dyn Platform>) {
+    let source = platform.clone();
+    platform.watch_displays(Box::new(move || {
+        shared.display_changed(source.monitors());
+    }));
+}
+
+/// Look up the anchored window once and store what was found. Returns
+/// whether it moved, resized, appeared, disappeared or was minimized.
+pub fn track_window(finder: &mut dyn WindowFinder, shared: &Shared) -> bool {
+    let target = shared.config.lock().unwrap().anchor.clone();
+    let state = target.and_then(|t| finder.find(&t));
+    let mut current = shared.window.lock().unwrap();
+    if *current == state {
+        return false;
+    }
+    debug!("anchored window: {state:?}");
+    *current = state;
+    drop(current);
+    shared.wake();
+    true
+}
+
+/// File name of our own executable. The panel runs from it, and the
+/// crosshair stays visible while the panel has focus so edits can be seen.
+fn own_process() -> &'static str {
+    static NAME: OnceLock<String> = OnceLock::new();
+    NAME.get_or_init(|| {
+        std::env::current_exe()
+            .ok()
+            .and_then(|exe| Some(exe.file_name()?.to_string_lossy().into_owned()))
+            .unwrap_or_default()
+    })
+}
+
+/// Check the focused window against the config's show rule. Returns whether
+/// the crosshair appeared or disappeared. When the window system can't tell
+/// what has focus, the crosshair stays visible.
+pub fn track_focus(finder: &mut dyn WindowFinder, shared: &Shared) -> bool {
+    let rule = shared.config.lock().unwrap().show_when.clone();
+    let show = rule.is_always()
+        || match finder.foreground() {
+            Foreground::Unknown => true,
+            Foreground::Nothing => rule.allows(None, false),
+            Foreground::Window { process, fullscreen } => {
+                WindowMatch::Process(own_process().into()).matches(&process, "")
+                    || rule.allows(Some(&process), fullscreen)
+            }
+        };
+    if shared.shown.swap(show, Ordering::SeqCst) == show {
+        return false;
+    }
+    debug!("show rule {rule:?}: {}", if show { "showing" } else { "hiding" });
+    shared.repaint();
+    true
+}
+
+/// Follow the anchored window, if the config names one, and the focused
+/// window, if the config has a show rule. With neither the thread parks
+/// until the next config update.
+pub fn spawn_window_tracker(shared: Shared, mut finder: Box<dyn WindowFinder>) {
+    std::thread::spawn(move || {
+        let _ = shared.tracker.set(std::thread::current());
+        loop {
+            track_window(finder.as_mut(), &shared);
+            track_focus(finder.as_mut(), &shared);
+            if shared.tracks_windows() {
+                std::thread::sleep(TRACK_INTERVAL);
+            } else {
+                std::thread::park();
+            }
+        }
+    });
+}
+
+/// Close the overlay cleanly on Ctrl+C, SIGTERM or SIGHUP (console close on Windows).
+pub fn install_signal_handler(shared: Shared) {
+    if let Err(e) = ctrlc::set_handler(move || {
+        info!("received termination signal, closing overlay");
+        shared.close();
+    }) {
+        warn!("failed to install signal handler: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ShowWhen;
+    use crate::platform::headless::Headless;
+    use crate::platform::window::ClientArea;
+
+    fn monitor(name: &str, width: u32, height: u32) -> Monitor {
+        Monitor {
+            name: name.into(),
+            x: 0,
+            y: 0,
+            width,
+            height,
+            scale: 1.0,
+            primary: true,
+        }
+    }
+
+    #[test]
+    fn unchanged_display_list_is_ignored() {
+        let shared = Shared::new(Config::default(), vec![monitor("DP-1", 1920, 1080)], false);
+        shared.changed.store(false, Ordering::SeqCst);
+        assert!(!shared.display_changed(vec![monitor("DP-1", 1920, 1080)]));
+        assert!(!shared.changed.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn resolution_change_recenters() {
+        let shared = Shared::new(Config::default(), vec![monitor("DP-1", 1920, 1080)], false);
+        shared.changed.store(false, Ordering::SeqCst);
+        let size = Config::default().window_size_px() as i32;
+
+        // The game switches the monitor to 1280x720
+        assert!(shared.display_changed(vec![monitor("DP-1", 1280, 720)]));
+        assert!(shared.changed.load(Ordering::SeqCst));
+
+        let config = shared.config.lock().unwrap().clone();
+        let placement = Placement::new(&config, &shared.monitors.lock().unwrap()).unwrap();
+        assert_eq!(placement.rect.x + size / 2, 640);
+        assert_eq!(placement.rect.y + size / 2, 360);
+    }
+
+    #[test]
+    fn display_watcher_picks_up_new_monitors() {
+        let platform = Arc::new(Headless::new(vec![monitor("DP-1", 1920, 1080)]));
+        let shared = Shared::new(Config::default(), platform.monitors(), false);
+        spawn_display_watcher(shared.clone(), platform.clone());
+        shared.changed.store(false, Ordering::SeqCst);
+
+        platform.set_monitors(vec![monitor("DP-1", 2560, 1440)]);
+        assert!(shared.changed.load(Ordering::SeqCst));
+        assert_eq!(shared.monitors.lock().unwrap()[0].width, 2560);
+    }
+
+    #[test]
+    fn unplugging_target_monitor_moves_to_primary() {
+        let config = Config {
+            monitor: Some("HDMI-1".into()),
+            ..Config::default()
+        };
+        let side = Monitor {
+            x: 1920,
+            primary: false,
+            ..monitor("HDMI-1", 1920, 1080)
+        };
+        let shared = Shared::new(config, vec![monitor("DP-1", 1920, 1080), side], false);
+        let placed = |shared: &Shared| {
+            let config = shared.config.lock().unwrap().clone();
+            Placement::new(&config, &shared.monitors.lock().unwrap()).unwrap()
+        };
+        assert_eq!(placed(&shared).monitor, "HDMI-1");
+
+        assert!(shared.display_changed(vec![monitor("DP-1", 1920, 1080)]));
+        assert_eq!(placed(&shared).monitor, "DP-1");
+    }
+
+    #[test]
+    fn anchored_window_is_followed() {
+        let config = Config {
+            anchor: Some(WindowMatch::Process("game.exe".into())),
+            ..Config::default()
+        };
+        let shared = Shared::new(config, vec![monitor("DP-1", 3440, 1440)], false);
+        let area = ClientArea {
+            x: 0,
+            y: 0,
+            width: 1720,
+            height: 1440,
+        };
+        let platform = Headless::default();
+        let mut windows = platform.window_finder();
+        platform.set_window("game.exe", "Game", Some(WindowState::Visible(area)));
+
+        assert!(track_window(windows.as_mut(), &shared));
+        assert!(!track_window(windows.as_mut(), &shared), "nothing moved");
+
+        let moved = ClientArea { x: 1720, ..area };
+        platform.set_window("game.exe", "Game", Some(WindowState::Visible(moved)));
+        assert!(track_window(windows.as_mut(), &shared));
+        assert_eq!(*shared.window.lock().unwrap(), Some(WindowState::Visible(moved)));
+
+        platform.set_window("game.exe", "Game", Some(WindowState::Minimized));
+        assert!(track_window(windows.as_mut(), &shared));
+        platform.set_window("game.exe", "Game", None);
+        assert!(track_window(windows.as_mut(), &shared));
+        assert_eq!(*shared.window.lock().unwrap(), None);
+    }
+
+    #[test]
+    fn show_rule_follows_focus() {
+        let mut config = Config::default();
+        config.set_field("show_when", "game.exe").unwrap();
+        let shared = Shared::new(config, vec![monitor("DP-1", 1920, 1080)], false);
+        let platform = Headless::default();
+        let mut finder = platform.window_finder();
+        let focus = |process: &str, fullscreen| Foreground::Window {
+            process: process.into(),
+            fullscreen,
+        };
+
+        // Without focus information the crosshair stays up
+        assert!(!track_focus(finder.as_mut(), &shared));
+        assert!(shared.shown.load(Ordering::SeqCst));
+
+        // One poll is enough to hide or show, no debouncing
+        platform.set_foreground(focus("discord.exe", false));
+        assert!(track_focus(finder.as_mut(), &shared));
+        assert!(!shared.shown.load(Ordering::SeqCst));
+        platform.set_foreground(focus("Game.exe", false));
+        assert!(track_focus(finder.as_mut(), &shared));
+        assert!(shared.shown.load(Ordering::SeqCst));
+        platform.set_foreground(Foreground::Nothing);
+        assert!(track_focus(finder.as_mut(), &shared));
+        assert!(!shared.shown.load(Ordering::SeqCst));
+
+        // The panel keeps it visible while editing
+        platform.set_foreground(focus(own_process(), false));
+        assert!(track_focus(finder.as_mut(), &shared));
+
+        shared.config.lock().unwrap().show_when = ShowWhen::Fullscreen;
+        platform.set_foreground(focus("browser", false));
+        assert!(track_focus(finder.as_mut(), &shared));
+        platform.set_foreground(focus("browser", true));
+        assert!(track_focus(finder.as_mut(), &shared));
+        assert!(shared.shown.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn held_trigger_swaps_or_hides() {
+        let mut config = Config::default();
+        config.set_field("activation", "swap:MouseRight").unwrap();
+        config.set_field("alternate_outer_radius", "20").unwrap();
+        let shared = Shared::new(config, vec![monitor("DP-1", 1920, 1080)], false);
+        shared.changed.store(false, Ordering::SeqCst);
+        let normal = shared.current_config().window_size_px();
+
+        shared.set_held(true);
+        assert!(shared.changed.swap(false, Ordering::SeqCst), "a swap may resize the window");
+        assert_eq!(shared.current_config().outer_radius, 20.0);
+        assert!(shared.current_config().window_size_px() > normal);
+        assert!(shared.activated());
+        shared.set_held(true);
+        assert!(!shared.changed.load(Ordering::SeqCst), "repeated state is ignored");
+
+        shared.config.lock().unwrap().set_field("activation", "hide:MouseRight").unwrap();
+        assert!(!shared.activated());
+        shared.set_held(false);
+        assert!(shared.activated());
+        assert_eq!(shared.current_config().window_size_px(), normal);
+    }
+
+    #[test]
+    fn calibration_waits_for_a_capable_window() {
+        let shared = Shared::new(Config::default(), vec![monitor("DP-1", 1920, 1080)], false);
+        shared.request_calibration(true);
+        assert_eq!(shared.take_calibration_request(), None, "refused without a window");
+
+        shared.can_calibrate.store(true, Ordering::SeqCst);
+        shared.request_calibration(true);
+        shared.request_calibration(false);
+        assert_eq!(shared.take_calibration_request(), Some(false), "only the latest counts");
+        assert_eq!(shared.take_calibration_request(), None);
+    }
+
+    #[test]
+    fn unanchored_config_does_not_look_up_windows() {
+        let shared = Shared::new(Config::default(), vec![monitor("DP-1", 1920, 1080)], false);
+        let platform = Headless::default();
+        platform.set_window("game.exe", "Game", Some(WindowState::Minimized));
+        assert!(!track_window(platform.window_finder().as_mut(), &shared));
+        assert_eq!(*shared.window.lock().unwrap(), None);
+    }
+
+    #[test]
+    fn tracker_wakes_up_for_an_anchor() {
+        let shared = Shared::new(Config::default(), vec![monitor("DP-1", 1920, 1080)], false);
+        assert!(!shared.tracks_windows());
+        let platform = Headless::default();
+        platform.set_window("game.exe", "Game", Some(WindowState::Minimized));
+        spawn_window_tracker(shared.clone(), platform.window_finder());
+
+        shared.set_config(Config {
+            anchor: Some(WindowMatch::Process("game.exe".into())),
+            ..Config::default()
+        });
+        assert!(shared.tracks_windows());
+        let found = (0..200).any(|_| {
+            std::thread::sleep(Duration::from_millis(10));
+            shared.window.lock().unwrap().is_some()
+        });
+        assert!(found, "the parked tracker picks up the new anchor");
+    }
+}
@@ -0,0 +1,476 @@
+use eframe::egui;
+use log::{debug, info, warn};
+use smithay_client_toolkit::compositor::{CompositorHandler, CompositorState, Region};
+use smithay_client_toolkit::output::{OutputHandler, OutputState};
+use smithay_client_toolkit::reexports::client::globals::registry_queue_init;
+use smithay_client_toolkit::reexports::client::protocol::{wl_output, wl_shm, wl_surface};
+use smithay_client_toolkit::reexports::client::{Connection, EventQueue, QueueHandle};
+use smithay_client_toolkit::registry::{ProvidesRegistryState, RegistryState};
+use smithay_client_toolkit::shell::WaylandSurface;
+use smithay_client_toolkit::shell::wlr_layer::{
+    Anchor, KeyboardInteractivity, Layer, LayerShell, LayerShellHandler, LayerSurface,
+    LayerSurfaceConfigure,
+};
+use smithay_client_toolkit::shm::slot::SlotPool;
+use smithay_client_toolkit::shm::{Shm, ShmHandler};
+use smithay_client_toolkit::{
+    delegate_compositor, delegate_layer, delegate_output, delegate_registry, delegate_shm,
+    registry_handlers,
+};
+use std::os::fd::AsRawFd;
+use std::sync::atomic::Ordering;
+
+use super::OverlayEvent;
+use super::source::Shared;
+use crate::config::Config;
+use crate::geometry::{self, Placement};
+use crate::platform;
+use crate::raster::Renderer;
+
+/// How long to wait for compositor events before checking for config updates.
+const POLL_TIMEOUT_MS: i32 = 50;
+
+/// Layer-shell margins `(top, right, bottom, left)` that place a surface
+/// anchored to all four edges at the given offset (surface-local units) from
+/// the output's center.
+/// The surface is centered in what the margins leave over, so shifting it by
+/// `d` takes a margin of `2d` on the opposite side.
+pub fn layer_margins(offset_x: f32, offset_y: f32) -> (i32, i32, i32, i32) {
+    let dx = (offset_x * 2.0).round() as i32;
+    let dy = (offset_y * 2.0).round() as i32;
+    (dy.max(0), (-dx).max(0), (-dy).max(0), dx.max(0))
+}
+
+/// Surface side length in surface-local (logical) units at `scale`, rounded up
+/// so the buffer holds the crosshair's full physical size.
+fn surface_size(config: &Config, scale: i32) -> u32 {
+    config.window_size_px().div_ceil(scale.max(1) as u32)
+}
+
+/// Warn about settings that need to see other clients' windows, which
+/// Wayland doesn't allow, when they are first set.
+fn warn_unsupported(config: &Config, previous: Option<&Config>) {
+    if config.anchor.is_some() && previous.is_none_or(|p| p.anchor != config.anchor) {
+        warn!("window anchoring is not available on Wayland, using the monitor center");
+    }
+    if !config.show_when.is_always() && previous.is_none_or(|p| p.show_when != config.show_when) {
+        warn!("show rules are not available on Wayland, the crosshair is always shown");
+    }
+}
+
+/// A crosshair drawn on a wlr-layer-shell surface in the overlay layer, above
+/// fullscreen windows, with an empty input region so clicks pass through.
+/// Rendering goes through the software rasterizer into shared memory.
+pub struct WaylandOverlay {
+    event_queue: EventQueue<State>,
+    state: State,
+    /// Last placement reported to the panel
+    placement: Option<Placement>,
+}
+
+struct State {
+    registry: RegistryState,
+    outputs: OutputState,
+    compositor: CompositorState,
+    layer_shell: LayerShell,
+    shm: Shm,
+    pool: SlotPool,
+    /// `None` until the outputs are known and the surface can be placed
+    layer: Option<LayerSurface>,
+    /// Output the current surface was created on, `None` for the compositor's choice
+    output: Option<wl_output::WlOutput>,
+    /// Outputs were added, removed or changed mode or scale
+    outputs_changed: bool,
+    renderer: Renderer,
+    /// The config as currently drawn, see [`Shared::current_config`]
+    config: Config,
+    /// The activation mode lets the crosshair be drawn
+    activated: bool,
+    scale: i32,
+    configured: bool,
+    closed: bool,
+}
+
+impl WaylandOverlay {
+    /// Connect to the compositor and create the layer surface. Fails when there
+    /// is no Wayland display or the compositor lacks `zwlr_layer_shell_v1`.
+    pub fn connect(config: Config) -> Result<Self, String> {
+        let conn = Connection::connect_to_env().map_err(|e| format!("no Wayland display: {e}"))?;
+        let (globals, event_queue) =
+            registry_queue_init::<State>(&conn).map_err(|e| format!("Wayland registry: {e}"))?;
+        let qh = event_queue.handle();
+
+        let compositor = CompositorState::bind(&globals, &qh)
+            .map_err(|e| format!("wl_compositor unavailable: {e}"))?;
+        let layer_shell = LayerShell::bind(&globals, &qh)
+            .map_err(|e| format!("compositor does not support wlr-layer-shell: {e}"))?;
+        let shm = Shm::bind(&globals, &qh).map_err(|e| format!("wl_shm unavailable: {e}"))?;
+
+        warn_unsupported(&config, None);
+        let side = config.window_size_px() as usize;
+        let pool = SlotPool::new(side * side * 4, &shm).map_err(|e| format!("shm pool: {e}"))?;
+
+        let state = State {
+            registry: RegistryState::new(&globals),
+            outputs: OutputState::new(&globals, &qh),
+            compositor,
+            layer_shell,
+            shm,
+            pool,
+            layer: None,
+            output: None,
+            outputs_changed: false,
+            renderer: Renderer::new(),
+            activated: config.activation.visible(false),
+            config,
+            scale: 1,
+            configured: false,
+            closed: false,
+        };
+        let mut overlay = Self {
+            event_queue,
+            state,
+            placement: None,
+        };
+        // Output names arrive after the outputs are bound
+        overlay.roundtrip()?;
+        overlay.roundtrip()?;
+        overlay.state.create_layer(&qh)?;
+        overlay.roundtrip()?;
+        Ok(overlay)
+    }
+
+    fn roundtrip(&mut self) -> Result<(), String> {
+        self.event_queue
+            .roundtrip(&mut self.state)
+            .map(|_| ())
+            .map_err(|e| format!("Wayland roundtrip: {e}"))
+    }
+
+    /// Process compositor events and config updates until the surface is closed.
+    pub fn run(mut self, shared: Shared) -> Result<(), String> {
+        let mut pattern_warned = false;
+        while !self.state.closed {
+            let config_changed = shared.changed.swap(false, Ordering::SeqCst);
+            if config_changed {
+                if shared.pattern.load(Ordering::SeqCst) && !std::mem::replace(&mut pattern_warned, true) {
+                    warn!("the test pattern needs the X11 or Windows overlay, ignoring it");
+                }
+                let config = shared.current_config();
+                warn_unsupported(&config, Some(&self.state.config));
+                self.state.config = config;
+                self.state.activated = shared.activated();
+            }
+            let outputs_changed = std::mem::take(&mut self.state.outputs_changed);
+            if config_changed || outputs_changed {
+                self.update(&shared)?;
+            }
+            self.dispatch(POLL_TIMEOUT_MS)?;
+        }
+        info!("layer surface closed by the compositor");
+        Ok(())
+    }
+
+    /// Re-place the surface after a config or output change and report where it went.
+    fn update(&mut self, shared: &Shared) -> Result<(), String> {
+        let state = &mut self.state;
+        if state.layer.is_none() || state.target_output() != state.output {
+            // Layer surfaces can't move between outputs; make a new one
+            state.create_layer(&self.event_queue.handle())?;
+        } else {
+            state.apply_layout();
+            state.draw();
+        }
+
+        let monitors = platform::wayland::list(&state.outputs);
+        if let Some(placement) = Placement::new(&state.config, &monitors)
+            && self.placement.as_ref() != Some(&placement)
+        {
+            info!("overlay placed on {}: {:?}", placement.monitor, placement.rect);
+            shared.report(&OverlayEvent::Placed(placement.clone()));
+            self.placement = Some(placement);
+        }
+        Ok(())
+    }
+
+    /// Flush requests, wait up to `timeout_ms` for events and dispatch them.
+    fn dispatch(&mut self, timeout_ms: i32) -> Result<(), String> {
+        let err = |e: &dyn std::fmt::Display| format!("Wayland connection error: {e}");
+        self.event_queue.dispatch_pending(&mut self.state).map_err(|e| err(&e))?;
+        self.event_queue.flush().map_err(|e| err(&e))?;
+        if let Some(guard) = self.event_queue.prepare_read() {
+            let mut fd = libc::pollfd {
+                fd: guard.connection_fd().as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            // SAFETY: one valid pollfd, borrowed from the guard for the duration of the call
+            let ready = unsafe { libc::poll(&mut fd, 1, timeout_ms) };
+            if ready > 0 {
+                guard.read().map_err(|e| err(&e))?;
+            }
+        }
+        self.event_queue.dispatch_pending(&mut self.state).map_err(|e| err(&e))?;
+        Ok(())
+    }
+}
+
+impl State {
+    /// The output whose name matches the configured monitor, if it is connected.
+    fn target_output(&self) -> Option<wl_output::WlOutput> {
+        let name = self.config.monitor.as_deref()?;
+        self.outputs
+            .outputs()
+            .find(|o| self.outputs.info(o).and_then(|i| i.name).as_deref() == Some(name))
+    }
+
+    /// (Re)create the layer surface on the configured monitor.
+    fn create_layer(&mut self, qh: &QueueHandle<Self>) -> Result<(), String> {
+        let output = self.target_output();
+        if let (Some(name), None) = (&self.config.monitor, &output) {
+            warn!("monitor {name:?} not found, letting the compositor choose");
+        }
+        let surface = self.compositor.create_surface(qh);
+        let layer = self.layer_shell.create_layer_surface(
+            qh,
+            surface,
+            Layer::Overlay,
+            Some("aimx"),
+            output.as_ref(),
+        );
+        layer.set_anchor(Anchor::all());
+        layer.set_exclusive_zone(-1);
+        layer.set_keyboard_interactivity(KeyboardInteractivity::None);
+
+        // Empty input region: the surface never receives pointer or touch input
+        let region = Region::new(&self.compositor).map_err(|e| e.to_string())?;
+        layer.wl_surface().set_input_region(Some(region.wl_region()));
+
+        // Dropping the old surface destroys it
+        self.layer = Some(layer);
+        self.output = output;
+        self.configured = false;
+        self.apply_layout();
+        // The first commit carries no buffer; the compositor answers with a configure
+        if let Some(layer) = &self.layer {
+            layer.commit();
+        }
+        info!("created Wayland layer-shell overlay surface");
+        Ok(())
+    }
+
+    /// Push the size and position for the current config to the compositor.
+    fn apply_layout(&mut self) {
+        let Some(layer) = &self.layer else {
+            return;
+        };
+        let side = surface_size(&self.config, self.scale);
+        // Margins are surface-local units too; offsets are physical pixels
+        let scale = self.scale as f32;
+        let (top, right, bottom, left) = layer_margins(
+            geometry::to_points(self.config.offset_x, scale),
+            geometry::to_points(self.config.offset_y, scale),
+        );
+        layer.set_size(side, side);
+        layer.set_margin(top, right, bottom, left);
+        debug!("layer surface: size={side} margins=({top}, {right}, {bottom}, {left})");
+    }
+
+    fn draw(&mut self) {
+        let Some(layer) = self.layer.as_ref().filter(|_| self.configured) else {
+            return;
+        };
+        // Whole logical pixels at the current scale, so the buffer maps 1:1 to the surface
+        let side = surface_size(&self.config, self.scale) as usize * self.scale as usize;
+        let (config, activated) = (&self.config, self.activated);
+        let image = self.renderer.render(
+            [side, side],
+            self.scale as f32,
+            egui::Color32::TRANSPARENT,
+            |painter| {
+                if activated {
+                    crate::crosshair::draw(painter, painter.clip_rect().center(), config);
+                }
+            },
+        );
+
+        let (buffer, canvas) = match self.pool.create_buffer(
+            side as i32,
+            side as i32,
+            side as i32 * 4,
+            wl_shm::Format::Argb8888,
+        ) {
+            Ok(b) => b,
+            Err(e) => {
+                warn!("failed to allocate overlay buffer: {e}");
+                return;
+            }
+        };
+        // egui colors are premultiplied RGBA; ARGB8888 is premultiplied BGRA in memory
+        for (px, c) in canvas.chunks_exact_mut(4).zip(&image.pixels) {
+            px.copy_from_slice(&[c.b(), c.g(), c.r(), c.a()]);
+        }
+
+        let surface = layer.wl_surface();
+        surface.set_buffer_scale(self.scale);
+        surface.damage_buffer(0, 0, side as i32, side as i32);
+        if let Err(e) = buffer.attach_to(surface) {
+            warn!("failed to attach overlay buffer: {e}");
+            return;
+        }
+        layer.commit();
+    }
+}
+
+impl CompositorHandler for State {
+    fn scale_factor_changed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        new_factor: i32,
+    ) {
+        info!("output scale changed to {new_factor}");
+        self.scale = new_factor.max(1);
+        // Same physical size means a different logical size
+        self.apply_layout();
+        self.draw();
+    }
+
+    fn transform_changed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _new_transform: wl_output::Transform,
+    ) {
+    }
+
+    fn frame(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _time: u32,
+    ) {
+    }
+
+    fn surface_enter(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _output: &wl_output::WlOutput,
+    ) {
+    }
+
+    fn surface_leave(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _output: &wl_output::WlOutput,
+    ) {
+    }
+}
+
+impl OutputHandler for State {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.outputs
+    }
+
+    fn new_output(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _output: wl_output::WlOutput) {
+        self.outputs_changed = true;
+    }
+
+    fn update_output(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _output: wl_output::WlOutput) {
+        self.outputs_changed = true;
+    }
+
+    fn output_destroyed(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _output: wl_output::WlOutput) {
+        self.outputs_changed = true;
+    }
+}
+
+impl LayerShellHandler for State {
+    fn closed(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _layer: &LayerSurface) {
+        if self.output.is_some() {
+            // Our output went away; start over on whichever output is left
+            info!("layer surface output disconnected");
+            self.layer = None;
+            self.output = None;
+            self.outputs_changed = true;
+        } else {
+            self.closed = true;
+        }
+    }
+
+    fn configure(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _layer: &LayerSurface,
+        configure: LayerSurfaceConfigure,
+        _serial: u32,
+    ) {
+        debug!("layer surface configured: {:?}", configure.new_size);
+        self.configured = true;
+        self.draw();
+    }
+}
+
+impl ShmHandler for State {
+    fn shm_state(&mut self) -> &mut Shm {
+        &mut self.shm
+    }
+}
+
+impl ProvidesRegistryState for State {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry
+    }
+    registry_handlers![OutputState];
+}
+
+delegate_compositor!(State);
+delegate_output!(State);
+delegate_shm!(State);
+delegate_layer!(State);
+delegate_registry!(State);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn centered_has_no_margins() {
+        assert_eq!(layer_margins(0.0, 0.0), (0, 0, 0, 0));
+    }
+
+    #[test]
+    fn offset_becomes_margin_on_opposite_side() {
+        // Right and down: push from the left and top
+        assert_eq!(layer_margins(10.0, 4.0), (8, 0, 0, 20));
+        // Left and up: push from the right and bottom
+        assert_eq!(layer_margins(-3.0, -7.5), (0, 6, 15, 0));
+    }
+
+    /// Run against a headless compositor, e.g. `sway --headless` or
+    /// `weston --backend=headless`. Skipped when WAYLAND_DISPLAY is not set.
+    #[test]
+    fn layer_surface_is_configured() {
+        if std::env::var_os("WAYLAND_DISPLAY").is_none() {
+            eprintln!("WAYLAND_DISPLAY not set, skipping Wayland test");
+            return;
+        }
+        let mut overlay = WaylandOverlay::connect(Config::default()).unwrap();
+        for _ in 0..20 {
+            if overlay.state.configured {
+                break;
+            }
+            overlay.dispatch(POLL_TIMEOUT_MS).unwrap();
+        }
+        assert!(overlay.state.configured, "compositor never configured the layer surface");
+        assert!(!overlay.state.closed);
+    }
+}
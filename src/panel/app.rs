@@ -1,18 +1,49 @@
+use crossbeam_channel::Receiver;
 use eframe::egui::{self, ViewportCommand};
-use log::info;
-use std::process::Child;
+use log::{info, warn};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
-use crate::config::Config;
-use super::{ipc, style, tray};
+use crate::config::{self, Config, OFFSET_RANGE, OverlayConfig, clamp_to};
+use crate::control;
+use crate::export;
+use crate::hotkey::{self, Action, Bindings};
+use crate::platform::hotkey::HotkeyGrabber;
+use crate::platform::{Monitor, Platform};
+use super::history::{self, History};
+use super::{hold, ipc, preview, style, tray};
+
+/// An undo step: the config and the profile it belongs to
+type Snapshot = (Config, Option<String>);
 
 pub struct PanelApp {
+    ctx: egui::Context,
     config: Config,
-    prev_config: Option<String>,
-    child: Option<Child>,
-    #[cfg(target_os = "windows")]
-    job: Option<crate::platform::JobObject>,
+    /// One process per overlay: the primary config first, then `config.overlays`
+    overlays: Vec<ipc::OverlayProcess>,
+    /// Index into `overlays` of the crosshair being edited
+    selected: usize,
+    profile: Option<String>,
+    profiles: Vec<String>,
+    new_profile_name: String,
+    /// Monitors offered in the panel, refreshed on demand
+    monitors: Vec<Monitor>,
+    control: Option<control::Server>,
+    platform: Arc<dyn Platform>,
+    hotkeys: Box<dyn HotkeyGrabber>,
+    hotkey_presses: Receiver<Action>,
+    /// Bindings currently held by `hotkeys`
+    registered: Option<Bindings>,
+    hotkey_errors: Vec<(Action, String)>,
+    /// Action whose new binding is being recorded in the panel
+    capturing: Option<Action>,
+    hold: hold::HoldWatcher,
+    preview: preview::Preview,
+    /// Undo and redo for the whole config, overlays and hotkeys included,
+    /// along with the profile it was loaded from or saved to
+    history: History<Snapshot>,
+    /// Shown under the Save button, e.g. where the last export went
+    notice: Option<String>,
     show_close_dialog: bool,
     remember_choice: bool,
     force_quit: bool,
@@ -25,22 +56,48 @@ pub struct PanelApp {
 }
 
 impl PanelApp {
-    pub fn new() -> Self {
+    pub fn new(ctx: &egui::Context, platform: Arc<dyn Platform>) -> Self {
         let config = Config::load();
-        #[cfg(target_os = "windows")]
-        let job = crate::platform::JobObject::new();
 
-        let child = ipc::spawn_overlay(
-            #[cfg(target_os = "windows")]
-            &job,
-        );
+        let overlays = (0..=config.overlays.len())
+            .map(|_| {
+                let mut overlay = ipc::OverlayProcess::default();
+                overlay.start(ctx, platform.as_ref());
+                overlay
+            })
+            .collect();
+
+        let (tx, hotkey_presses) = crossbeam_channel::unbounded();
+        let waker = ctx.clone();
+        let hotkeys = platform.hotkeys(Box::new(move |action| {
+            let _ = tx.send(action);
+            // Wakes the panel even while it sits in the tray
+            waker.request_repaint();
+        }));
+
+        let waker = ctx.clone();
+        let hold = hold::HoldWatcher::start(platform.input_poller(), Box::new(move || waker.request_repaint()));
 
         Self {
+            ctx: ctx.clone(),
+            history: History::new((config.clone(), None), history::LIMIT),
             config,
-            prev_config: None,
-            child,
-            #[cfg(target_os = "windows")]
-            job,
+            overlays,
+            selected: 0,
+            profile: None,
+            profiles: config::list_profiles(),
+            new_profile_name: String::new(),
+            monitors: platform.monitors(),
+            control: control::Server::start(ctx.clone()),
+            platform,
+            hotkeys,
+            hotkey_presses,
+            registered: None,
+            hotkey_errors: Vec::new(),
+            capturing: None,
+            hold,
+            preview: preview::Preview::default(),
+            notice: None,
             show_close_dialog: false,
             remember_choice: false,
             force_quit: false,
@@ -63,6 +120,7 @@ impl PanelApp {
                 self.tray_restore.clone(),
                 self.tray_quit.clone(),
                 ctx.clone(),
+                self.platform.clone(),
             );
         }
         self.minimized = true;
@@ -79,12 +137,389 @@ impl PanelApp {
         ctx.send_viewport_cmd(ViewportCommand::Focus);
         info!("restored from tray");
     }
+
+    /// Change overlay `index`'s settings and send them on.
+    fn edit_overlay(&mut self, index: usize, edit: impl FnOnce(&mut Config)) {
+        self.config.edit_overlay(index, edit);
+        self.overlays[index].mark_dirty();
+    }
+
+    fn any_overlay_running(&self) -> bool {
+        self.overlays.iter().any(|o| o.is_running())
+    }
+
+    fn start_overlay(&mut self, index: usize) {
+        self.overlays[index].start(&self.ctx, self.platform.as_ref());
+    }
+
+    fn show_overlays(&mut self) {
+        for i in 0..self.overlays.len() {
+            self.start_overlay(i);
+        }
+    }
+
+    fn hide_overlays(&mut self) {
+        for overlay in &mut self.overlays {
+            overlay.stop();
+        }
+    }
+
+    fn add_overlay(&mut self) {
+        self.config.overlays.push(OverlayConfig::default());
+        self.overlays.push(ipc::OverlayProcess::default());
+        self.selected = self.overlays.len() - 1;
+        self.start_overlay(self.selected);
+    }
+
+    fn remove_overlay(&mut self, index: usize) {
+        // The primary overlay is the config itself and can't be removed
+        if index == 0 || index >= self.overlays.len() {
+            return;
+        }
+        self.config.overlays.remove(index - 1);
+        self.overlays.remove(index);
+        self.selected = self.selected.min(self.overlays.len() - 1);
+    }
+
+    /// Match the overlay processes to the config after it was replaced wholesale,
+    /// e.g. by loading a profile. New overlays start if the primary is showing.
+    fn sync_overlays(&mut self) {
+        let wanted = self.config.overlays.len() + 1;
+        self.overlays.truncate(wanted);
+        while self.overlays.len() < wanted {
+            self.overlays.push(ipc::OverlayProcess::default());
+            if self.overlays[0].is_running() {
+                self.start_overlay(self.overlays.len() - 1);
+            }
+        }
+        self.selected = self.selected.min(wanted - 1);
+    }
+
+    /// Mark every overlay dirty after the config was replaced wholesale.
+    fn mark_all_dirty(&mut self) {
+        for overlay in &mut self.overlays {
+            overlay.mark_dirty();
+        }
+    }
+
+    fn undo(&mut self) {
+        if let Some(state) = self.history.undo().cloned() {
+            self.restore(state);
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(state) = self.history.redo().cloned() {
+            self.restore(state);
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        (self.config.clone(), self.profile.clone())
+    }
+
+    /// Put back a config and its profile name from the edit history.
+    fn restore(&mut self, (config, profile): Snapshot) {
+        self.config = config;
+        self.profile = profile;
+        self.sync_overlays();
+        self.mark_all_dirty();
+    }
+
+    /// Ctrl+Z undoes, Ctrl+Y or Ctrl+Shift+Z redoes. Left alone while a text
+    /// field has focus, which has its own undo, or while a hotkey is recorded.
+    fn undo_shortcuts(&mut self, ctx: &egui::Context) {
+        let editing_text = ctx
+            .memory(|m| m.focused())
+            .is_some_and(|id| egui::text_edit::TextEditState::load(ctx, id).is_some());
+        if editing_text || self.capturing.is_some() {
+            return;
+        }
+        let shortcut = |modifiers, key| egui::KeyboardShortcut::new(modifiers, key);
+        let command_shift = egui::Modifiers::COMMAND | egui::Modifiers::SHIFT;
+        // Ctrl+Z also matches with Shift held, so redo is checked first
+        let redo = ctx.input_mut(|i| {
+            i.consume_shortcut(&shortcut(command_shift, egui::Key::Z))
+                || i.consume_shortcut(&shortcut(egui::Modifiers::COMMAND, egui::Key::Y))
+        });
+        if redo {
+            self.redo();
+        } else if ctx.input_mut(|i| i.consume_shortcut(&shortcut(egui::Modifiers::COMMAND, egui::Key::Z))) {
+            self.undo();
+        }
+    }
+
+    /// Stream pending config changes, coalescing them while a control is dragged.
+    fn send_configs(&mut self, ctx: &egui::Context) {
+        let now = std::time::Instant::now();
+        let dragging = ctx.input(|i| i.pointer.any_down());
+        let config = &self.config;
+        for (i, overlay) in self.overlays.iter_mut().enumerate() {
+            if let Some(delay) = overlay.send_config(|| config.overlay(i), now, dragging) {
+                // Make sure the held-back update goes out even if no more input arrives
+                ctx.request_repaint_after(delay);
+            }
+        }
+    }
+
+    fn load_profile(&mut self, name: &str) -> Result<(), String> {
+        let mut cfg = Config::load_profile(name)?;
+        // Window behaviour and hotkeys are app preferences, not part of a crosshair profile
+        cfg.close_action = self.config.close_action.clone();
+        cfg.hotkeys = self.config.hotkeys.clone();
+        self.config = cfg;
+        self.profile = Some(name.to_string());
+        self.sync_overlays();
+        self.mark_all_dirty();
+        Ok(())
+    }
+
+    fn save_profile(&mut self, name: &str) {
+        match self.config.save_profile(name) {
+            Ok(()) => {
+                self.profile = Some(name.to_string());
+                // Saving changes no settings, so it isn't an undo step of its own
+                self.history.amend(&self.snapshot());
+                self.profiles = config::list_profiles();
+                self.new_profile_name.clear();
+            }
+            Err(e) => warn!("{e}"),
+        }
+    }
+
+    /// Where the selected crosshair is exported to: `exports/`, named after the active profile.
+    fn export_path(&self) -> std::path::PathBuf {
+        let name = match (self.profile.as_deref().unwrap_or("crosshair"), self.selected) {
+            (name, 0) => name.to_string(),
+            (name, i) => format!("{name}-{}", i + 1),
+        };
+        config::exports_dir().join(format!("{name}.svg"))
+    }
+
+    fn export_svg(&mut self) {
+        let path = self.export_path();
+        let dir = config::exports_dir();
+        let result = std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("cannot create {}: {e}", dir.display()))
+            .and_then(|_| export::save_svg(&self.config.overlay(self.selected), &Default::default(), &path));
+        self.notice = Some(match result {
+            Ok(_) => {
+                info!("exported crosshair to {}", path.display());
+                format!("Exported to {}", path.display())
+            }
+            Err(e) => {
+                warn!("{e}");
+                e
+            }
+        });
+    }
+
+    /// Replace the selected crosshair's look with the one in its exported SVG file.
+    fn import_svg(&mut self) {
+        let path = self.export_path();
+        let selected = self.selected;
+        let result = std::fs::read_to_string(&path)
+            .map_err(|e| format!("cannot read {}: {e}", path.display()))
+            .and_then(|svg| export::svg::from_svg(&svg, &self.config.overlay(selected)));
+        self.notice = Some(match result {
+            Ok(imported) => {
+                self.edit_overlay(selected, |cfg| {
+                    let overlays = std::mem::take(&mut cfg.overlays);
+                    *cfg = Config { overlays, ..imported };
+                });
+                info!("imported crosshair from {}", path.display());
+                format!("Imported from {}", path.display())
+            }
+            Err(e) => {
+                warn!("{e}");
+                e
+            }
+        });
+    }
+
+    /// Load the profile `step` places after the active one in the list, wrapping around.
+    fn cycle_profile(&mut self, step: isize) {
+        self.profiles = config::list_profiles();
+        let count = self.profiles.len() as isize;
+        if count == 0 {
+            info!("no saved profiles to switch to");
+            return;
+        }
+        let current = self.profile.as_ref().and_then(|p| self.profiles.iter().position(|n| n == p));
+        let next = match current {
+            Some(i) => (i as isize + step).rem_euclid(count),
+            None if step > 0 => 0,
+            None => count - 1,
+        };
+        let name = self.profiles[next as usize].clone();
+        match self.load_profile(&name) {
+            Ok(()) => info!("switched to profile {name:?}"),
+            Err(e) => warn!("{e}"),
+        }
+    }
+
+    /// Hand the configured hotkeys to the platform when they changed. While a
+    /// binding is being recorded nothing is grabbed, so the keys reach the panel.
+    fn sync_hotkeys(&mut self) {
+        let duplicates = hotkey::duplicates(&self.config.hotkeys);
+        let wanted: Bindings = match self.capturing {
+            Some(_) => Bindings::new(),
+            None => self
+                .config
+                .hotkeys
+                .iter()
+                .filter(|(action, _)| !duplicates.iter().any(|(a, _)| a == *action))
+                .map(|(action, hotkey)| (*action, *hotkey))
+                .collect(),
+        };
+        if self.registered.as_ref() == Some(&wanted) {
+            return;
+        }
+        let errors = self.hotkeys.register(&wanted);
+        for (action, error) in &errors {
+            warn!("cannot register hotkey for {}: {error}", action.name());
+        }
+        if self.capturing.is_none() {
+            self.hotkey_errors = duplicates.into_iter().chain(errors).collect();
+        }
+        self.registered = Some(wanted);
+    }
+
+    fn handle_hotkey(&mut self, action: Action) {
+        info!("hotkey: {}", action.name());
+        match action {
+            Action::Toggle if self.any_overlay_running() => self.hide_overlays(),
+            Action::Toggle => self.show_overlays(),
+            Action::NextProfile => self.cycle_profile(1),
+            Action::PreviousProfile => self.cycle_profile(-1),
+            Action::ResetOffset => self.edit_overlay(self.selected, |cfg| (cfg.offset_x, cfg.offset_y) = (0.0, 0.0)),
+            nudge => {
+                let Some((dx, dy)) = nudge.nudge() else {
+                    return;
+                };
+                self.edit_overlay(self.selected, |cfg| {
+                    cfg.offset_x = clamp_to(cfg.offset_x + dx, OFFSET_RANGE);
+                    cfg.offset_y = clamp_to(cfg.offset_y + dy, OFFSET_RANGE);
+                });
+            }
+        }
+    }
+
+    fn poll_hotkeys(&mut self, ctx: &egui::Context) {
+        self.sync_hotkeys();
+        let pending: Vec<_> = self.hotkey_presses.try_iter().collect();
+        if pending.is_empty() {
+            return;
+        }
+        for action in pending {
+            self.handle_hotkey(action);
+        }
+        // The panel may be in the tray and skip the rest of update()
+        self.send_configs(ctx);
+    }
+
+    /// Watch the triggers of every overlay's activation mode and pass their
+    /// state on. Runs every frame, so edits to the mode apply at once.
+    fn poll_holds(&mut self) {
+        let triggers: Vec<_> = (0..self.overlays.len())
+            .map(|i| self.config.activation_for(i).trigger())
+            .collect();
+        let mut watched = Vec::new();
+        for trigger in triggers.iter().flatten() {
+            if !watched.contains(trigger) {
+                watched.push(*trigger);
+            }
+        }
+        self.hold.watch(watched);
+        self.hold.update();
+        for (overlay, trigger) in self.overlays.iter_mut().zip(triggers) {
+            overlay.send_held(trigger.is_some_and(|t| self.hold.held(t)));
+        }
+    }
+
+    fn activate(&mut self, ctx: &egui::Context) {
+        if self.minimized {
+            self.restore_from_tray(ctx);
+        } else {
+            ctx.send_viewport_cmd(ViewportCommand::Minimized(false));
+            ctx.send_viewport_cmd(ViewportCommand::Focus);
+        }
+        self.platform.show_panel_window();
+    }
+
+    fn handle_control(&mut self, ctx: &egui::Context, request: &control::Request) -> control::Response {
+        use control::{Request, Response};
+
+        match request {
+            Request::Activate => {
+                info!("activated by another launch");
+                self.activate(ctx);
+            }
+            Request::Toggle if self.any_overlay_running() => self.hide_overlays(),
+            Request::Toggle | Request::Show => self.show_overlays(),
+            Request::Hide => self.hide_overlays(),
+            Request::Profile(name) => {
+                if let Err(e) = self.load_profile(name) {
+                    return Response::Rejected(e);
+                }
+            }
+            Request::Set(assignments) => {
+                let mut cfg = self.config.clone();
+                for (key, value) in assignments {
+                    if let Err(e) = cfg.set_field(key, value) {
+                        return Response::Rejected(e);
+                    }
+                }
+                self.config = cfg;
+                // The additional overlays follow the activation mode
+                self.mark_all_dirty();
+            }
+            Request::Status => {
+                return Response::Status(Box::new(control::Status {
+                    overlay_visible: self.any_overlay_running(),
+                    profile: self.profile.clone(),
+                    config: self.config.clone(),
+                }));
+            }
+        }
+        Response::Ok
+    }
+
+    fn poll_control(&mut self, ctx: &egui::Context) {
+        let Some(server) = &self.control else {
+            return;
+        };
+        let pending: Vec<_> = server.requests.try_iter().collect();
+        if pending.is_empty() {
+            return;
+        }
+        for p in pending {
+            let response = self.handle_control(ctx, &p.request);
+            p.reply(response);
+        }
+        // The panel may be in the tray and skip the rest of update()
+        self.send_configs(ctx);
+    }
 }
 
 impl eframe::App for PanelApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // Requests from `aimx ctl` are served even while minimized to tray
+        self.poll_control(ctx);
+        self.poll_hotkeys(ctx);
+        self.poll_holds();
+        for i in 0..self.overlays.len() {
+            if let Some((x, y)) = self.overlays[i].poll_events() {
+                self.edit_overlay(i, |cfg| (cfg.offset_x, cfg.offset_y) = (x, y));
+            }
+        }
+
         // Check tray menu actions
         if self.minimized {
+            // The rest of update() is skipped, so record hotkey, `aimx ctl`
+            // and calibration changes here, each as its own step
+            let snapshot = self.snapshot();
+            self.history.record(&snapshot, false);
             if self.tray_quit.load(Ordering::SeqCst) {
                 self.restore_from_tray(ctx);
                 self.force_quit = true;
@@ -164,37 +599,104 @@ impl eframe::App for PanelApp {
             }
         }
 
+        self.undo_shortcuts(ctx);
         egui::CentralPanel::default().show(ctx, |ui| {
-            let overlay_alive = self.child.is_some();
-            let action = style::draw_panel_ui(ui, &mut self.config, overlay_alive);
+            let running: Vec<bool> = self.overlays.iter().map(|o| o.is_running()).collect();
+            let selected = self.selected;
+            let activation = self.config.activation.clone();
+            let (action, changed) = self.config.edit_overlay(selected, |cfg| style::draw_panel_ui(
+                ui,
+                cfg,
+                &style::PanelView {
+                    running: &running,
+                    selected,
+                    profiles: &self.profiles,
+                    active_profile: self.profile.as_deref(),
+                    monitors: &self.monitors,
+                    placement: self.overlays[selected].placement(),
+                    hidden: self.overlays[selected].hidden(),
+                    notice: self.notice.as_deref(),
+                    calibrating: self.overlays[selected].calibrating(),
+                    pattern: self.overlays[selected].pattern(),
+                    can_undo: self.history.can_undo(),
+                    can_redo: self.history.can_redo(),
+                },
+                &mut self.new_profile_name,
+                &mut self.preview,
+            ));
+
+            if self.config.activation != activation {
+                // The additional overlays follow the first one's activation mode
+                self.mark_all_dirty();
+            } else if changed {
+                self.overlays[selected].mark_dirty();
+            }
+
+            ui.separator();
+            match style::draw_hotkeys(ui, &self.config.hotkeys, self.capturing, &self.hotkey_errors) {
+                style::HotkeyEdit::Capture(action) => self.capturing = Some(action),
+                style::HotkeyEdit::CancelCapture => self.capturing = None,
+                style::HotkeyEdit::Set(action, hotkey) => {
+                    self.config.hotkeys.insert(action, hotkey);
+                    self.capturing = None;
+                }
+                style::HotkeyEdit::Clear(action) => {
+                    self.config.hotkeys.remove(&action);
+                }
+                style::HotkeyEdit::None => {}
+            }
 
             match action {
                 style::PanelAction::Save => self.config.save(),
-                style::PanelAction::Reset => self.config = Config::default(),
-                style::PanelAction::ShowOverlay => {
-                    self.child = ipc::spawn_overlay(
-                        #[cfg(target_os = "windows")]
-                        &self.job,
-                    );
-                    self.prev_config = None;
+                style::PanelAction::Undo => self.undo(),
+                style::PanelAction::Redo => self.redo(),
+                style::PanelAction::Reset => match selected {
+                    // Keep the other overlays; Reset only applies to the one being edited
+                    0 => {
+                        self.config = Config {
+                            overlays: std::mem::take(&mut self.config.overlays),
+                            hotkeys: std::mem::take(&mut self.config.hotkeys),
+                            ..Config::default()
+                        };
+                        self.mark_all_dirty();
+                    }
+                    i => self.edit_overlay(i, |cfg| *cfg = Config::default()),
+                },
+                style::PanelAction::ShowOverlay => self.show_overlays(),
+                style::PanelAction::HideOverlay => self.hide_overlays(),
+                style::PanelAction::SelectOverlay(i) => self.selected = i,
+                style::PanelAction::AddOverlay => self.add_overlay(),
+                style::PanelAction::RemoveOverlay(i) => self.remove_overlay(i),
+                style::PanelAction::StartOverlay(i) => self.start_overlay(i),
+                style::PanelAction::StopOverlay(i) => self.overlays[i].stop(),
+                style::PanelAction::Calibrate(i) => {
+                    let cfg = self.config.overlay(i);
+                    self.overlays[i].calibrate(&cfg, self.platform.as_ref());
                 }
-                style::PanelAction::HideOverlay => {
-                    if let Some(mut child) = self.child.take() {
-                        info!("killing overlay process");
-                        let _ = child.kill();
+                style::PanelAction::CancelCalibration(i) => self.overlays[i].cancel_calibration(),
+                style::PanelAction::SetPattern(i, on) => self.overlays[i].set_pattern(on),
+                style::PanelAction::RefreshMonitors => self.monitors = self.platform.monitors(),
+                style::PanelAction::LoadProfile(name) => {
+                    if let Err(e) = self.load_profile(&name) {
+                        warn!("{e}");
                     }
                 }
+                style::PanelAction::SaveProfile(name) => self.save_profile(&name),
+                style::PanelAction::ExportSvg => self.export_svg(),
+                style::PanelAction::ImportSvg => self.import_svg(),
                 style::PanelAction::None => {}
             }
         });
 
-        ipc::send_config(&mut self.child, &self.config, &mut self.prev_config);
+        // Each frame's edits are one step, or part of one while the mouse is held
+        let dragging = ctx.input(|i| i.pointer.any_down());
+        let snapshot = self.snapshot();
+        self.history.record(&snapshot, dragging);
+        self.send_configs(ctx);
     }
 
     fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
-        if let Some(mut child) = self.child.take() {
-            info!("panel exiting, killing overlay process");
-            let _ = child.kill();
-        }
+        info!("panel exiting, killing overlay processes");
+        self.hide_overlays();
     }
 }
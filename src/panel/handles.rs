@@ -0,0 +1,144 @@
+//! Direct manipulation of a crosshair on the preview: what each drag handle
+//! edits and how a drag or an arrow key changes the config.
+//!
+//! The crosshair is a dot and a ring, both circles, so there are handles for
+//! the dot radius, the ring radius (which sets the gap to the dot) and the
+//! ring thickness, but none for rotation.
+
+use eframe::egui::{Vec2, vec2};
+
+use crate::config::{Config, OFFSET_RANGE, RADIUS_RANGE, STROKE_RANGE, clamp_to};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Handle {
+    /// The whole crosshair: sets `offset_x`/`offset_y`
+    Move,
+    /// On the dot's right edge: `inner_radius`
+    Dot,
+    /// On top of the ring: `outer_radius`
+    Ring,
+    /// On the ring's outer edge, up and right: `stroke_width`
+    Thickness,
+}
+
+impl Handle {
+    /// Handles drawn on the preview, in hit-test order.
+    pub const SIZES: [Handle; 3] = [Handle::Thickness, Handle::Ring, Handle::Dot];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Move => "Offset",
+            Self::Dot => "Inner Radius",
+            Self::Ring => "Outer Radius (gap)",
+            Self::Thickness => "Stroke Width",
+        }
+    }
+
+    /// Unit vector the setting grows along, screen coordinates (y down).
+    fn direction(self) -> Vec2 {
+        match self {
+            Self::Move => Vec2::ZERO,
+            Self::Dot => vec2(1.0, 0.0),
+            Self::Ring => vec2(0.0, -1.0),
+            Self::Thickness => vec2(1.0, -1.0).normalized(),
+        }
+    }
+
+    /// Where the handle sits relative to the crosshair's center, in physical pixels.
+    pub fn position(self, config: &Config) -> Vec2 {
+        let distance = match self {
+            Self::Move => 0.0,
+            Self::Dot => config.inner_radius,
+            Self::Ring => config.outer_radius,
+            Self::Thickness => config.outer_radius + config.stroke_width / 2.0,
+        };
+        self.direction() * distance
+    }
+
+    /// Apply a drag that has moved `total` physical pixels since it started on
+    /// `start`. `constrain` (Shift) keeps a move on one axis and sizes on whole pixels.
+    pub fn drag(self, config: &mut Config, start: &Config, total: Vec2, constrain: bool) {
+        let along = total.dot(self.direction());
+        let size = |value: f32, range| clamp_to(if constrain { value.round() } else { value }, range);
+        match self {
+            Self::Move => {
+                let total = match constrain {
+                    true if total.x.abs() >= total.y.abs() => vec2(total.x, 0.0),
+                    true => vec2(0.0, total.y),
+                    false => total,
+                };
+                config.offset_x = clamp_to((start.offset_x + total.x).round(), OFFSET_RANGE);
+                config.offset_y = clamp_to((start.offset_y + total.y).round(), OFFSET_RANGE);
+            }
+            Self::Dot => config.inner_radius = size(start.inner_radius + along, RADIUS_RANGE),
+            Self::Ring => config.outer_radius = size(start.outer_radius + along, RADIUS_RANGE),
+            // The stroke is centered on the ring, so its outer edge moves half as far
+            Self::Thickness => config.stroke_width = size(start.stroke_width + along * 2.0, STROKE_RANGE),
+        }
+    }
+
+    /// Nudge with an arrow key: `(dx, dy)` is the key's direction times the step.
+    /// Offsets move that way; sizes grow with right or up and shrink with left or down.
+    pub fn nudge(self, config: &mut Config, dx: f32, dy: f32) {
+        let grow = dx - dy;
+        match self {
+            Self::Move => {
+                config.offset_x = clamp_to(config.offset_x + dx, OFFSET_RANGE);
+                config.offset_y = clamp_to(config.offset_y + dy, OFFSET_RANGE);
+            }
+            Self::Dot => config.inner_radius = clamp_to(config.inner_radius + grow, RADIUS_RANGE),
+            Self::Ring => config.outer_radius = clamp_to(config.outer_radius + grow, RADIUS_RANGE),
+            Self::Thickness => config.stroke_width = clamp_to(config.stroke_width + grow, STROKE_RANGE),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dragging_handles_edits_their_setting() {
+        let start = Config::default();
+        let mut config = start.clone();
+
+        Handle::Dot.drag(&mut config, &start, vec2(2.0, 5.0), false);
+        assert_eq!(config.inner_radius, start.inner_radius + 2.0);
+        Handle::Ring.drag(&mut config, &start, vec2(0.0, -4.0), false);
+        assert_eq!(config.outer_radius, start.outer_radius + 4.0);
+        Handle::Thickness.drag(&mut config, &start, vec2(1.0, -1.0).normalized(), false);
+        assert!((config.stroke_width - (start.stroke_width + 2.0)).abs() < 1e-5);
+        Handle::Dot.drag(&mut config, &start, vec2(-100.0, 0.0), false);
+        assert_eq!(config.inner_radius, *RADIUS_RANGE.start(), "clamped like the slider");
+
+        Handle::Move.drag(&mut config, &start, vec2(7.4, -2.0), false);
+        assert_eq!((config.offset_x, config.offset_y), (7.0, -2.0));
+        Handle::Move.drag(&mut config, &start, vec2(7.4, -2.0), true);
+        assert_eq!((config.offset_x, config.offset_y), (7.0, 0.0), "shift keeps one axis");
+        Handle::Ring.drag(&mut config, &start, vec2(0.0, -1.3), true);
+        assert_eq!(config.outer_radius, 5.0, "shift snaps sizes to whole pixels");
+    }
+
+    #[test]
+    fn arrows_nudge_the_selected_handle() {
+        let mut config = Config::default();
+        Handle::Move.nudge(&mut config, -1.0, 0.0);
+        Handle::Move.nudge(&mut config, 0.0, 1.0);
+        assert_eq!((config.offset_x, config.offset_y), (-1.0, 1.0));
+
+        let before = config.outer_radius;
+        Handle::Ring.nudge(&mut config, 0.0, -1.0);
+        assert_eq!(config.outer_radius, before + 1.0, "up grows");
+        Handle::Ring.nudge(&mut config, -1.0, 0.0);
+        assert_eq!(config.outer_radius, before, "left shrinks");
+    }
+
+    #[test]
+    fn handles_sit_on_the_crosshair() {
+        let config = Config::default();
+        assert_eq!(Handle::Dot.position(&config), vec2(config.inner_radius, 0.0));
+        assert_eq!(Handle::Ring.position(&config), vec2(0.0, -config.outer_radius));
+        let edge = Handle::Thickness.position(&config).length();
+        assert!((edge - (config.outer_radius + config.stroke_width / 2.0)).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,156 @@
+//! Undo and redo for the panel's edits: snapshots of the whole config, taken
+//! once a frame. Changes made while the mouse is held, like a slider or
+//! handle drag, merge into a single step.
+
+use std::collections::VecDeque;
+
+/// Steps kept for undo; the oldest are dropped past this
+pub const LIMIT: usize = 100;
+
+pub struct History<T> {
+    /// Earlier states, oldest first
+    past: VecDeque<T>,
+    /// States undone, most recently undone last
+    future: Vec<T>,
+    /// The state as last recorded
+    present: T,
+    limit: usize,
+    /// The last change was made mid-drag, so further changes extend it
+    merging: bool,
+}
+
+impl<T: Clone + PartialEq> History<T> {
+    pub fn new(present: T, limit: usize) -> Self {
+        Self {
+            past: VecDeque::new(),
+            future: Vec::new(),
+            present,
+            limit: limit.max(1),
+            merging: false,
+        }
+    }
+
+    /// Note the current `state`. A change becomes a new undo step, unless the
+    /// previous change was made with `merge` set and this one continues it.
+    pub fn record(&mut self, state: &T, merge: bool) {
+        if *state == self.present {
+            self.merging &= merge;
+            return;
+        }
+        if !self.merging {
+            let previous = std::mem::replace(&mut self.present, state.clone());
+            self.past.push_back(previous);
+            if self.past.len() > self.limit {
+                self.past.pop_front();
+            }
+        } else {
+            self.present = state.clone();
+        }
+        self.future.clear();
+        self.merging = merge;
+    }
+
+    /// Step back, returning the state to restore.
+    pub fn undo(&mut self) -> Option<&T> {
+        let previous = self.past.pop_back()?;
+        self.future.push(std::mem::replace(&mut self.present, previous));
+        self.merging = false;
+        Some(&self.present)
+    }
+
+    /// Step forward again after [`History::undo`], returning the state to restore.
+    pub fn redo(&mut self) -> Option<&T> {
+        let next = self.future.pop()?;
+        self.past.push_back(std::mem::replace(&mut self.present, next));
+        self.merging = false;
+        Some(&self.present)
+    }
+
+    /// Replace the current state without making an undo step, for changes
+    /// that shouldn't be undone on their own.
+    pub fn amend(&mut self, state: &T) {
+        self.present = state.clone();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.past.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.future.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drags_merge_into_one_step() {
+        let mut history = History::new(0, LIMIT);
+        // A drag over several frames, held still for one, then released
+        for (value, held) in [(1, true), (2, true), (2, true), (3, true), (3, false)] {
+            history.record(&value, held);
+        }
+        // Two separate clicks
+        history.record(&4, false);
+        history.record(&5, false);
+
+        assert_eq!(history.undo(), Some(&4));
+        assert_eq!(history.undo(), Some(&3));
+        assert_eq!(history.undo(), Some(&0), "the whole drag undone at once");
+        assert_eq!(history.undo(), None);
+        assert_eq!(history.redo(), Some(&3));
+    }
+
+    #[test]
+    fn a_new_drag_is_its_own_step() {
+        let mut history = History::new(0, LIMIT);
+        history.record(&1, true);
+        // Released without further change, then pressed again
+        history.record(&1, false);
+        history.record(&2, true);
+        assert_eq!(history.undo(), Some(&1));
+    }
+
+    #[test]
+    fn redo_is_dropped_by_a_new_edit() {
+        let mut history = History::new("a", LIMIT);
+        history.record(&"b", false);
+        history.record(&"c", false);
+        assert_eq!(history.undo(), Some(&"b"));
+        assert!(history.can_redo());
+        // The restored state is already recorded
+        history.record(&"b", false);
+        assert!(history.can_redo());
+
+        history.record(&"d", false);
+        assert!(!history.can_redo());
+        assert_eq!(history.redo(), None);
+        assert_eq!(history.undo(), Some(&"b"));
+        assert_eq!(history.undo(), Some(&"a"));
+    }
+
+    #[test]
+    fn oldest_steps_are_dropped_past_the_limit() {
+        let mut history = History::new(0, 3);
+        for value in 1..=5 {
+            history.record(&value, false);
+        }
+        assert_eq!(history.undo(), Some(&4));
+        assert_eq!(history.undo(), Some(&3));
+        assert_eq!(history.undo(), Some(&2));
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn amended_state_is_restored_by_redo() {
+        let mut history = History::new(("a", None), LIMIT);
+        history.record(&("b", None), false);
+        history.amend(&("b", Some("saved")));
+        assert!(!history.can_redo());
+        assert_eq!(history.undo(), Some(&("a", None)));
+        assert!(!history.can_undo(), "amending made no step");
+        assert_eq!(history.redo(), Some(&("b", Some("saved"))));
+    }
+}
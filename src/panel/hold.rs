@@ -0,0 +1,173 @@
+use crossbeam_channel::Receiver;
+use log::warn;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use crate::hotkey::Trigger;
+use crate::platform::input::InputPoller;
+
+/// How often held triggers are read; a fraction of a frame, so the overlay
+/// reacts on the next frame after a click.
+const POLL_INTERVAL: Duration = Duration::from_millis(4);
+
+/// Watches the triggers of the overlays' activation modes on a background
+/// thread and reports presses and releases.
+pub struct HoldWatcher {
+    /// Triggers to poll; the thread waits on the condvar while there are none
+    triggers: Arc<(Mutex<Vec<Trigger>>, Condvar)>,
+    changes: Receiver<(Trigger, bool)>,
+    /// Latest state of every watched trigger
+    held: HashMap<Trigger, bool>,
+}
+
+impl HoldWatcher {
+    /// Start polling. `wake` is called after each change so the panel picks it up.
+    /// Nothing is polled until [`HoldWatcher::watch`] gives a trigger.
+    pub fn start(mut poller: Box<dyn InputPoller>, wake: Box<dyn Fn() + Send>) -> Self {
+        let triggers = Arc::new((Mutex::new(Vec::new()), Condvar::new()));
+        let (tx, changes) = crossbeam_channel::unbounded();
+        let watched = triggers.clone();
+        std::thread::spawn(move || {
+            let mut state = PollState::default();
+            loop {
+                let triggers = {
+                    let (list, added) = &*watched;
+                    let mut list = list.lock().unwrap();
+                    while list.is_empty() {
+                        // Forget old state, so triggers watched again are reported afresh
+                        state.poll(poller.as_mut(), &[]);
+                        list = added.wait(list).unwrap();
+                    }
+                    list.clone()
+                };
+                let changed = state.poll(poller.as_mut(), &triggers);
+                if !changed.is_empty() {
+                    for change in changed {
+                        if tx.send(change).is_err() {
+                            return;
+                        }
+                    }
+                    wake();
+                }
+                std::thread::sleep(POLL_INTERVAL);
+            }
+        });
+        Self {
+            triggers,
+            changes,
+            held: HashMap::new(),
+        }
+    }
+
+    /// Replace the set of triggers to watch.
+    pub fn watch(&mut self, triggers: Vec<Trigger>) {
+        let (list, added) = &*self.triggers;
+        let mut watched = list.lock().unwrap();
+        if *watched != triggers {
+            self.held.retain(|t, _| triggers.contains(t));
+            *watched = triggers;
+            added.notify_one();
+        }
+    }
+
+    /// Apply changes reported since the last call.
+    pub fn update(&mut self) {
+        for (trigger, held) in self.changes.try_iter() {
+            self.held.insert(trigger, held);
+        }
+    }
+
+    /// Whether `trigger` is held. Triggers that are not watched, or can't be
+    /// read on this platform, count as released.
+    pub fn held(&self, trigger: Trigger) -> bool {
+        self.held.get(&trigger).copied().unwrap_or(false)
+    }
+}
+
+/// What the polling thread knows between polls.
+#[derive(Default)]
+struct PollState {
+    held: HashMap<Trigger, bool>,
+    /// Triggers already warned about as unreadable
+    unreadable: HashSet<Trigger>,
+}
+
+impl PollState {
+    /// Read every trigger and return the ones that changed since the last
+    /// poll. A trigger seen for the first time is always reported.
+    fn poll(&mut self, poller: &mut dyn InputPoller, triggers: &[Trigger]) -> Vec<(Trigger, bool)> {
+        self.held.retain(|t, _| triggers.contains(t));
+        let mut changed = Vec::new();
+        for &trigger in triggers {
+            let held = poller.held(trigger).unwrap_or_else(|| {
+                if self.unreadable.insert(trigger) {
+                    warn!("cannot read {trigger} on this platform, treating it as released");
+                }
+                false
+            });
+            if self.held.insert(trigger, held) != Some(held) {
+                changed.push((trigger, held));
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::Platform;
+    use crate::platform::headless::Headless;
+
+    #[test]
+    fn reports_presses_and_releases_once() {
+        let platform = Headless::default();
+        let mut poller = platform.input_poller();
+        let mut state = PollState::default();
+        let triggers = [Trigger::MouseRight, Trigger::Shift];
+
+        assert_eq!(
+            state.poll(poller.as_mut(), &triggers),
+            vec![(Trigger::MouseRight, false), (Trigger::Shift, false)]
+        );
+        assert!(state.poll(poller.as_mut(), &triggers).is_empty());
+
+        platform.set_held(Trigger::MouseRight, true);
+        assert_eq!(state.poll(poller.as_mut(), &triggers), vec![(Trigger::MouseRight, true)]);
+        assert!(state.poll(poller.as_mut(), &triggers).is_empty());
+        platform.set_held(Trigger::MouseRight, false);
+        assert_eq!(state.poll(poller.as_mut(), &triggers), vec![(Trigger::MouseRight, false)]);
+
+        // A trigger that is watched again starts over
+        platform.set_held(Trigger::Shift, true);
+        assert!(state.poll(poller.as_mut(), &[]).is_empty());
+        assert_eq!(state.poll(poller.as_mut(), &[Trigger::Shift]), vec![(Trigger::Shift, true)]);
+    }
+
+    #[test]
+    fn unreadable_triggers_count_as_released() {
+        let mut poller = crate::platform::input::NoInput;
+        let mut state = PollState::default();
+        assert_eq!(state.poll(&mut poller, &[Trigger::Mouse4]), vec![(Trigger::Mouse4, false)]);
+        assert!(state.poll(&mut poller, &[Trigger::Mouse4]).is_empty());
+    }
+
+    #[test]
+    fn idle_watcher_starts_polling_when_given_a_trigger() {
+        let platform = Headless::default();
+        platform.set_held(Trigger::MouseRight, true);
+        let mut watcher = HoldWatcher::start(platform.input_poller(), Box::new(|| {}));
+        std::thread::sleep(Duration::from_millis(20));
+        watcher.update();
+        assert!(!watcher.held(Trigger::MouseRight), "nothing watched yet");
+
+        watcher.watch(vec![Trigger::MouseRight]);
+        let seen = (0..200).any(|_| {
+            std::thread::sleep(Duration::from_millis(10));
+            watcher.update();
+            watcher.held(Trigger::MouseRight)
+        });
+        assert!(seen, "the waiting thread picks up the new trigger");
+    }
+}
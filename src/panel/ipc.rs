@@ -1,30 +1,80 @@
+use crossbeam_channel::Receiver;
+use eframe::egui;
 use log::{debug, error, info, warn};
-use std::io::Write;
-use std::process::{Child, Command, Stdio};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::time::{Duration, Instant};
 
 use crate::config::Config;
+use crate::geometry::Placement;
+use crate::overlay::{OverlayCommand, OverlayEvent};
+use crate::platform::Platform;
 
-pub fn spawn_overlay(
-    #[cfg(target_os = "windows")] job: &Option<crate::platform::JobObject>,
-) -> Option<Child> {
-    let exe = std::env::current_exe().expect("cannot find own executable");
-    let mut cmd = Command::new(exe);
-    cmd.arg("overlay").stdin(Stdio::piped());
+/// Minimum spacing between updates to one overlay while a control is being
+/// dragged, roughly one overlay frame at 60 Hz.
+pub const SEND_INTERVAL: Duration = Duration::from_millis(16);
+
+/// What a [`ConfigStream`] wants to do this frame.
+#[derive(Debug, PartialEq)]
+pub enum SendDecision {
+    /// Overlay is up to date.
+    Idle,
+    /// There is an update, but one was sent too recently. Check again after this long.
+    Wait(Duration),
+    Send,
+}
+
+/// Dirty tracking for one overlay's config. Edits bump a generation counter,
+/// so deciding whether to send costs an integer compare rather than a
+/// serialization, and drags are coalesced to one update per [`SEND_INTERVAL`].
+#[derive(Default)]
+pub struct ConfigStream {
+    generation: u64,
+    /// `None` until the current process has received a config
+    sent_generation: Option<u64>,
+    last_sent: Option<Instant>,
+}
+
+impl ConfigStream {
+    pub fn mark_dirty(&mut self) {
+        self.generation += 1;
+    }
+
+    /// Start over for a freshly spawned process that has received nothing yet.
+    pub fn reset(&mut self) {
+        self.sent_generation = None;
+        self.last_sent = None;
+    }
+
+    /// `dragging` rate-limits updates; once it ends the pending state goes out immediately.
+    pub fn poll(&self, now: Instant, dragging: bool) -> SendDecision {
+        if self.sent_generation == Some(self.generation) {
+            return SendDecision::Idle;
+        }
+        match self.last_sent {
+            Some(last) if dragging && now.duration_since(last) < SEND_INTERVAL => {
+                SendDecision::Wait(SEND_INTERVAL - now.duration_since(last))
+            }
+            _ => SendDecision::Send,
+        }
+    }
 
-    #[cfg(target_os = "windows")]
-    {
-        use std::os::windows::process::CommandExt;
-        const CREATE_NO_WINDOW: u32 = 0x08000000;
-        cmd.creation_flags(CREATE_NO_WINDOW);
+    pub fn mark_sent(&mut self, now: Instant) {
+        self.sent_generation = Some(self.generation);
+        self.last_sent = Some(now);
     }
+}
 
-    match cmd.spawn() {
+fn spawn_overlay(platform: &dyn Platform) -> Option<Child> {
+    let exe = std::env::current_exe().expect("cannot find own executable");
+    let mut cmd = Command::new(exe);
+    cmd.args(["overlay", "--stdin"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped());
+
+    match platform.spawn_supervised(&mut cmd) {
         Ok(child) => {
             info!("spawned overlay process (pid: {})", child.id());
-            #[cfg(target_os = "windows")]
-            if let Some(job) = job {
-                job.assign(&child);
-            }
             Some(child)
         }
         Err(e) => {
@@ -34,24 +84,309 @@ pub fn spawn_overlay(
     }
 }
 
-pub fn send_config(child: &mut Option<Child>, config: &Config, prev_config: &mut Option<String>) {
-    let json = match serde_json::to_string(config) {
-        Ok(j) => j,
-        Err(_) => return,
-    };
+/// Forward [`OverlayEvent`]s from an overlay's stdout, waking the panel for each.
+fn spawn_event_reader(stdout: ChildStdout, ctx: egui::Context) -> Receiver<OverlayEvent> {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            let Ok(line) = line else { break };
+            match serde_json::from_str(&line) {
+                Ok(event) => {
+                    if tx.send(event).is_err() {
+                        break;
+                    }
+                    ctx.request_repaint();
+                }
+                Err(e) => warn!("bad event from overlay: {e}"),
+            }
+        }
+    });
+    rx
+}
+
+/// One overlay child process and its own config stream.
+/// The process is killed when this is dropped.
+#[derive(Default)]
+pub struct OverlayProcess {
+    child: Option<Child>,
+    stream: ConfigStream,
+    events: Option<Receiver<OverlayEvent>>,
+    /// Where the overlay last reported itself
+    placement: Option<Placement>,
+    /// The overlay said its anchored window is minimized or missing
+    hidden: bool,
+    /// Activation trigger state last sent to the current process
+    held_sent: Option<bool>,
+    /// Offsets shown on the overlay while it is being calibrated
+    calibrating: Option<(f32, f32)>,
+    /// The test pattern is wanted; kept across restarts of the process
+    pattern: bool,
+}
+
+impl OverlayProcess {
+    pub fn is_running(&self) -> bool {
+        self.child.is_some()
+    }
+
+    pub fn start(&mut self, ctx: &egui::Context, platform: &dyn Platform) {
+        if self.child.is_some() {
+            return;
+        }
+        self.child = spawn_overlay(platform);
+        self.events = self
+            .child
+            .as_mut()
+            .and_then(|c| c.stdout.take())
+            .map(|stdout| spawn_event_reader(stdout, ctx.clone()));
+        // Force a full sync to the new process
+        self.stream.reset();
+        self.held_sent = None;
+        if self.pattern {
+            self.write(&OverlayCommand::Pattern(true));
+        }
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            info!("killing overlay process (pid: {})", child.id());
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        self.events = None;
+        self.placement = None;
+        self.hidden = false;
+        self.calibrating = None;
+    }
+
+    /// Apply events the overlay sent since the last call. Returns the offsets
+    /// to store when the overlay reports a confirmed calibration.
+    pub fn poll_events(&mut self) -> Option<(f32, f32)> {
+        let events = self.events.as_ref()?;
+        let mut confirmed = None;
+        for event in events.try_iter() {
+            match event {
+                OverlayEvent::Placed(placement) => {
+                    self.placement = Some(placement);
+                    self.hidden = false;
+                }
+                OverlayEvent::Hidden => {
+                    self.placement = None;
+                    self.hidden = true;
+                }
+                OverlayEvent::Calibrating(x, y) => self.calibrating = Some((x, y)),
+                OverlayEvent::Calibrated(offsets) => {
+                    self.calibrating = None;
+                    confirmed = offsets;
+                }
+            }
+        }
+        confirmed
+    }
+
+    /// Put the overlay into calibration mode, starting from `config`'s offsets.
+    pub fn calibrate(&mut self, config: &Config, platform: &dyn Platform) {
+        let Some(child) = &self.child else {
+            return;
+        };
+        // The overlay takes the foreground for the arrow keys
+        platform.allow_set_foreground_window(child.id());
+        if self.write(&OverlayCommand::Calibrate(true)) {
+            self.calibrating = Some((config.offset_x, config.offset_y));
+        }
+    }
+
+    /// Leave calibration mode, reverting the offsets.
+    pub fn cancel_calibration(&mut self) {
+        if self.calibrating.is_some() {
+            self.write(&OverlayCommand::Calibrate(false));
+        }
+    }
+
+    /// Show or hide the test pattern, now and whenever the overlay restarts.
+    pub fn set_pattern(&mut self, on: bool) {
+        self.pattern = on;
+        if self.child.is_some() {
+            self.write(&OverlayCommand::Pattern(on));
+        }
+    }
+
+    pub fn pattern(&self) -> bool {
+        self.pattern
+    }
+
+    /// The offsets on the overlay while it is being calibrated.
+    pub fn calibrating(&self) -> Option<(f32, f32)> {
+        self.calibrating
+    }
+
+    pub fn placement(&self) -> Option<&Placement> {
+        self.placement.as_ref()
+    }
+
+    pub fn hidden(&self) -> bool {
+        self.hidden
+    }
+
+    /// Record that this overlay's config was edited.
+    pub fn mark_dirty(&mut self) {
+        self.stream.mark_dirty();
+    }
 
-    if prev_config.as_deref() == Some(&json) {
-        return;
+    /// Tell the overlay whether its activation trigger is held, if that changed.
+    pub fn send_held(&mut self, held: bool) {
+        if self.child.is_none() || self.held_sent == Some(held) {
+            return;
+        }
+        if self.write(&OverlayCommand::Held(held)) {
+            self.held_sent = Some(held);
+        }
     }
-    debug!("config changed, sending to overlay");
-    *prev_config = Some(json.clone());
 
-    if let Some(child) = child
-        && let Some(stdin) = &mut child.stdin
-    {
-        let line = format!("{json}\n");
-        if stdin.write_all(line.as_bytes()).is_err() {
+    /// Write one command line to the overlay's stdin. Returns false, and
+    /// stops the overlay, if it can't be written.
+    fn write(&mut self, command: &OverlayCommand) -> bool {
+        let Some(stdin) = self.child.as_mut().and_then(|c| c.stdin.as_mut()) else {
+            return false;
+        };
+        let Ok(json) = serde_json::to_string(command) else {
+            return false;
+        };
+        if stdin.write_all(format!("{json}\n").as_bytes()).is_err() {
             warn!("overlay stdin write failed, process likely died");
+            self.stop();
+            return false;
+        }
+        true
+    }
+
+    /// Send the config if it changed and the rate limit allows; `config` is only
+    /// called to build it then. Returns how long to wait before trying again
+    /// when an update is being held back.
+    pub fn send_config(&mut self, config: impl FnOnce() -> Config, now: Instant, dragging: bool) -> Option<Duration> {
+        let child = self.child.as_mut()?;
+        match self.stream.poll(now, dragging) {
+            SendDecision::Idle => return None,
+            SendDecision::Wait(delay) => return Some(delay),
+            SendDecision::Send => {}
+        }
+        debug!("config changed, sending to overlay (pid: {})", child.id());
+        if !self.write(&OverlayCommand::Config(Box::new(config()))) {
+            return None;
+        }
+        self.stream.mark_sent(now);
+        None
+    }
+}
+
+impl Drop for OverlayProcess {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits_within_an_interval_send_once() {
+        let start = Instant::now();
+        let mut stream = ConfigStream::default();
+        stream.mark_dirty();
+        assert_eq!(stream.poll(start, true), SendDecision::Send);
+        stream.mark_sent(start);
+
+        for ms in 1..SEND_INTERVAL.as_millis() as u64 {
+            stream.mark_dirty();
+            let now = start + Duration::from_millis(ms);
+            match stream.poll(now, true) {
+                SendDecision::Wait(delay) => assert_eq!(now + delay, start + SEND_INTERVAL),
+                decision => panic!("{decision:?} at {ms} ms"),
+            }
+        }
+        // The held-back edits go out together
+        let now = start + SEND_INTERVAL;
+        assert_eq!(stream.poll(now, true), SendDecision::Send);
+        stream.mark_sent(now);
+        assert_eq!(stream.poll(now, true), SendDecision::Idle);
+
+        // Once the drag ends, a pending edit goes out without waiting
+        stream.mark_dirty();
+        assert_eq!(stream.poll(now + Duration::from_millis(1), false), SendDecision::Send);
+    }
+
+    #[test]
+    fn unchanged_config_is_idle() {
+        let start = Instant::now();
+        let mut stream = ConfigStream::default();
+        assert_eq!(stream.poll(start, false), SendDecision::Send, "a new process needs a first config");
+        stream.mark_sent(start);
+        for ms in [0, 5, 100, 10_000] {
+            assert_eq!(stream.poll(start + Duration::from_millis(ms), false), SendDecision::Idle);
+            assert_eq!(stream.poll(start + Duration::from_millis(ms), true), SendDecision::Idle);
         }
     }
+
+    #[test]
+    fn reset_forces_a_full_resend() {
+        let start = Instant::now();
+        let mut stream = ConfigStream::default();
+        stream.mark_dirty();
+        stream.mark_sent(start);
+        assert_eq!(stream.poll(start, true), SendDecision::Idle);
+
+        // A restarted process gets the config at once, even mid-drag
+        stream.reset();
+        assert_eq!(stream.poll(start + Duration::from_millis(1), true), SendDecision::Send);
+    }
+
+    /// A shell script stands in for the overlay: it answers the first config
+    /// with `Hidden` and the calibration start with a confirmed calibration.
+    #[cfg(unix)]
+    #[test]
+    fn overlay_process_drives_a_headless_child() {
+        use crate::platform::headless::Headless;
+
+        // Replies come from another process, so give each a few seconds
+        fn wait_for(overlay: &mut OverlayProcess, mut done: impl FnMut(&mut OverlayProcess) -> bool) {
+            let answered = (0..500).any(|_| {
+                std::thread::sleep(Duration::from_millis(10));
+                done(overlay)
+            });
+            assert!(answered, "the stand-in overlay never answered");
+        }
+
+        let script = r#"read -r _; echo '"Hidden"'; read -r _; echo '{"Calibrated":[3.0,-1.5]}'; cat >/dev/null"#;
+        let platform = Headless::default();
+        platform.set_stand_in(&["sh", "-c", script]);
+        let ctx = egui::Context::default();
+        let mut overlay = OverlayProcess::default();
+        overlay.start(&ctx, &platform);
+        assert!(overlay.is_running());
+        assert_eq!(platform.spawned(), vec![vec!["overlay".to_string(), "--stdin".to_string()]]);
+
+        assert_eq!(overlay.send_config(Config::default, Instant::now(), false), None);
+        wait_for(&mut overlay, |o| {
+            o.poll_events();
+            o.hidden()
+        });
+
+        let config = Config {
+            offset_x: 1.0,
+            ..Config::default()
+        };
+        overlay.calibrate(&config, &platform);
+        assert_eq!(overlay.calibrating(), Some((1.0, 0.0)));
+        assert_eq!(platform.foreground_allowed().len(), 1);
+        let mut confirmed = None;
+        wait_for(&mut overlay, |o| {
+            confirmed = o.poll_events();
+            confirmed.is_some()
+        });
+        assert_eq!(confirmed, Some((3.0, -1.5)));
+        assert_eq!(overlay.calibrating(), None);
+
+        overlay.stop();
+        assert!(!overlay.is_running());
+    }
 }
@@ -1,5 +1,9 @@
 mod app;
-mod ipc;
+mod handles;
+mod history;
+mod hold;
+pub mod ipc;
+mod preview;
 mod style;
 mod tray;
 
@@ -16,7 +20,6 @@ fn load_icon_rgba() -> (Vec<u8>, u32, u32) {
 }
 
 pub fn run() -> eframe::Result<()> {
-    crate::platform::set_app_user_model_id();
     let (rgba, w, h) = load_icon_rgba();
     let viewport_icon = egui::IconData {
         rgba: rgba.clone(),
@@ -27,7 +30,7 @@ pub fn run() -> eframe::Result<()> {
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_title("AIMX Control Panel")
-            .with_inner_size([350.0, 450.0])
+            .with_inner_size([350.0, 540.0])
             .with_icon(viewport_icon),
         ..Default::default()
     };
@@ -36,18 +39,9 @@ pub fn run() -> eframe::Result<()> {
         "AIMX Control Panel",
         options,
         Box::new(|cc| {
-            #[cfg(target_os = "windows")]
-            {
-                use raw_window_handle::HasWindowHandle;
-                if let Ok(handle) = cc.window_handle()
-                    && let raw_window_handle::RawWindowHandle::Win32(w) = handle.as_raw()
-                {
-                    crate::platform::store_panel_hwnd(w.hwnd.get());
-                }
-            }
-            #[cfg(not(target_os = "windows"))]
-            let _ = cc;
-            Ok(Box::new(app::PanelApp::new()))
+            let platform = crate::platform::current();
+            platform.register_panel_window(cc);
+            Ok(Box::new(app::PanelApp::new(&cc.egui_ctx, platform)))
         }),
     )
 }
@@ -0,0 +1,265 @@
+//! The panel's live crosshair preview: the edited config rendered on the CPU
+//! exactly as the overlay draws it, magnified so single pixels can be judged,
+//! with drag handles for its size and offset.
+
+use eframe::egui::{self, Color32, ColorImage};
+use std::path::PathBuf;
+
+use crate::config::{Config, Style};
+use crate::export::{self, Background, RenderOptions};
+use crate::raster::Renderer;
+use super::handles::Handle;
+
+pub const MAX_ZOOM: u32 = 16;
+/// The preview scrolls rather than growing past this height, in points
+const MAX_HEIGHT: f32 = 240.0;
+/// Radius of a drawn handle, and how close a press must land to grab it, in points
+const HANDLE_RADIUS: f32 = 4.0;
+const HANDLE_REACH: f32 = 8.0;
+
+/// Solid backgrounds offered next to the checkerboard and a screenshot.
+const SOLID_BACKGROUNDS: [(&str, Color32); 3] = [
+    ("Dark", Color32::from_rgb(32, 32, 32)),
+    ("Light", Color32::from_rgb(224, 224, 224)),
+    ("Sky", Color32::from_rgb(120, 170, 220)),
+];
+
+pub struct Preview {
+    renderer: Renderer,
+    options: RenderOptions,
+    /// Text of the screenshot path field
+    screenshot_path: String,
+    /// The loaded [`Background::Image`] and where it came from
+    screenshot: Option<(PathBuf, ColorImage)>,
+    /// Why the screenshot could not be loaded
+    error: Option<String>,
+    texture: Option<egui::TextureHandle>,
+    /// What `texture` shows, so it is only re-rendered after a change
+    drawn: Option<(Style, RenderOptions)>,
+    /// The handle arrow keys nudge: the last one grabbed
+    selected: Handle,
+    /// The handle being dragged and the config when the drag started
+    dragging: Option<(Handle, Config)>,
+}
+
+impl Default for Preview {
+    fn default() -> Self {
+        Self {
+            renderer: Renderer::new(),
+            options: RenderOptions {
+                scale: 8,
+                grid: true,
+                background: Background::Color(SOLID_BACKGROUNDS[0].1),
+            },
+            screenshot_path: String::new(),
+            screenshot: None,
+            error: None,
+            texture: None,
+            drawn: None,
+            selected: Handle::Move,
+            dragging: None,
+        }
+    }
+}
+
+impl Preview {
+    /// The preview image for `config` with the current zoom, grid and background.
+    pub fn image(&mut self, config: &Config) -> ColorImage {
+        let crosshair = self.renderer.render_crosshair(config, 1.0);
+        export::compose(&crosshair, &self.options, self.screenshot.as_ref().map(|(_, image)| image))
+    }
+
+    /// Show the zoom, grid and background controls and the preview itself.
+    /// Returns whether a handle edited `config`.
+    pub fn show(&mut self, ui: &mut egui::Ui, config: &mut Config) -> bool {
+        ui.horizontal(|ui| {
+            ui.add(egui::Slider::new(&mut self.options.scale, 1..=MAX_ZOOM).text("Zoom").suffix("x"));
+            ui.checkbox(&mut self.options.grid, "Grid");
+        });
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Background:");
+            for (name, color) in SOLID_BACKGROUNDS {
+                ui.selectable_value(&mut self.options.background, Background::Color(color), name);
+            }
+            if let Background::Color(color) = &mut self.options.background {
+                ui.color_edit_button_srgba(color);
+            }
+            ui.selectable_value(&mut self.options.background, Background::Checkerboard, "Checkerboard");
+            if let Some((path, _)) = &self.screenshot {
+                ui.selectable_value(&mut self.options.background, Background::Image(path.clone()), "Screenshot");
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.screenshot_path)
+                    .hint_text("screenshot.png")
+                    .desired_width(180.0),
+            );
+            if ui.button("Load").on_hover_text("Use a PNG screenshot as the background").clicked() {
+                self.load_screenshot();
+            }
+        });
+        if let Some(error) = &self.error {
+            ui.colored_label(ui.visuals().warn_fg_color, error);
+        }
+
+        let key = (config.style(), self.options.clone());
+        if self.drawn.as_ref() != Some(&key) || self.texture.is_none() {
+            let image = self.image(config);
+            match &mut self.texture {
+                Some(texture) => texture.set(image, egui::TextureOptions::NEAREST),
+                None => {
+                    self.texture = Some(ui.ctx().load_texture("crosshair-preview", image, egui::TextureOptions::NEAREST));
+                }
+            }
+            self.drawn = Some(key);
+        }
+        let Some(texture) = self.texture.clone() else {
+            return false;
+        };
+        // One image pixel per physical pixel, so 1x is the size on screen
+        let ppp = ui.ctx().pixels_per_point();
+        let size = texture.size_vec2() / ppp;
+        let mut changed = false;
+        egui::ScrollArea::both()
+            .id_salt("preview")
+            .max_height(MAX_HEIGHT)
+            .scroll_source(egui::scroll_area::ScrollSource {
+                drag: false,
+                ..egui::scroll_area::ScrollSource::ALL
+            })
+            .show(ui, |ui| {
+                let image = egui::Image::new((texture.id(), size)).sense(egui::Sense::click_and_drag());
+                let response = ui.add(image);
+                changed = self.handle_input(ui, &response, config, self.options.scale as f32 / ppp);
+            });
+        ui.weak(format!(
+            "Drag the crosshair or a handle; Shift constrains, arrows nudge. Selected: {}",
+            self.selected.label()
+        ));
+        changed
+    }
+
+    /// Drag, arrow-key and drawing logic for the handles. `points_per_px` maps
+    /// the crosshair's physical pixels to points on the preview.
+    fn handle_input(&mut self, ui: &egui::Ui, response: &egui::Response, config: &mut Config, points_per_px: f32) -> bool {
+        let center = response.rect.center();
+        let at = |handle: Handle, config: &Config| center + handle.position(config) * points_per_px;
+        let mut changed = false;
+
+        if response.drag_started()
+            && let Some(origin) = ui.input(|i| i.pointer.press_origin())
+        {
+            let grabbed = Handle::SIZES
+                .into_iter()
+                .find(|h| at(*h, config).distance(origin) <= HANDLE_REACH)
+                .unwrap_or(Handle::Move);
+            self.selected = grabbed;
+            self.dragging = Some((grabbed, config.clone()));
+            response.request_focus();
+        }
+        if let Some((handle, start)) = &self.dragging {
+            let origin = ui.input(|i| i.pointer.press_origin());
+            if let (Some(origin), Some(pos)) = (origin, response.interact_pointer_pos()) {
+                let constrain = ui.input(|i| i.modifiers.shift);
+                let before = (config.offset_x, config.offset_y, config.style());
+                handle.drag(config, start, (pos - origin) / points_per_px, constrain);
+                changed |= before != (config.offset_x, config.offset_y, config.style());
+            }
+        }
+        if response.drag_stopped() {
+            self.dragging = None;
+        }
+        if response.clicked() {
+            response.request_focus();
+        }
+
+        if response.has_focus() {
+            // Keep the arrows for nudging rather than moving focus
+            ui.memory_mut(|m| {
+                m.set_focus_lock_filter(
+                    response.id,
+                    egui::EventFilter {
+                        horizontal_arrows: true,
+                        vertical_arrows: true,
+                        ..Default::default()
+                    },
+                )
+            });
+            for (key, dx, dy) in [
+                (egui::Key::ArrowLeft, -1.0, 0.0),
+                (egui::Key::ArrowRight, 1.0, 0.0),
+                (egui::Key::ArrowUp, 0.0, -1.0),
+                (egui::Key::ArrowDown, 0.0, 1.0),
+            ] {
+                if ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, key)) {
+                    self.selected.nudge(config, dx, dy);
+                    changed = true;
+                }
+            }
+        }
+
+        let painter = ui.painter();
+        let focused = response.has_focus();
+        for handle in Handle::SIZES {
+            let (fill, width) = match handle == self.selected && focused {
+                true => (ui.visuals().selection.bg_fill, 2.0),
+                false => (Color32::WHITE, 1.0),
+            };
+            painter.circle(at(handle, config), HANDLE_RADIUS, fill, egui::Stroke::new(width, Color32::BLACK));
+        }
+        if self.selected == Handle::Move && focused {
+            painter.rect_stroke(response.rect, 0.0, ui.visuals().selection.stroke, egui::StrokeKind::Inside);
+        }
+        let hovered = response.hover_pos().map(|pos| {
+            Handle::SIZES
+                .into_iter()
+                .find(|h| at(*h, config).distance(pos) <= HANDLE_REACH)
+        });
+        if let Some(hovered) = hovered {
+            ui.ctx().set_cursor_icon(match hovered {
+                Some(_) => egui::CursorIcon::Crosshair,
+                None => egui::CursorIcon::Move,
+            });
+        }
+        changed
+    }
+
+    fn load_screenshot(&mut self) {
+        let path = PathBuf::from(self.screenshot_path.trim());
+        match export::load_png(&path) {
+            Ok(image) => {
+                self.screenshot = Some((path.clone(), image));
+                self.options.background = Background::Image(path);
+                self.error = None;
+                // The path may be unchanged while the file isn't
+                self.drawn = None;
+            }
+            Err(e) => self.error = Some(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zoom_and_background_change_the_image() {
+        let config = Config::default();
+        let side = config.window_size_px() as usize;
+        let mut preview = Preview::default();
+        preview.options.scale = 1;
+        assert_eq!(preview.image(&config).size, [side, side]);
+
+        preview.options.scale = MAX_ZOOM;
+        preview.options.grid = false;
+        let dark = preview.image(&config);
+        assert_eq!(dark.size, [side * 16, side * 16]);
+        assert_eq!(dark.pixels[0], SOLID_BACKGROUNDS[0].1);
+
+        // A screenshot that never loaded leaves that background transparent
+        preview.options.background = Background::Image("missing.png".into());
+        assert_eq!(preview.image(&config).pixels[0], Color32::TRANSPARENT);
+    }
+}
@@ -1,6 +1,10 @@
 use eframe::egui::{self, Color32};
 
-use crate::config::Config;
+use crate::config::{Activation, Config, OFFSET_RANGE, RADIUS_RANGE, STROKE_RANGE, ShowWhen, Style, WindowMatch};
+use crate::geometry::Placement;
+use crate::hotkey::{self, Action, Bindings, Hotkey, Trigger};
+use crate::platform::Monitor;
+use super::preview::Preview;
 
 #[allow(dead_code)]
 pub struct PanelTheme {
@@ -27,18 +31,101 @@ pub enum PanelAction {
     None,
     Save,
     Reset,
+    /// Step back or forward through the edit history
+    Undo,
+    Redo,
     ShowOverlay,
     HideOverlay,
+    SelectOverlay(usize),
+    AddOverlay,
+    RemoveOverlay(usize),
+    StartOverlay(usize),
+    StopOverlay(usize),
+    /// Drag or nudge the crosshair into place on the overlay itself
+    Calibrate(usize),
+    CancelCalibration(usize),
+    /// Show or hide the alignment test pattern on an overlay's monitor
+    SetPattern(usize, bool),
+    LoadProfile(String),
+    SaveProfile(String),
+    RefreshMonitors,
+    /// Write the selected crosshair to an SVG file
+    ExportSvg,
+    /// Read the selected crosshair back from its exported SVG file
+    ImportSvg,
 }
 
-pub fn draw_panel_ui(ui: &mut egui::Ui, config: &mut Config, overlay_alive: bool) -> PanelAction {
+/// A change to the hotkey bindings requested from the panel.
+pub enum HotkeyEdit {
+    None,
+    /// Start listening for the next key combination for this action
+    Capture(Action),
+    CancelCapture,
+    Set(Action, Hotkey),
+    Clear(Action),
+}
+
+/// Read-only state the panel shows next to the config being edited.
+pub struct PanelView<'a> {
+    /// Whether each overlay's process is running
+    pub running: &'a [bool],
+    /// Index of the overlay being edited
+    pub selected: usize,
+    pub profiles: &'a [String],
+    pub active_profile: Option<&'a str>,
+    pub monitors: &'a [Monitor],
+    /// Where the selected overlay last reported itself
+    pub placement: Option<&'a Placement>,
+    /// The selected overlay is hidden because its anchored window is minimized or missing
+    pub hidden: bool,
+    /// Outcome of the last file operation, e.g. where an export went
+    pub notice: Option<&'a str>,
+    /// The selected overlay is being calibrated and shows these offsets
+    pub calibrating: Option<(f32, f32)>,
+    /// The selected overlay shows the test pattern
+    pub pattern: bool,
+    pub can_undo: bool,
+    pub can_redo: bool,
+}
+
+pub fn draw_panel_ui(
+    ui: &mut egui::Ui,
+    config: &mut Config,
+    view: &PanelView,
+    new_profile_name: &mut String,
+    preview: &mut Preview,
+) -> (PanelAction, bool) {
+    let PanelView {
+        running,
+        selected,
+        profiles,
+        active_profile,
+        monitors,
+        placement,
+        hidden,
+        notice,
+        calibrating,
+        pattern,
+        can_undo,
+        can_redo,
+    } = *view;
     let mut action = PanelAction::None;
+    // Whether any crosshair setting was edited this frame
+    let mut changed = false;
 
-    ui.heading("Crosshair Settings");
+    ui.horizontal(|ui| {
+        ui.heading("Crosshair Settings");
+        if ui.add_enabled(can_undo, egui::Button::new("Undo")).on_hover_text("Ctrl+Z").clicked() {
+            action = PanelAction::Undo;
+        }
+        if ui.add_enabled(can_redo, egui::Button::new("Redo")).on_hover_text("Ctrl+Y").clicked() {
+            action = PanelAction::Redo;
+        }
+    });
     ui.separator();
 
     // Overlay control
-    if overlay_alive {
+    if running.iter().any(|&r| r) {
         if ui.button("Hide Overlay").clicked() {
             action = PanelAction::HideOverlay;
         }
@@ -47,15 +134,253 @@ pub fn draw_panel_ui(ui: &mut egui::Ui, config: &mut Config, overlay_alive: bool
     }
 
     ui.separator();
-    ui.label("Position Offset");
-    ui.add(egui::Slider::new(&mut config.offset_x, -500.0..=500.0).text("X"));
-    ui.add(egui::Slider::new(&mut config.offset_y, -500.0..=500.0).text("Y"));
+    ui.horizontal_wrapped(|ui| {
+        ui.label("Overlays:");
+        for (i, &alive) in running.iter().enumerate() {
+            let label = if alive { format!("{} ●", i + 1) } else { format!("{} ○", i + 1) };
+            if ui.selectable_label(i == selected, label).clicked() {
+                action = PanelAction::SelectOverlay(i);
+            }
+        }
+        if ui.button("+").on_hover_text("Add an overlay").clicked() {
+            action = PanelAction::AddOverlay;
+        }
+    });
+    ui.horizontal_wrapped(|ui| {
+        if running[selected] {
+            if ui.button("Stop This Overlay").clicked() {
+                action = PanelAction::StopOverlay(selected);
+            }
+        } else if ui.button("Start This Overlay").clicked() {
+            action = PanelAction::StartOverlay(selected);
+        }
+        if calibrating.is_some() {
+            if ui.button("Cancel Calibration").clicked() {
+                action = PanelAction::CancelCalibration(selected);
+            }
+        } else if ui
+            .add_enabled(running[selected], egui::Button::new("Calibrate"))
+            .on_hover_text("Drag the crosshair or nudge it with the arrow keys on screen, with a loupe")
+            .clicked()
+        {
+            action = PanelAction::Calibrate(selected);
+        }
+        let mut show_pattern = pattern;
+        if ui
+            .checkbox(&mut show_pattern, "Test Pattern")
+            .on_hover_text("Center lines, pixel rulers and the offset from the true monitor center, across the whole monitor")
+            .changed()
+        {
+            action = PanelAction::SetPattern(selected, show_pattern);
+        }
+        if selected > 0 && ui.button("Remove").clicked() {
+            action = PanelAction::RemoveOverlay(selected);
+        }
+    });
+    if let Some((x, y)) = calibrating {
+        ui.label(format!("Calibrating: offset {x}, {y} px. Press Enter on the overlay to keep it, Esc to cancel."));
+    }
+
+    ui.separator();
+    ui.label("Profile");
+    egui::ComboBox::from_id_salt("profile")
+        .selected_text(active_profile.unwrap_or("(none)"))
+        .show_ui(ui, |ui| {
+            for name in profiles {
+                if ui
+                    .selectable_label(active_profile == Some(name.as_str()), name)
+                    .clicked()
+                {
+                    action = PanelAction::LoadProfile(name.clone());
+                }
+            }
+        });
+    ui.horizontal(|ui| {
+        ui.add(
+            egui::TextEdit::singleline(new_profile_name)
+                .hint_text("profile name")
+                .desired_width(140.0),
+        );
+        let name = new_profile_name.trim();
+        if ui
+            .add_enabled(crate::config::valid_profile_name(name), egui::Button::new("Save Profile"))
+            .clicked()
+        {
+            action = PanelAction::SaveProfile(name.to_string());
+        }
+    });
+
+    ui.separator();
+    ui.label("Anchor");
+    ui.horizontal(|ui| {
+        let kinds = ["Monitor center", "Process", "Window title"];
+        let current = match &config.anchor {
+            None => 0,
+            Some(WindowMatch::Process(_)) => 1,
+            Some(WindowMatch::Title(_)) => 2,
+        };
+        let mut kind = current;
+        egui::ComboBox::from_id_salt("anchor")
+            .selected_text(kinds[kind])
+            .show_ui(ui, |ui| {
+                for (i, label) in kinds.iter().enumerate() {
+                    ui.selectable_value(&mut kind, i, *label);
+                }
+            });
+        if kind != current {
+            // Keep the typed name when switching between process and title
+            let text = match config.anchor.take() {
+                Some(WindowMatch::Process(t) | WindowMatch::Title(t)) => t,
+                None => String::new(),
+            };
+            config.anchor = match kind {
+                1 => Some(WindowMatch::Process(text)),
+                2 => Some(WindowMatch::Title(text)),
+                _ => None,
+            };
+            changed = true;
+        }
+        if let Some(WindowMatch::Process(text) | WindowMatch::Title(text)) = &mut config.anchor {
+            let hint = if kind == 1 { "game.exe" } else { "part of the title" };
+            changed |= ui
+                .add(egui::TextEdit::singleline(text).hint_text(hint).desired_width(140.0))
+                .changed();
+        }
+    });
+    if hidden {
+        ui.weak("Hidden: the window is minimized or not running");
+    }
+
+    ui.label("Show");
+    ui.horizontal(|ui| {
+        let kinds = ["Always", "While focused", "Fullscreen only"];
+        let current = match &config.show_when {
+            ShowWhen::Always => 0,
+            ShowWhen::Focused(_) => 1,
+            ShowWhen::Fullscreen => 2,
+        };
+        let mut kind = current;
+        egui::ComboBox::from_id_salt("show_when")
+            .selected_text(kinds[kind])
+            .show_ui(ui, |ui| {
+                for (i, label) in kinds.iter().enumerate() {
+                    ui.selectable_value(&mut kind, i, *label);
+                }
+            });
+        if kind != current {
+            config.show_when = match kind {
+                1 => ShowWhen::Focused(Vec::new()),
+                2 => ShowWhen::Fullscreen,
+                _ => ShowWhen::Always,
+            };
+            changed = true;
+        }
+        if let ShowWhen::Focused(names) = &mut config.show_when {
+            // Split without trimming so typing a comma or space survives the round trip
+            let mut text = names.join(",");
+            let edit = egui::TextEdit::singleline(&mut text)
+                .hint_text("game.exe, other.exe")
+                .desired_width(140.0);
+            if ui.add(edit).changed() {
+                *names = text.split(',').map(str::to_string).collect();
+                changed = true;
+            }
+        }
+    });
+
+    if selected == 0 {
+        ui.label("Activation");
+        ui.horizontal(|ui| {
+            let kinds = ["Always", "Hold to show", "Hold to hide", "Hold to swap"];
+            let current = match &config.activation {
+                Activation::Always => 0,
+                Activation::HoldToShow(_) => 1,
+                Activation::HoldToHide(_) => 2,
+                Activation::HoldToSwap(..) => 3,
+            };
+            let mut kind = current;
+            egui::ComboBox::from_id_salt("activation")
+                .selected_text(kinds[kind])
+                .show_ui(ui, |ui| {
+                    for (i, label) in kinds.iter().enumerate() {
+                        ui.selectable_value(&mut kind, i, *label);
+                    }
+                });
+            if kind != current {
+                // Keep the trigger when switching modes; aiming is usually the right button
+                let trigger = config.activation.trigger().unwrap_or(Trigger::MouseRight);
+                config.activation = match kind {
+                    1 => Activation::HoldToShow(trigger),
+                    2 => Activation::HoldToHide(trigger),
+                    3 => Activation::HoldToSwap(trigger, config.style()),
+                    _ => Activation::Always,
+                };
+                changed = true;
+            }
+            if let Activation::HoldToShow(trigger) | Activation::HoldToHide(trigger) | Activation::HoldToSwap(trigger, _) =
+                &mut config.activation
+            {
+                egui::ComboBox::from_id_salt("trigger")
+                    .selected_text(trigger.to_string())
+                    .show_ui(ui, |ui| {
+                        for option in Trigger::COMMON {
+                            changed |= ui.selectable_value(trigger, option, option.to_string()).changed();
+                        }
+                    });
+            }
+        });
+        if let Activation::HoldToSwap(_, alternate) = &mut config.activation {
+            egui::CollapsingHeader::new("Alternate crosshair").show(ui, |ui| {
+                changed |= draw_style(ui, alternate);
+            });
+        }
+    } else {
+        ui.weak("Activation follows overlay 1; hold to swap only swaps overlay 1.");
+    }
+
+    ui.label("Monitor");
+    ui.add_enabled_ui(config.anchor.is_none(), |ui| ui.horizontal(|ui| {
+        let selected_text = match &config.monitor {
+            None => "Primary".to_string(),
+            Some(name) if monitors.iter().any(|m| &m.name == name) => name.clone(),
+            Some(name) => format!("{name} (disconnected)"),
+        };
+        egui::ComboBox::from_id_salt("monitor")
+            .selected_text(selected_text)
+            .show_ui(ui, |ui| {
+                changed |= ui.selectable_value(&mut config.monitor, None, "Primary").changed();
+                for m in monitors {
+                    let label = format!("{} ({}x{})", m.name, m.width, m.height);
+                    changed |= ui
+                        .selectable_value(&mut config.monitor, Some(m.name.clone()), label)
+                        .changed();
+                }
+            });
+        if ui.button("⟳").on_hover_text("Refresh monitor list").clicked() {
+            action = PanelAction::RefreshMonitors;
+        }
+    }));
+    if let Some(p) = placement {
+        ui.weak(format!(
+            "On {} at {}%: {} px window at ({}, {})",
+            p.monitor,
+            (p.scale * 100.0).round(),
+            p.rect.size,
+            p.rect.x,
+            p.rect.y
+        ));
+    }
+
+    ui.separator();
+    ui.label("Position Offset (px)");
+    changed |= ui.add(egui::Slider::new(&mut config.offset_x, OFFSET_RANGE).text("X")).changed();
+    changed |= ui.add(egui::Slider::new(&mut config.offset_y, OFFSET_RANGE).text("Y")).changed();
 
     ui.separator();
-    ui.label("Size");
-    ui.add(egui::Slider::new(&mut config.inner_radius, 0.5..=50.0).text("Inner Radius"));
-    ui.add(egui::Slider::new(&mut config.outer_radius, 0.5..=50.0).text("Outer Radius"));
-    ui.add(egui::Slider::new(&mut config.stroke_width, 0.1..=10.0).text("Stroke Width"));
+    ui.label("Size (px)");
+    changed |= ui.add(egui::Slider::new(&mut config.inner_radius, RADIUS_RANGE).text("Inner Radius")).changed();
+    changed |= ui.add(egui::Slider::new(&mut config.outer_radius, RADIUS_RANGE).text("Outer Radius")).changed();
+    changed |= ui.add(egui::Slider::new(&mut config.stroke_width, STROKE_RANGE).text("Stroke Width")).changed();
 
     ui.separator();
     ui.label("Colors");
@@ -63,7 +388,7 @@ pub fn draw_panel_ui(ui: &mut egui::Ui, config: &mut Config, overlay_alive: bool
     let mut fill = Color32::from_rgb(config.color[0], config.color[1], config.color[2]);
     ui.horizontal(|ui| {
         ui.label("Fill:");
-        ui.color_edit_button_srgba(&mut fill);
+        changed |= ui.color_edit_button_srgba(&mut fill).changed();
     });
     config.color = [fill.r(), fill.g(), fill.b()];
 
@@ -74,19 +399,131 @@ pub fn draw_panel_ui(ui: &mut egui::Ui, config: &mut Config, overlay_alive: bool
     );
     ui.horizontal(|ui| {
         ui.label("Stroke:");
-        ui.color_edit_button_srgba(&mut stroke);
+        changed |= ui.color_edit_button_srgba(&mut stroke).changed();
     });
     config.stroke_color = [stroke.r(), stroke.g(), stroke.b()];
 
     ui.separator();
-    ui.horizontal(|ui| {
+    egui::CollapsingHeader::new("Preview")
+        .default_open(true)
+        .show(ui, |ui| changed |= preview.show(ui, config));
+
+    ui.separator();
+    ui.horizontal_wrapped(|ui| {
         if ui.button("Save").clicked() {
             action = PanelAction::Save;
         }
         if ui.button("Reset").clicked() {
             action = PanelAction::Reset;
         }
+        if ui.button("Export SVG").on_hover_text("Save this crosshair as a vector image").clicked() {
+            action = PanelAction::ExportSvg;
+        }
+        if ui.button("Import SVG").on_hover_text("Load the exported file back, e.g. after editing it").clicked() {
+            action = PanelAction::ImportSvg;
+        }
+    });
+    if let Some(notice) = notice {
+        ui.weak(notice);
+    }
+
+    (action, changed)
+}
+
+/// Size and color controls for an alternate crosshair style.
+fn draw_style(ui: &mut egui::Ui, style: &mut Style) -> bool {
+    let mut changed = false;
+    changed |= ui.add(egui::Slider::new(&mut style.inner_radius, RADIUS_RANGE).text("Inner Radius")).changed();
+    changed |= ui.add(egui::Slider::new(&mut style.outer_radius, RADIUS_RANGE).text("Outer Radius")).changed();
+    changed |= ui.add(egui::Slider::new(&mut style.stroke_width, STROKE_RANGE).text("Stroke Width")).changed();
+    for (label, rgb) in [("Fill:", &mut style.color), ("Stroke:", &mut style.stroke_color)] {
+        let mut color = Color32::from_rgb(rgb[0], rgb[1], rgb[2]);
+        ui.horizontal(|ui| {
+            ui.label(label);
+            changed |= ui.color_edit_button_srgba(&mut color).changed();
+        });
+        *rgb = [color.r(), color.g(), color.b()];
+    }
+    changed
+}
+
+/// The "Hotkeys" section: one row per action, click a binding to record a new one.
+pub fn draw_hotkeys(
+    ui: &mut egui::Ui,
+    bindings: &Bindings,
+    capturing: Option<Action>,
+    errors: &[(Action, String)],
+) -> HotkeyEdit {
+    let mut edit = HotkeyEdit::None;
+
+    if let Some(action) = capturing {
+        // Swallow key presses so they don't also reach other widgets
+        let presses: Vec<_> = ui.input_mut(|input| {
+            let presses = input
+                .events
+                .iter()
+                .filter_map(|event| match event {
+                    egui::Event::Key {
+                        key, pressed: true, modifiers, ..
+                    } => Some((*key, *modifiers)),
+                    _ => None,
+                })
+                .collect();
+            input.events.retain(|event| !matches!(event, egui::Event::Key { .. }));
+            presses
+        });
+        for (key, modifiers) in presses {
+            if key == egui::Key::Escape {
+                edit = HotkeyEdit::CancelCapture;
+                break;
+            }
+            let modifiers = hotkey::Modifiers {
+                ctrl: modifiers.ctrl,
+                alt: modifiers.alt,
+                shift: modifiers.shift,
+                logo: modifiers.mac_cmd,
+            };
+            // Keys we can't bind, or bare letters, keep the capture going
+            if let Some(hotkey) = hotkey::Key::from_name(key.name()).and_then(|k| Hotkey::new(modifiers, k).ok()) {
+                edit = HotkeyEdit::Set(action, hotkey);
+                break;
+            }
+        }
+    }
+
+    egui::CollapsingHeader::new("Hotkeys").show(ui, |ui| {
+        egui::Grid::new("hotkeys").num_columns(3).show(ui, |ui| {
+            for action in Action::ALL {
+                ui.label(action.label());
+                let text = match bindings.get(&action) {
+                    _ if capturing == Some(action) => "Press keys…".to_string(),
+                    Some(hotkey) => hotkey.to_string(),
+                    None => "(none)".to_string(),
+                };
+                if ui.selectable_label(capturing == Some(action), text).clicked() {
+                    edit = if capturing == Some(action) {
+                        HotkeyEdit::CancelCapture
+                    } else {
+                        HotkeyEdit::Capture(action)
+                    };
+                }
+                if ui
+                    .add_enabled(bindings.contains_key(&action), egui::Button::new("×"))
+                    .on_hover_text("Unbind")
+                    .clicked()
+                {
+                    edit = HotkeyEdit::Clear(action);
+                }
+                ui.end_row();
+            }
+        });
+        if capturing.is_some() {
+            ui.weak("Press a combination with Ctrl, Alt, Shift or Super, or a function key. Esc cancels.");
+        }
+        for (action, error) in errors {
+            ui.colored_label(Color32::from_rgb(230, 90, 90), format!("{}: {error}", action.label()));
+        }
     });
 
-    action
+    edit
 }
@@ -5,6 +5,8 @@ use std::sync::Arc;
 use tray_icon::menu::{Menu, MenuEvent, MenuItem};
 use tray_icon::TrayIconBuilder;
 
+use crate::platform::Platform;
+
 pub const MENU_SHOW_ID: &str = "show";
 pub const MENU_QUIT_ID: &str = "quit";
 
@@ -33,6 +35,7 @@ pub fn spawn_tray_poller(
     restore_flag: Arc<AtomicBool>,
     quit_flag: Arc<AtomicBool>,
     ctx: egui::Context,
+    platform: Arc<dyn Platform>,
 ) {
     let menu_rx = MenuEvent::receiver().clone();
     let tray_rx = tray_icon::TrayIconEvent::receiver().clone();
@@ -49,12 +52,12 @@ pub fn spawn_tray_poller(
                             if id == MENU_SHOW_ID {
                                 info!("tray poller: setting restore flag");
                                 restore_flag.store(true, Ordering::SeqCst);
-                                crate::platform::show_panel_window();
+                                platform.show_panel_window();
                                 ctx.request_repaint();
                             } else if id == MENU_QUIT_ID {
                                 info!("tray poller: setting quit flag");
                                 quit_flag.store(true, Ordering::SeqCst);
-                                crate::platform::show_panel_window();
+                                platform.show_panel_window();
                                 ctx.request_repaint();
                             }
                         }
@@ -75,7 +78,7 @@ pub fn spawn_tray_poller(
                         }) => {
                             info!("tray poller: left click, setting restore flag");
                             restore_flag.store(true, Ordering::SeqCst);
-                            crate::platform::show_panel_window();
+                            platform.show_panel_window();
                             ctx.request_repaint();
                         }
                         Ok(_) => {} // ignore Move, Enter, Leave, right-clicks
@@ -0,0 +1,320 @@
+//! A [`Platform`] with no window system, for tests and systems without a backend.
+
+use std::io;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use super::hotkey::{HotkeyGrabber, OnPress};
+use super::input::InputPoller;
+use super::surface::Surface;
+use super::window::{Foreground, WindowFinder, WindowState};
+use super::{Monitor, Platform};
+use crate::config::WindowMatch;
+use crate::hotkey::{Action, Bindings, Hotkey, Trigger};
+use eframe::egui::{Color32, ColorImage};
+
+/// A top-level window other than our own: process name, title and state.
+type FakeWindow = (String, String, WindowState);
+
+/// The image on the software overlay window and its top-left corner.
+type Frame = (i32, i32, ColorImage);
+
+/// Hotkeys held by the panel and by other applications, and the panel's callback.
+#[derive(Default)]
+struct Hotkeys {
+    bound: Bindings,
+    taken: Vec<Hotkey>,
+    on_press: Option<OnPress>,
+}
+
+/// Monitors and windows are whatever the test sets; nothing is drawn.
+pub struct Headless {
+    monitors: Mutex<Vec<Monitor>>,
+    watchers: Mutex<Vec<Box<dyn Fn() + Send>>>,
+    windows: Arc<Mutex<Vec<FakeWindow>>>,
+    foreground: Arc<Mutex<Foreground>>,
+    hotkeys: Arc<Mutex<Hotkeys>>,
+    held: Arc<Mutex<HashSet<Trigger>>>,
+    presented: Arc<Mutex<Option<Frame>>>,
+    /// What screen capture sees, with its top-left corner at 0,0
+    screen: Mutex<Option<ColorImage>>,
+    interactive: AtomicBool,
+    shown: AtomicUsize,
+    /// Program and arguments run instead of supervised children
+    stand_in: Mutex<Option<Vec<String>>>,
+    /// Arguments of every supervised spawn
+    spawned: Mutex<Vec<Vec<String>>>,
+    /// Processes allowed to take the foreground
+    foreground_allowed: Mutex<Vec<u32>>,
+}
+
+impl Headless {
+    pub fn new(monitors: Vec<Monitor>) -> Self {
+        Self {
+            monitors: Mutex::new(monitors),
+            watchers: Mutex::new(Vec::new()),
+            windows: Arc::new(Mutex::new(Vec::new())),
+            foreground: Arc::new(Mutex::new(Foreground::Unknown)),
+            hotkeys: Arc::default(),
+            held: Arc::default(),
+            presented: Arc::default(),
+            screen: Mutex::new(None),
+            interactive: AtomicBool::new(false),
+            shown: AtomicUsize::new(0),
+            stand_in: Mutex::new(None),
+            spawned: Mutex::new(Vec::new()),
+            foreground_allowed: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Replace the monitor list and notify display watchers, as if a monitor
+    /// was plugged in or changed resolution. Watchers run on the calling thread.
+    pub fn set_monitors(&self, monitors: Vec<Monitor>) {
+        *self.monitors.lock().unwrap() = monitors;
+        for watcher in self.watchers.lock().unwrap().iter() {
+            watcher();
+        }
+    }
+
+    /// Open, move or minimize the window owned by `process`; `None` closes it.
+    pub fn set_window(&self, process: &str, title: &str, state: Option<WindowState>) {
+        let mut windows = self.windows.lock().unwrap();
+        windows.retain(|(p, _, _)| p != process);
+        if let Some(state) = state {
+            windows.push((process.into(), title.into(), state));
+        }
+    }
+
+    /// Give focus to another window. Starts out [`Foreground::Unknown`].
+    pub fn set_foreground(&self, foreground: Foreground) {
+        *self.foreground.lock().unwrap() = foreground;
+    }
+
+    /// Let another application own `hotkey`, so registering it fails.
+    pub fn take_hotkey(&self, hotkey: Hotkey) {
+        self.hotkeys.lock().unwrap().taken.push(hotkey);
+    }
+
+    /// Press `hotkey`. Returns the action it triggered, if it is registered.
+    pub fn press(&self, hotkey: Hotkey) -> Option<Action> {
+        let hotkeys = self.hotkeys.lock().unwrap();
+        let (action, _) = hotkeys.bound.iter().find(|(_, h)| **h == hotkey)?;
+        if let Some(on_press) = &hotkeys.on_press {
+            on_press(*action);
+        }
+        Some(*action)
+    }
+
+    /// Press or release a key or mouse button.
+    pub fn set_held(&self, trigger: Trigger, held: bool) {
+        let mut pressed = self.held.lock().unwrap();
+        if held {
+            pressed.insert(trigger);
+        } else {
+            pressed.remove(&trigger);
+        }
+    }
+
+    /// What the software overlay window shows, or `None` while it's hidden.
+    pub fn presented(&self) -> Option<Frame> {
+        self.presented.lock().unwrap().clone()
+    }
+
+    /// Set what screen capture returns. Without one, capture fails.
+    pub fn set_screen(&self, screen: ColorImage) {
+        *self.screen.lock().unwrap() = Some(screen);
+    }
+
+    /// Whether the overlay window currently takes input.
+    pub fn interactive(&self) -> bool {
+        self.interactive.load(Ordering::SeqCst)
+    }
+
+    /// How often the panel window was asked to show itself.
+    pub fn shown(&self) -> usize {
+        self.shown.load(Ordering::SeqCst)
+    }
+
+    /// Run `command` with piped stdin and stdout for supervised spawns, in
+    /// place of what was asked for, such as the overlay from a test binary.
+    pub fn set_stand_in(&self, command: &[&str]) {
+        *self.stand_in.lock().unwrap() = Some(command.iter().map(|s| s.to_string()).collect());
+    }
+
+    /// Arguments of every supervised spawn so far.
+    pub fn spawned(&self) -> Vec<Vec<String>> {
+        self.spawned.lock().unwrap().clone()
+    }
+
+    /// Pids allowed to take the foreground so far.
+    pub fn foreground_allowed(&self) -> Vec<u32> {
+        self.foreground_allowed.lock().unwrap().clone()
+    }
+}
+
+impl Default for Headless {
+    fn default() -> Self {
+        Self::new(vec![Monitor::fallback()])
+    }
+}
+
+struct HeadlessWindows {
+    windows: Arc<Mutex<Vec<FakeWindow>>>,
+    foreground: Arc<Mutex<Foreground>>,
+}
+
+impl WindowFinder for HeadlessWindows {
+    fn find(&mut self, target: &WindowMatch) -> Option<WindowState> {
+        let windows = self.windows.lock().unwrap();
+        windows
+            .iter()
+            .find(|(process, title, _)| target.matches(process, title))
+            .map(|(_, _, state)| *state)
+    }
+
+    fn foreground(&mut self) -> Foreground {
+        self.foreground.lock().unwrap().clone()
+    }
+}
+
+struct HeadlessHotkeys(Arc<Mutex<Hotkeys>>);
+
+impl HotkeyGrabber for HeadlessHotkeys {
+    fn register(&mut self, bindings: &Bindings) -> Vec<(Action, String)> {
+        let mut hotkeys = self.0.lock().unwrap();
+        let (free, taken): (Bindings, Bindings) = bindings.iter().partition(|(_, h)| !hotkeys.taken.contains(h));
+        hotkeys.bound = free;
+        taken
+            .into_iter()
+            .map(|(action, hotkey)| (action, format!("{hotkey} is already taken by another application")))
+            .collect()
+    }
+}
+
+struct HeadlessInput(Arc<Mutex<HashSet<Trigger>>>);
+
+impl InputPoller for HeadlessInput {
+    fn held(&mut self, trigger: Trigger) -> Option<bool> {
+        Some(self.0.lock().unwrap().contains(&trigger))
+    }
+}
+
+struct HeadlessSurface(Arc<Mutex<Option<Frame>>>);
+
+impl Surface for HeadlessSurface {
+    fn present(&mut self, x: i32, y: i32, image: &ColorImage) -> Result<(), String> {
+        *self.0.lock().unwrap() = Some((x, y, image.clone()));
+        Ok(())
+    }
+
+    fn hide(&mut self) {
+        *self.0.lock().unwrap() = None;
+    }
+}
+
+impl Platform for Headless {
+    fn monitors(&self) -> Vec<Monitor> {
+        super::non_empty(self.monitors.lock().unwrap().clone())
+    }
+
+    fn watch_displays(&self, on_change: Box<dyn Fn() + Send>) {
+        self.watchers.lock().unwrap().push(on_change);
+    }
+
+    fn window_finder(&self) -> Box<dyn WindowFinder> {
+        Box::new(HeadlessWindows {
+            windows: self.windows.clone(),
+            foreground: self.foreground.clone(),
+        })
+    }
+
+    fn hotkeys(&self, on_press: OnPress) -> Box<dyn HotkeyGrabber> {
+        self.hotkeys.lock().unwrap().on_press = Some(on_press);
+        Box::new(HeadlessHotkeys(self.hotkeys.clone()))
+    }
+
+    fn software_surface(&self) -> Result<Box<dyn Surface>, String> {
+        Ok(Box::new(HeadlessSurface(self.presented.clone())))
+    }
+
+    fn input_poller(&self) -> Box<dyn InputPoller> {
+        Box::new(HeadlessInput(self.held.clone()))
+    }
+
+    fn set_overlay_interactive(&self, interactive: bool) {
+        self.interactive.store(interactive, Ordering::SeqCst);
+    }
+
+    /// Crops the image given to [`Headless::set_screen`]; outside it is black.
+    fn capture_screen(&self, x: i32, y: i32, width: u32, height: u32) -> Result<ColorImage, String> {
+        let screen = self.screen.lock().unwrap();
+        let screen = screen.as_ref().ok_or("no screen to capture")?;
+        let mut image = ColorImage::filled([width as usize, height as usize], Color32::BLACK);
+        for row in 0..height as i32 {
+            for col in 0..width as i32 {
+                let (sx, sy) = (x + col, y + row);
+                if (0..screen.size[0] as i32).contains(&sx) && (0..screen.size[1] as i32).contains(&sy) {
+                    image[(col as usize, row as usize)] = screen[(sx as usize, sy as usize)];
+                }
+            }
+        }
+        Ok(image)
+    }
+
+    fn show_panel_window(&self) {
+        self.shown.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn allow_set_foreground_window(&self, pid: u32) {
+        self.foreground_allowed.lock().unwrap().push(pid);
+    }
+
+    /// Nothing to tie children to; they are spawned plainly, or replaced by
+    /// the stand-in.
+    fn spawn_supervised(&self, cmd: &mut Command) -> io::Result<Child> {
+        let args = cmd.get_args().map(|a| a.to_string_lossy().into_owned()).collect();
+        self.spawned.lock().unwrap().push(args);
+        match self.stand_in.lock().unwrap().as_deref() {
+            Some([program, args @ ..]) => Command::new(program)
+                .args(args)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn(),
+            _ => cmd.spawn(),
+        }
+    }
+
+    /// Only this process is known to exist.
+    fn process_alive(&self, pid: u32) -> bool {
+        pid == std::process::id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hotkey::default_bindings;
+
+    #[test]
+    fn hotkeys_fire_unless_taken() {
+        let platform = Headless::default();
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let mut grabber = platform.hotkeys(Box::new(move |action| tx.send(action).unwrap()));
+        let toggle: Hotkey = "Ctrl+Alt+H".parse().unwrap();
+        let reset: Hotkey = "Ctrl+Alt+Home".parse().unwrap();
+        platform.take_hotkey(reset);
+
+        let errors = grabber.register(&default_bindings());
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, Action::ResetOffset);
+        assert_eq!(platform.press(toggle), Some(Action::Toggle));
+        assert_eq!(rx.try_recv(), Ok(Action::Toggle));
+        assert_eq!(platform.press(reset), None);
+
+        grabber.register(&Bindings::new());
+        assert_eq!(platform.press(toggle), None);
+        assert!(rx.try_recv().is_err());
+    }
+}
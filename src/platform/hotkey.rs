@@ -0,0 +1,22 @@
+use crate::hotkey::{Action, Bindings};
+
+/// Called on the grabber's own thread whenever a registered hotkey is pressed.
+pub type OnPress = Box<dyn Fn(Action) + Send>;
+
+/// Claims global hotkeys from the window system.
+pub trait HotkeyGrabber: Send {
+    /// Replace all registered hotkeys with `bindings`. Returns an error for
+    /// each binding that could not be registered, typically because another
+    /// application already owns the combination.
+    fn register(&mut self, bindings: &Bindings) -> Vec<(Action, String)>;
+}
+
+/// For window systems without global hotkeys, like Wayland. Every binding
+/// fails with the same reason.
+pub struct NoHotkeys(pub &'static str);
+
+impl HotkeyGrabber for NoHotkeys {
+    fn register(&mut self, bindings: &Bindings) -> Vec<(Action, String)> {
+        bindings.keys().map(|action| (*action, self.0.to_string())).collect()
+    }
+}
@@ -0,0 +1,17 @@
+use crate::hotkey::Trigger;
+
+/// Reads whether keys and mouse buttons are held, whichever window has focus.
+pub trait InputPoller: Send {
+    /// Whether `trigger` is held down right now, or `None` if this platform
+    /// can't tell. Called every few milliseconds while an activation mode is set.
+    fn held(&mut self, trigger: Trigger) -> Option<bool>;
+}
+
+/// For window systems that don't expose global input state, like Wayland.
+pub struct NoInput;
+
+impl InputPoller for NoInput {
+    fn held(&mut self, _trigger: Trigger) -> Option<bool> {
+        None
+    }
+}
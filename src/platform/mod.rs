@@ -0,0 +1,250 @@
+//! Everything the panel and overlay need from the OS and window system sits
+//! behind [`Platform`], so the rest of the crate can run against
+//! [`headless::Headless`] in tests. The only `cfg(target_os)` code left
+//! outside is the overlay's Wayland layer-shell path, which drives the
+//! overlay's own state rather than a window eframe created.
+
+use eframe::egui::{Color32, ColorImage};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::sync::{Arc, OnceLock};
+
+use hotkey::{HotkeyGrabber, NoHotkeys, OnPress};
+use input::{InputPoller, NoInput};
+use surface::Surface;
+use window::{NoWindows, WindowFinder};
+
+pub mod headless;
+pub mod hotkey;
+pub mod input;
+pub mod supervise;
+pub mod surface;
+#[cfg(target_os = "linux")]
+pub mod wayland;
+#[cfg(target_os = "windows")]
+pub mod win32;
+pub mod window;
+#[cfg(target_os = "linux")]
+pub mod x11;
+
+/// OS services for the panel and overlay. Methods with a default do nothing
+/// where the platform has no equivalent.
+pub trait Platform: Send + Sync {
+    /// Make the overlay window transparent, topmost, click-through and hidden
+    /// from the taskbar. Fails if the window can't be made transparent.
+    fn apply_overlay_style(&self, _cc: &eframe::CreationContext) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// A window for the software renderer, for when GL is unavailable or not wanted.
+    fn software_surface(&self) -> Result<Box<dyn Surface>, String> {
+        Err("no software overlay window on this platform".into())
+    }
+
+    /// Color the overlay clears to; anything else shows up on screen.
+    fn overlay_background(&self) -> Color32 {
+        Color32::TRANSPARENT
+    }
+
+    /// Prepare the overlay window styled by [`Platform::apply_overlay_style`]
+    /// for calibration, or undo that. Mouse passthrough itself is switched with
+    /// `ViewportCommand::MousePassthrough`; this covers what the window system
+    /// needs besides, like focus for the arrow keys.
+    fn set_overlay_interactive(&self, _interactive: bool) {}
+
+    /// Copy a rectangle of the screen, in physical pixels of the virtual desktop.
+    fn capture_screen(&self, _x: i32, _y: i32, _width: u32, _height: u32) -> Result<ColorImage, String> {
+        Err("screen capture is not supported on this platform".into())
+    }
+
+    /// Every connected monitor. Never empty: falls back to a single 1920x1080 display.
+    fn monitors(&self) -> Vec<Monitor>;
+
+    /// Call `on_change` when monitors are added or removed, or change
+    /// resolution or scale. Spurious calls are possible, so compare
+    /// [`Platform::monitors`] against the previous list before acting.
+    fn watch_displays(&self, _on_change: Box<dyn Fn() + Send>) {}
+
+    /// Looks up other applications' windows for anchoring a crosshair.
+    fn window_finder(&self) -> Box<dyn WindowFinder> {
+        Box::new(NoWindows)
+    }
+
+    /// Grabs system-wide key combinations for the panel; `on_press` runs on a
+    /// background thread.
+    fn hotkeys(&self, _on_press: OnPress) -> Box<dyn HotkeyGrabber> {
+        Box::new(NoHotkeys("global hotkeys are not supported on this platform"))
+    }
+
+    /// Reads held keys and mouse buttons for activation modes.
+    fn input_poller(&self) -> Box<dyn InputPoller> {
+        Box::new(NoInput)
+    }
+
+    /// Remember the panel window so [`Platform::show_panel_window`] works from any thread.
+    fn register_panel_window(&self, _cc: &eframe::CreationContext) {}
+
+    /// Force-show and focus the panel window, even when eframe considers it hidden.
+    fn show_panel_window(&self) {}
+
+    /// Let another process take the foreground. Windows only lets the
+    /// foreground process do this, so a second launch calls it before handing off.
+    fn allow_set_foreground_window(&self, _pid: u32) {}
+
+    /// Spawn `cmd` so the OS terminates it when this process dies.
+    fn spawn_supervised(&self, cmd: &mut Command) -> io::Result<Child>;
+
+    /// Group panel and overlay under one app identity in the taskbar and task manager.
+    fn set_app_identity(&self) {}
+
+    /// Let command-line subcommands print from a GUI-subsystem build.
+    fn attach_parent_console(&self) {}
+
+    /// Whether a process with this pid is still running.
+    fn process_alive(&self, pid: u32) -> bool;
+
+    /// Fill `buf` from the OS's cryptographically secure random source.
+    fn random_bytes(&self, buf: &mut [u8]) -> io::Result<()> {
+        use std::io::Read;
+        std::fs::File::open("/dev/urandom")?.read_exact(buf)
+    }
+
+    /// Per-user directory for the control endpoint and instance lock.
+    fn runtime_dir(&self) -> PathBuf {
+        std::env::var_os("XDG_RUNTIME_DIR")
+            .map(|d| PathBuf::from(d).join("aimx"))
+            .unwrap_or_else(temp_runtime_dir)
+    }
+
+    /// Create `path` and its parents, readable only by the current user where
+    /// the file system has permissions.
+    fn create_private_dir(&self, path: &Path) -> io::Result<()> {
+        let mut builder = std::fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::DirBuilderExt;
+            builder.mode(0o700);
+        }
+        builder.create(path)
+    }
+}
+
+/// The platform this process runs on.
+pub fn current() -> Arc<dyn Platform> {
+    static CURRENT: OnceLock<Arc<dyn Platform>> = OnceLock::new();
+    CURRENT
+        .get_or_init(|| {
+            #[cfg(target_os = "windows")]
+            let platform: Arc<dyn Platform> = Arc::new(win32::Win32Platform::new());
+            #[cfg(target_os = "linux")]
+            let platform: Arc<dyn Platform> = Arc::new(x11::X11Platform::new());
+            #[cfg(not(any(target_os = "windows", target_os = "linux")))]
+            let platform: Arc<dyn Platform> = Arc::new(headless::Headless::default());
+            platform
+        })
+        .clone()
+}
+
+/// One display, placed in virtual-desktop coordinates. Position and size are
+/// physical pixels.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Monitor {
+    /// Stable name for choosing this monitor, e.g. `\\.\DISPLAY2` or `DP-1`
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    /// Desktop scale factor, 1.0 at 96 DPI
+    pub scale: f32,
+    pub primary: bool,
+}
+
+impl Monitor {
+    /// Stand-in when no display can be queried.
+    fn fallback() -> Self {
+        Self {
+            name: "default".into(),
+            x: 0,
+            y: 0,
+            width: 1920,
+            height: 1080,
+            scale: 1.0,
+            primary: true,
+        }
+    }
+
+    pub fn center(&self) -> (f32, f32) {
+        (
+            self.x as f32 + self.width as f32 / 2.0,
+            self.y as f32 + self.height as f32 / 2.0,
+        )
+    }
+}
+
+/// The monitor called `name`, or the primary one when `name` is `None` or
+/// no longer connected.
+pub fn select_monitor<'a>(monitors: &'a [Monitor], name: Option<&str>) -> Option<&'a Monitor> {
+    name.and_then(|name| monitors.iter().find(|m| m.name == name))
+        .or_else(|| monitors.iter().find(|m| m.primary))
+        .or_else(|| monitors.first())
+}
+
+/// Runtime directory in the temp directory, for when the OS names none.
+fn temp_runtime_dir() -> PathBuf {
+    let user = std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_default();
+    std::env::temp_dir().join(format!("aimx-{user}"))
+}
+
+/// `found`, or the fallback display when nothing could be queried.
+fn non_empty(found: Vec<Monitor>) -> Vec<Monitor> {
+    if found.is_empty() {
+        log::debug!("no monitors detected, using fallback 1920x1080");
+        return vec![Monitor::fallback()];
+    }
+    log::debug!("detected monitors: {found:?}");
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor(name: &str, x: i32, primary: bool) -> Monitor {
+        Monitor {
+            name: name.into(),
+            x,
+            y: 0,
+            width: 1920,
+            height: 1080,
+            scale: 1.0,
+            primary,
+        }
+    }
+
+    #[test]
+    fn selects_monitor_by_name() {
+        let list = [monitor("DP-1", 0, true), monitor("HDMI-1", 1920, false)];
+        assert_eq!(select_monitor(&list, Some("HDMI-1")).unwrap().name, "HDMI-1");
+        assert_eq!(select_monitor(&list, Some("HDMI-1")).unwrap().center(), (2880.0, 540.0));
+    }
+
+    #[test]
+    fn missing_or_unset_name_falls_back_to_primary() {
+        let list = [monitor("HDMI-1", -1920, false), monitor("DP-1", 0, true)];
+        assert_eq!(select_monitor(&list, None).unwrap().name, "DP-1");
+        assert_eq!(select_monitor(&list, Some("DP-9")).unwrap().name, "DP-1");
+    }
+
+    #[test]
+    fn without_primary_takes_first() {
+        let list = [monitor("a", 0, false), monitor("b", 1920, false)];
+        assert_eq!(select_monitor(&list, None).unwrap().name, "a");
+        assert!(select_monitor(&[], None).is_none());
+    }
+}
+
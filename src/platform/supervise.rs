@@ -0,0 +1,169 @@
+//! Keeping child processes from outliving the panel.
+//!
+//! A panel that crashes or is killed can't clean up after itself, so the
+//! overlays it spawned must be tied to it by the OS: a Job Object on Windows
+//! and a parent-death signal on Linux.
+
+use std::io;
+use std::process::{Child, Command};
+
+/// Spawns children that the OS terminates when this process dies, however it dies.
+pub struct Supervisor {
+    #[cfg(target_os = "windows")]
+    job: Option<JobObject>,
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(target_os = "windows")]
+            job: JobObject::new(),
+        }
+    }
+
+    /// Spawn `cmd` under supervision. On Linux the child gets SIGTERM when the
+    /// spawning *thread* exits, so spawn from a thread that lives as long as
+    /// the process, like the UI thread.
+    pub fn spawn(&self, cmd: &mut Command) -> io::Result<Child> {
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::unix::process::CommandExt;
+
+            let parent = std::process::id() as libc::pid_t;
+            // Only async-signal-safe calls between fork and exec
+            unsafe {
+                cmd.pre_exec(move || {
+                    if libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGTERM) != 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    // The parent may have died before the signal was armed
+                    if libc::getppid() != parent {
+                        libc::_exit(0);
+                    }
+                    Ok(())
+                });
+            }
+        }
+
+        let child = cmd.spawn()?;
+        #[cfg(target_os = "windows")]
+        if let Some(job) = &self.job {
+            job.assign(&child);
+        }
+        Ok(child)
+    }
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A Win32 Job Object that kills all assigned processes when dropped.
+/// Ensures child processes (e.g. overlay) are terminated when the panel exits.
+#[cfg(target_os = "windows")]
+struct JobObject {
+    handle: *mut core::ffi::c_void,
+}
+
+// Job handles may be used from any thread
+#[cfg(target_os = "windows")]
+unsafe impl Send for JobObject {}
+#[cfg(target_os = "windows")]
+unsafe impl Sync for JobObject {}
+
+#[cfg(target_os = "windows")]
+impl JobObject {
+    fn new() -> Option<Self> {
+        use core::ffi::c_void;
+
+        #[repr(C)]
+        struct JobObjectExtendedLimitInformation {
+            basic: [u8; 48], // JOBOBJECT_BASIC_LIMIT_INFORMATION
+            io_info: [u8; 48],
+            process_memory_limit: usize,
+            job_memory_limit: usize,
+            peak_process_memory_used: usize,
+            peak_job_memory_used: usize,
+        }
+
+        const JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE: u32 = 0x2000;
+
+        unsafe extern "system" {
+            fn CreateJobObjectW(attrs: *mut c_void, name: *const u16) -> *mut c_void;
+            fn SetInformationJobObject(
+                job: *mut c_void,
+                class: u32,
+                info: *const c_void,
+                len: u32,
+            ) -> i32;
+        }
+
+        unsafe {
+            let handle = CreateJobObjectW(core::ptr::null_mut(), core::ptr::null());
+            if handle.is_null() {
+                log::warn!("failed to create job object");
+                return None;
+            }
+
+            let mut info = core::mem::zeroed::<JobObjectExtendedLimitInformation>();
+            // LimitFlags is at offset 20 in the basic struct (5th u32)
+            let limit_flags_ptr = info.basic.as_mut_ptr().add(20) as *mut u32;
+            *limit_flags_ptr = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+
+            const JOB_OBJECT_EXTENDED_LIMIT_INFORMATION: u32 = 9;
+            let ret = SetInformationJobObject(
+                handle,
+                JOB_OBJECT_EXTENDED_LIMIT_INFORMATION,
+                &info as *const _ as *const c_void,
+                core::mem::size_of::<JobObjectExtendedLimitInformation>() as u32,
+            );
+            if ret == 0 {
+                log::warn!("failed to set job object limits");
+            }
+
+            log::info!("created job object: {:?}", handle);
+            Some(Self { handle })
+        }
+    }
+
+    fn assign(&self, child: &std::process::Child) {
+        use core::ffi::c_void;
+
+        unsafe extern "system" {
+            fn OpenProcess(access: u32, inherit: i32, pid: u32) -> *mut c_void;
+            fn AssignProcessToJobObject(job: *mut c_void, process: *mut c_void) -> i32;
+            fn CloseHandle(handle: *mut c_void) -> i32;
+        }
+
+        const PROCESS_ALL_ACCESS: u32 = 0x1FFFFF;
+
+        unsafe {
+            let proc_handle = OpenProcess(PROCESS_ALL_ACCESS, 0, child.id());
+            if proc_handle.is_null() {
+                log::warn!("failed to open child process for job assignment");
+                return;
+            }
+            let ret = AssignProcessToJobObject(self.handle, proc_handle);
+            if ret == 0 {
+                log::warn!("failed to assign child to job object");
+            } else {
+                log::info!("assigned child pid {} to job object", child.id());
+            }
+            CloseHandle(proc_handle);
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+impl Drop for JobObject {
+    fn drop(&mut self) {
+        unsafe extern "system" {
+            fn CloseHandle(handle: *mut core::ffi::c_void) -> i32;
+        }
+        unsafe {
+            CloseHandle(self.handle);
+        }
+    }
+}
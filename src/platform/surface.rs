@@ -0,0 +1,16 @@
+use eframe::egui::ColorImage;
+
+/// A top-level window for CPU-rendered overlays: transparent, topmost,
+/// click-through and hidden from the taskbar, showing whatever image it was
+/// last given. Used where no GL context can be created.
+pub trait Surface {
+    /// Show `image` with its top-left corner at `(x, y)`, physical pixels of
+    /// the virtual desktop. Colors are premultiplied, as egui stores them.
+    fn present(&mut self, x: i32, y: i32, image: &ColorImage) -> Result<(), String>;
+
+    /// Take the window off the screen until the next [`Surface::present`].
+    fn hide(&mut self);
+
+    /// Handle pending window-system messages; called between frames.
+    fn dispatch(&mut self) {}
+}
@@ -0,0 +1,84 @@
+use smithay_client_toolkit::output::{OutputHandler, OutputInfo, OutputState};
+use smithay_client_toolkit::reexports::client::globals::registry_queue_init;
+use smithay_client_toolkit::reexports::client::protocol::wl_output;
+use smithay_client_toolkit::reexports::client::{Connection, QueueHandle};
+use smithay_client_toolkit::registry::{ProvidesRegistryState, RegistryState};
+use smithay_client_toolkit::{delegate_output, delegate_registry, registry_handlers};
+
+use super::Monitor;
+
+struct Outputs {
+    registry: RegistryState,
+    outputs: OutputState,
+}
+
+/// List Wayland outputs. Wayland has no primary output, so the first one
+/// stands in for it.
+pub fn monitors() -> Result<Vec<Monitor>, String> {
+    let conn = Connection::connect_to_env().map_err(|e| e.to_string())?;
+    let (globals, mut event_queue) = registry_queue_init::<Outputs>(&conn).map_err(|e| e.to_string())?;
+    let qh = event_queue.handle();
+    let mut state = Outputs {
+        registry: RegistryState::new(&globals),
+        outputs: OutputState::new(&globals, &qh),
+    };
+    // The first roundtrip binds the outputs, the second receives their details
+    for _ in 0..2 {
+        event_queue.roundtrip(&mut state).map_err(|e| e.to_string())?;
+    }
+
+    Ok(list(&state.outputs))
+}
+
+/// Every output `outputs` knows the details of.
+pub fn list(outputs: &OutputState) -> Vec<Monitor> {
+    outputs
+        .outputs()
+        .filter_map(|o| outputs.info(&o))
+        .enumerate()
+        .map(|(i, info)| to_monitor(info, i == 0))
+        .collect()
+}
+
+fn to_monitor(info: OutputInfo, primary: bool) -> Monitor {
+    let scale = info.scale_factor.max(1);
+    let (x, y) = info.logical_position.unwrap_or(info.location);
+    let (width, height) = info
+        .modes
+        .iter()
+        .find(|m| m.current)
+        .map(|m| m.dimensions)
+        .or(info.logical_size.map(|(w, h)| (w * scale, h * scale)))
+        .unwrap_or((0, 0));
+    Monitor {
+        name: info.name.unwrap_or_else(|| format!("output-{}", info.id)),
+        x: x * scale,
+        y: y * scale,
+        width: width.max(0) as u32,
+        height: height.max(0) as u32,
+        scale: scale as f32,
+        primary,
+    }
+}
+
+impl OutputHandler for Outputs {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.outputs
+    }
+
+    fn new_output(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _output: wl_output::WlOutput) {}
+
+    fn update_output(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _output: wl_output::WlOutput) {}
+
+    fn output_destroyed(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _output: wl_output::WlOutput) {}
+}
+
+impl ProvidesRegistryState for Outputs {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry
+    }
+    registry_handlers![OutputState];
+}
+
+delegate_output!(Outputs);
+delegate_registry!(Outputs);
@@ -0,0 +1,1100 @@
+//! Win32 implementation of [`Platform`].
+
+use core::ffi::c_void;
+use eframe::egui::{Color32, ColorImage};
+use std::io;
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::sync::atomic::{AtomicIsize, Ordering};
+
+use super::hotkey::{HotkeyGrabber, NoHotkeys, OnPress};
+use super::input::InputPoller;
+use super::supervise::Supervisor;
+use super::surface::Surface;
+use super::window::WindowFinder;
+use super::{Monitor, Platform};
+use crate::hotkey::Trigger;
+
+pub type HWND = *mut c_void;
+const GWL_EXSTYLE: i32 = -20;
+const WS_EX_LAYERED: u32 = 0x00080000;
+const WS_EX_TRANSPARENT: u32 = 0x00000020;
+const WS_EX_TOOLWINDOW: u32 = 0x00000080;
+const LWA_COLORKEY: u32 = 0x00000001;
+
+unsafe extern "system" {
+    fn GetWindowLongW(hwnd: HWND, index: i32) -> i32;
+    fn SetWindowLongW(hwnd: HWND, index: i32, new_long: i32) -> i32;
+    fn SetLayeredWindowAttributes(hwnd: HWND, cr_key: u32, alpha: u8, flags: u32) -> i32;
+}
+
+/// Window class and message loop declarations shared by the threads that
+/// run their own hidden windows or message queues.
+mod ffi {
+    use core::ffi::c_void;
+
+    use super::HWND;
+
+    pub type WndProc = unsafe extern "system" fn(HWND, u32, usize, isize) -> isize;
+
+    #[repr(C)]
+    pub struct WndClassW {
+        pub style: u32,
+        pub wnd_proc: WndProc,
+        pub cls_extra: i32,
+        pub wnd_extra: i32,
+        pub instance: *mut c_void,
+        pub icon: *mut c_void,
+        pub cursor: *mut c_void,
+        pub background: *mut c_void,
+        pub menu_name: *const u16,
+        pub class_name: *const u16,
+    }
+
+    #[repr(C)]
+    pub struct Msg {
+        pub hwnd: HWND,
+        pub message: u32,
+        pub wparam: usize,
+        pub lparam: isize,
+        pub time: u32,
+        pub pt: [i32; 2],
+        pub private: u32,
+    }
+
+    unsafe extern "system" {
+        pub fn GetModuleHandleW(name: *const u16) -> *mut c_void;
+        pub fn RegisterClassW(class: *const WndClassW) -> u16;
+        pub fn CreateWindowExW(
+            ex_style: u32,
+            class_name: *const u16,
+            window_name: *const u16,
+            style: u32,
+            x: i32,
+            y: i32,
+            width: i32,
+            height: i32,
+            parent: HWND,
+            menu: *mut c_void,
+            instance: *mut c_void,
+            param: *mut c_void,
+        ) -> HWND;
+        pub fn DefWindowProcW(hwnd: HWND, msg: u32, wparam: usize, lparam: isize) -> isize;
+        pub fn GetMessageW(msg: *mut Msg, hwnd: HWND, min: u32, max: u32) -> i32;
+        pub fn PeekMessageW(msg: *mut Msg, hwnd: HWND, min: u32, max: u32, remove: u32) -> i32;
+        pub fn TranslateMessage(msg: *const Msg) -> i32;
+        pub fn DispatchMessageW(msg: *const Msg) -> isize;
+    }
+}
+
+/// The `HWND` behind an eframe window.
+fn hwnd(cc: &eframe::CreationContext) -> Option<HWND> {
+    use raw_window_handle::HasWindowHandle;
+
+    match cc.window_handle().ok()?.as_raw() {
+        raw_window_handle::RawWindowHandle::Win32(w) => Some(w.hwnd.get() as HWND),
+        _ => None,
+    }
+}
+
+pub struct Win32Platform {
+    supervisor: Supervisor,
+    /// Panel window, so the tray poller thread can show it directly via Win32
+    panel_hwnd: AtomicIsize,
+    /// The styled overlay window, or 0 before there is one
+    overlay_hwnd: AtomicIsize,
+}
+
+impl Win32Platform {
+    pub fn new() -> Self {
+        Self {
+            supervisor: Supervisor::new(),
+            panel_hwnd: AtomicIsize::new(0),
+            overlay_hwnd: AtomicIsize::new(0),
+        }
+    }
+}
+
+impl Default for Win32Platform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Platform for Win32Platform {
+    /// Color-key transparency and click-through.
+    fn apply_overlay_style(&self, cc: &eframe::CreationContext) -> Result<(), String> {
+        let hwnd = hwnd(cc).ok_or("cannot get overlay window handle")?;
+        log::info!("applying overlay style to HWND {:?}", hwnd);
+        self.overlay_hwnd.store(hwnd as isize, Ordering::SeqCst);
+        unsafe {
+            let style = GetWindowLongW(hwnd, GWL_EXSTYLE);
+            SetWindowLongW(
+                hwnd,
+                GWL_EXSTYLE,
+                style | WS_EX_LAYERED as i32 | WS_EX_TRANSPARENT as i32 | WS_EX_TOOLWINDOW as i32,
+            );
+            SetLayeredWindowAttributes(hwnd, 0x00000000, 0, LWA_COLORKEY);
+        }
+        Ok(())
+    }
+
+    /// Pure black, which `LWA_COLORKEY` keys out.
+    fn overlay_background(&self) -> Color32 {
+        Color32::BLACK
+    }
+
+    /// Brings the window to the foreground for the arrow keys, and keeps it
+    /// out of screen captures meanwhile so the loupe sees only what is behind it.
+    /// eframe drops `WS_EX_TRANSPARENT` itself via `ViewportCommand::MousePassthrough`.
+    fn set_overlay_interactive(&self, interactive: bool) {
+        const WDA_NONE: u32 = 0;
+        const WDA_EXCLUDEFROMCAPTURE: u32 = 0x11;
+        unsafe extern "system" {
+            fn SetForegroundWindow(hwnd: HWND) -> i32;
+            fn SetWindowDisplayAffinity(hwnd: HWND, affinity: u32) -> i32;
+        }
+        let hwnd = self.overlay_hwnd.load(Ordering::SeqCst) as HWND;
+        if hwnd.is_null() {
+            return;
+        }
+        unsafe {
+            // Needs Windows 10 2004; older versions capture the overlay too
+            SetWindowDisplayAffinity(hwnd, if interactive { WDA_EXCLUDEFROMCAPTURE } else { WDA_NONE });
+            if interactive {
+                SetForegroundWindow(hwnd);
+            }
+        }
+    }
+
+    fn capture_screen(&self, x: i32, y: i32, width: u32, height: u32) -> Result<ColorImage, String> {
+        layered::capture(x, y, width, height)
+    }
+
+    fn monitors(&self) -> Vec<Monitor> {
+        super::non_empty(monitors())
+    }
+
+    fn watch_displays(&self, on_change: Box<dyn Fn() + Send>) {
+        watch_displays(on_change);
+    }
+
+    fn window_finder(&self) -> Box<dyn WindowFinder> {
+        Box::new(windows::Win32Windows::default())
+    }
+
+    fn hotkeys(&self, on_press: OnPress) -> Box<dyn HotkeyGrabber> {
+        match hotkeys::Win32Hotkeys::start(on_press) {
+            Some(grabber) => Box::new(grabber),
+            None => Box::new(NoHotkeys("cannot start the hotkey thread")),
+        }
+    }
+
+    fn input_poller(&self) -> Box<dyn InputPoller> {
+        Box::new(Win32Input)
+    }
+
+    /// A layered window updated with `UpdateLayeredWindow`, so per-pixel alpha
+    /// works without a color key.
+    fn software_surface(&self) -> Result<Box<dyn Surface>, String> {
+        Ok(Box::new(layered::LayeredSurface::create()?))
+    }
+
+    fn register_panel_window(&self, cc: &eframe::CreationContext) {
+        if let Some(hwnd) = hwnd(cc) {
+            self.panel_hwnd.store(hwnd as isize, Ordering::SeqCst);
+            log::info!("stored panel HWND: {:#x}", hwnd as isize);
+        }
+    }
+
+    /// ShowWindow/SetForegroundWindow, which work even when eframe
+    /// considers the window hidden.
+    fn show_panel_window(&self) {
+        let hwnd = self.panel_hwnd.load(Ordering::SeqCst);
+        if hwnd == 0 {
+            log::warn!("show_panel_window: no HWND stored");
+            return;
+        }
+        const SW_SHOW: i32 = 5;
+        unsafe extern "system" {
+            fn ShowWindow(hwnd: HWND, cmd: i32) -> i32;
+            fn SetForegroundWindow(hwnd: HWND) -> i32;
+        }
+        unsafe {
+            ShowWindow(hwnd as HWND, SW_SHOW);
+            SetForegroundWindow(hwnd as HWND);
+        }
+        log::info!("show_panel_window: showed HWND {:#x}", hwnd);
+    }
+
+    fn allow_set_foreground_window(&self, pid: u32) {
+        unsafe extern "system" {
+            fn AllowSetForegroundWindow(pid: u32) -> i32;
+        }
+        unsafe {
+            AllowSetForegroundWindow(pid);
+        }
+    }
+
+    /// Also keeps console children from flashing a console window.
+    fn spawn_supervised(&self, cmd: &mut Command) -> io::Result<Child> {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        cmd.creation_flags(CREATE_NO_WINDOW);
+        self.supervisor.spawn(cmd)
+    }
+
+    /// Sets the Application User Model ID.
+    fn set_app_identity(&self) {
+        unsafe extern "system" {
+            fn SetCurrentProcessExplicitAppUserModelID(app_id: *const u16) -> i32;
+        }
+        // "AIMX" encoded as null-terminated UTF-16
+        let id: Vec<u16> = "AIMX".encode_utf16().chain(std::iter::once(0)).collect();
+        unsafe {
+            SetCurrentProcessExplicitAppUserModelID(id.as_ptr());
+        }
+        log::info!("set AppUserModelID to AIMX");
+    }
+
+    /// Release builds use the GUI subsystem and start without a console.
+    fn attach_parent_console(&self) {
+        const ATTACH_PARENT_PROCESS: u32 = 0xFFFFFFFF;
+        unsafe extern "system" {
+            fn AttachConsole(pid: u32) -> i32;
+        }
+        unsafe {
+            AttachConsole(ATTACH_PARENT_PROCESS);
+        }
+    }
+
+    fn process_alive(&self, pid: u32) -> bool {
+        const PROCESS_QUERY_LIMITED_INFORMATION: u32 = 0x1000;
+        const STILL_ACTIVE: u32 = 259;
+        unsafe extern "system" {
+            fn OpenProcess(access: u32, inherit: i32, pid: u32) -> *mut c_void;
+            fn GetExitCodeProcess(process: *mut c_void, code: *mut u32) -> i32;
+            fn CloseHandle(handle: *mut c_void) -> i32;
+        }
+        unsafe {
+            let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+            if handle.is_null() {
+                return false;
+            }
+            let mut code = 0;
+            let ok = GetExitCodeProcess(handle, &mut code);
+            CloseHandle(handle);
+            ok != 0 && code == STILL_ACTIVE
+        }
+    }
+
+    fn runtime_dir(&self) -> PathBuf {
+        std::env::var_os("LOCALAPPDATA")
+            .map(|d| PathBuf::from(d).join("AIMX"))
+            .unwrap_or_else(super::temp_runtime_dir)
+    }
+
+    fn random_bytes(&self, buf: &mut [u8]) -> io::Result<()> {
+        const BCRYPT_USE_SYSTEM_PREFERRED_RNG: u32 = 2;
+        #[link(name = "bcrypt")]
+        unsafe extern "system" {
+            fn BCryptGenRandom(algorithm: *mut c_void, buffer: *mut u8, len: u32, flags: u32) -> i32;
+        }
+        let len = u32::try_from(buf.len()).map_err(io::Error::other)?;
+        let status = unsafe {
+            BCryptGenRandom(std::ptr::null_mut(), buf.as_mut_ptr(), len, BCRYPT_USE_SYSTEM_PREFERRED_RNG)
+        };
+        match status {
+            0 => Ok(()),
+            status => Err(io::Error::other(format!("BCryptGenRandom failed: NTSTATUS {status:#x}"))),
+        }
+    }
+}
+
+fn monitors() -> Vec<Monitor> {
+    #[repr(C)]
+    struct Rect {
+        left: i32,
+        top: i32,
+        right: i32,
+        bottom: i32,
+    }
+
+    #[repr(C)]
+    struct MonitorInfoExW {
+        size: u32,
+        monitor: Rect,
+        work: Rect,
+        flags: u32,
+        device: [u16; 32],
+    }
+
+    const MONITORINFOF_PRIMARY: u32 = 1;
+    const MDT_EFFECTIVE_DPI: u32 = 0;
+
+    const DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2: isize = -4;
+
+    type MonitorEnumProc = unsafe extern "system" fn(*mut c_void, *mut c_void, *mut Rect, isize) -> i32;
+
+    unsafe extern "system" {
+        fn EnumDisplayMonitors(hdc: *mut c_void, clip: *const Rect, proc_: MonitorEnumProc, data: isize) -> i32;
+        fn GetMonitorInfoW(monitor: *mut c_void, info: *mut c_void) -> i32;
+        fn SetProcessDpiAwarenessContext(context: isize) -> i32;
+    }
+    #[link(name = "shcore")]
+    unsafe extern "system" {
+        fn GetDpiForMonitor(monitor: *mut c_void, kind: u32, dpi_x: *mut u32, dpi_y: *mut u32) -> i32;
+    }
+
+    unsafe extern "system" fn collect(monitor: *mut c_void, _hdc: *mut c_void, _rect: *mut Rect, data: isize) -> i32 {
+        let list = unsafe { &mut *(data as *mut Vec<Monitor>) };
+        let mut info = MonitorInfoExW {
+            size: core::mem::size_of::<MonitorInfoExW>() as u32,
+            monitor: Rect { left: 0, top: 0, right: 0, bottom: 0 },
+            work: Rect { left: 0, top: 0, right: 0, bottom: 0 },
+            flags: 0,
+            device: [0; 32],
+        };
+        if unsafe { GetMonitorInfoW(monitor, (&raw mut info).cast()) } == 0 {
+            return 1;
+        }
+        let (mut dpi_x, mut dpi_y) = (96, 96);
+        if unsafe { GetDpiForMonitor(monitor, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y) } != 0 {
+            dpi_x = 96;
+        }
+        let len = info.device.iter().position(|&c| c == 0).unwrap_or(info.device.len());
+        let r = &info.monitor;
+        list.push(Monitor {
+            name: String::from_utf16_lossy(&info.device[..len]),
+            x: r.left,
+            y: r.top,
+            width: (r.right - r.left) as u32,
+            height: (r.bottom - r.top) as u32,
+            scale: dpi_x as f32 / 96.0,
+            primary: info.flags & MONITORINFOF_PRIMARY != 0,
+        });
+        1
+    }
+
+    let mut list: Vec<Monitor> = Vec::new();
+    unsafe {
+        // Without DPI awareness Windows reports scaled-down rectangles. winit sets
+        // the same mode later; whichever call comes second fails harmlessly.
+        SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
+        EnumDisplayMonitors(
+            core::ptr::null_mut(),
+            core::ptr::null(),
+            collect,
+            &mut list as *mut Vec<Monitor> as isize,
+        );
+    }
+    list
+}
+
+/// `GetAsyncKeyState` sees keys and buttons whichever process has focus.
+struct Win32Input;
+
+impl InputPoller for Win32Input {
+    fn held(&mut self, trigger: Trigger) -> Option<bool> {
+        unsafe extern "system" {
+            fn GetAsyncKeyState(vk: i32) -> i16;
+        }
+        let vk = match trigger {
+            Trigger::MouseLeft => 0x01,
+            Trigger::MouseRight => 0x02,
+            Trigger::MouseMiddle => 0x04,
+            Trigger::Mouse4 => 0x05,
+            Trigger::Mouse5 => 0x06,
+            Trigger::Shift => 0x10,
+            Trigger::Ctrl => 0x11,
+            Trigger::Alt => 0x12,
+            Trigger::Key(key) => hotkeys::vk(key),
+        };
+        // The high bit is set while the key is down
+        Some(unsafe { GetAsyncKeyState(vk as i32) } < 0)
+    }
+}
+
+/// Display changes are broadcast to top-level windows, so run a hidden one
+/// with its own message loop.
+fn watch_displays(on_change: Box<dyn Fn() + Send>) {
+    use ffi::{
+        CreateWindowExW, DefWindowProcW, DispatchMessageW, GetMessageW, GetModuleHandleW, Msg, RegisterClassW,
+        TranslateMessage, WndClassW,
+    };
+    use std::cell::RefCell;
+
+    const WM_SETTINGCHANGE: u32 = 0x001A;
+    const WM_DISPLAYCHANGE: u32 = 0x007E;
+    const WM_DPICHANGED: u32 = 0x02E0;
+
+    thread_local! {
+        static ON_CHANGE: RefCell<Option<Box<dyn Fn()>>> = RefCell::new(None);
+    }
+
+    unsafe extern "system" fn wnd_proc(hwnd: HWND, msg: u32, wparam: usize, lparam: isize) -> isize {
+        if matches!(msg, WM_DISPLAYCHANGE | WM_DPICHANGED | WM_SETTINGCHANGE) {
+            ON_CHANGE.with(|f| {
+                if let Some(f) = f.borrow().as_ref() {
+                    f();
+                }
+            });
+        }
+        unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) }
+    }
+
+    std::thread::spawn(move || {
+        ON_CHANGE.with(|f| *f.borrow_mut() = Some(on_change));
+        let class_name: Vec<u16> = "AIMX.DisplayWatcher".encode_utf16().chain(std::iter::once(0)).collect();
+        unsafe {
+            let instance = GetModuleHandleW(core::ptr::null());
+            let class = WndClassW {
+                style: 0,
+                wnd_proc,
+                cls_extra: 0,
+                wnd_extra: 0,
+                instance,
+                icon: core::ptr::null_mut(),
+                cursor: core::ptr::null_mut(),
+                background: core::ptr::null_mut(),
+                menu_name: core::ptr::null(),
+                class_name: class_name.as_ptr(),
+            };
+            RegisterClassW(&class);
+            // Never shown; message-only windows would miss broadcasts
+            let hwnd = CreateWindowExW(
+                WS_EX_TOOLWINDOW,
+                class_name.as_ptr(),
+                class_name.as_ptr(),
+                0,
+                0,
+                0,
+                0,
+                0,
+                core::ptr::null_mut(),
+                core::ptr::null_mut(),
+                instance,
+                core::ptr::null_mut(),
+            );
+            if hwnd.is_null() {
+                log::warn!("cannot create display watcher window");
+                return;
+            }
+            let mut msg = core::mem::zeroed::<Msg>();
+            while GetMessageW(&mut msg, core::ptr::null_mut(), 0, 0) > 0 {
+                TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+        }
+    });
+}
+
+mod layered {
+    use core::ffi::c_void;
+    use eframe::egui::ColorImage;
+
+    use super::ffi::{
+        CreateWindowExW, DefWindowProcW, DispatchMessageW, GetModuleHandleW, Msg, PeekMessageW, RegisterClassW,
+        TranslateMessage, WndClassW,
+    };
+    use super::{HWND, WS_EX_LAYERED, WS_EX_TOOLWINDOW, WS_EX_TRANSPARENT};
+    use crate::platform::surface::Surface;
+
+    const WS_EX_TOPMOST: u32 = 0x00000008;
+    const WS_EX_NOACTIVATE: u32 = 0x08000000;
+    const WS_POPUP: u32 = 0x80000000;
+    const SW_HIDE: i32 = 0;
+    const SW_SHOWNOACTIVATE: i32 = 4;
+    const PM_REMOVE: u32 = 1;
+    const ULW_ALPHA: u32 = 2;
+    const AC_SRC_ALPHA: u8 = 1;
+    const DIB_RGB_COLORS: u32 = 0;
+    const SRCCOPY: u32 = 0x00CC0020;
+    const CAPTUREBLT: u32 = 0x40000000;
+
+    #[repr(C)]
+    struct BitmapInfoHeader {
+        size: u32,
+        width: i32,
+        height: i32,
+        planes: u16,
+        bit_count: u16,
+        compression: u32,
+        size_image: u32,
+        x_pels_per_meter: i32,
+        y_pels_per_meter: i32,
+        clr_used: u32,
+        clr_important: u32,
+    }
+
+    #[repr(C)]
+    struct BlendFunction {
+        op: u8,
+        flags: u8,
+        constant_alpha: u8,
+        alpha_format: u8,
+    }
+
+    unsafe extern "system" {
+        fn ShowWindow(hwnd: HWND, cmd: i32) -> i32;
+        fn GetDC(hwnd: HWND) -> *mut c_void;
+        fn ReleaseDC(hwnd: HWND, hdc: *mut c_void) -> i32;
+        fn UpdateLayeredWindow(
+            hwnd: HWND,
+            hdc_dst: *mut c_void,
+            pt_dst: *const [i32; 2],
+            size: *const [i32; 2],
+            hdc_src: *mut c_void,
+            pt_src: *const [i32; 2],
+            key: u32,
+            blend: *const BlendFunction,
+            flags: u32,
+        ) -> i32;
+    }
+    #[link(name = "gdi32")]
+    unsafe extern "system" {
+        fn CreateCompatibleDC(hdc: *mut c_void) -> *mut c_void;
+        fn CreateDIBSection(
+            hdc: *mut c_void,
+            info: *const BitmapInfoHeader,
+            usage: u32,
+            bits: *mut *mut c_void,
+            section: *mut c_void,
+            offset: u32,
+        ) -> *mut c_void;
+        fn SelectObject(hdc: *mut c_void, object: *mut c_void) -> *mut c_void;
+        fn DeleteObject(object: *mut c_void) -> i32;
+        fn DeleteDC(hdc: *mut c_void) -> i32;
+        fn BitBlt(
+            hdc: *mut c_void,
+            x: i32,
+            y: i32,
+            width: i32,
+            height: i32,
+            hdc_src: *mut c_void,
+            x_src: i32,
+            y_src: i32,
+            rop: u32,
+        ) -> i32;
+    }
+
+    unsafe extern "system" fn wnd_proc(hwnd: HWND, msg: u32, wparam: usize, lparam: isize) -> isize {
+        unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) }
+    }
+
+    /// A top-down 32-bit DIB header for a `width` by `height` bitmap.
+    fn header(width: i32, height: i32) -> BitmapInfoHeader {
+        BitmapInfoHeader {
+            size: core::mem::size_of::<BitmapInfoHeader>() as u32,
+            width,
+            // Negative height: rows run top to bottom like an image
+            height: -height,
+            planes: 1,
+            bit_count: 32,
+            compression: 0,
+            size_image: 0,
+            x_pels_per_meter: 0,
+            y_pels_per_meter: 0,
+            clr_used: 0,
+            clr_important: 0,
+        }
+    }
+
+    /// Copy part of the virtual screen into an opaque image. `CAPTUREBLT`
+    /// includes other layered windows, such as games' own overlays.
+    pub fn capture(x: i32, y: i32, width: u32, height: u32) -> Result<ColorImage, String> {
+        let (w, h) = (width as i32, height as i32);
+        let mut image = ColorImage::filled([width as usize, height as usize], eframe::egui::Color32::BLACK);
+        unsafe {
+            let screen = GetDC(core::ptr::null_mut());
+            let memory = CreateCompatibleDC(screen);
+            let mut bits = core::ptr::null_mut();
+            let bitmap = CreateDIBSection(memory, &header(w, h), DIB_RGB_COLORS, &mut bits, core::ptr::null_mut(), 0);
+            let result = if bitmap.is_null() || bits.is_null() {
+                Err(format!("cannot create bitmap: {}", std::io::Error::last_os_error()))
+            } else {
+                let previous = SelectObject(memory, bitmap);
+                let ok = BitBlt(memory, 0, 0, w, h, screen, x, y, SRCCOPY | CAPTUREBLT);
+                SelectObject(memory, previous);
+                if ok == 0 {
+                    Err(format!("cannot read the screen: {}", std::io::Error::last_os_error()))
+                } else {
+                    let src = core::slice::from_raw_parts(bits as *const u8, image.pixels.len() * 4);
+                    for (c, px) in image.pixels.iter_mut().zip(src.chunks_exact(4)) {
+                        *c = eframe::egui::Color32::from_rgb(px[2], px[1], px[0]);
+                    }
+                    Ok(())
+                }
+            };
+            if !bitmap.is_null() {
+                DeleteObject(bitmap);
+            }
+            DeleteDC(memory);
+            ReleaseDC(core::ptr::null_mut(), screen);
+            result.map(|()| image)
+        }
+    }
+
+    /// Topmost, click-through and out of the taskbar like the GL overlay, but
+    /// with per-pixel alpha instead of a black color key.
+    pub struct LayeredSurface {
+        hwnd: HWND,
+        shown: bool,
+    }
+
+    impl LayeredSurface {
+        pub fn create() -> Result<Self, String> {
+            let class_name: Vec<u16> = "AIMX.SoftwareOverlay".encode_utf16().chain(std::iter::once(0)).collect();
+            let hwnd = unsafe {
+                let instance = GetModuleHandleW(core::ptr::null());
+                let class = WndClassW {
+                    style: 0,
+                    wnd_proc,
+                    cls_extra: 0,
+                    wnd_extra: 0,
+                    instance,
+                    icon: core::ptr::null_mut(),
+                    cursor: core::ptr::null_mut(),
+                    background: core::ptr::null_mut(),
+                    menu_name: core::ptr::null(),
+                    class_name: class_name.as_ptr(),
+                };
+                RegisterClassW(&class);
+                CreateWindowExW(
+                    WS_EX_LAYERED | WS_EX_TRANSPARENT | WS_EX_TOOLWINDOW | WS_EX_TOPMOST | WS_EX_NOACTIVATE,
+                    class_name.as_ptr(),
+                    class_name.as_ptr(),
+                    WS_POPUP,
+                    0,
+                    0,
+                    1,
+                    1,
+                    core::ptr::null_mut(),
+                    core::ptr::null_mut(),
+                    instance,
+                    core::ptr::null_mut(),
+                )
+            };
+            if hwnd.is_null() {
+                return Err(format!("cannot create layered window: {}", std::io::Error::last_os_error()));
+            }
+            log::info!("created layered software overlay window {:?}", hwnd);
+            Ok(Self { hwnd, shown: false })
+        }
+    }
+
+    impl Surface for LayeredSurface {
+        fn present(&mut self, x: i32, y: i32, image: &ColorImage) -> Result<(), String> {
+            let [width, height] = image.size.map(|v| v as i32);
+            let header = header(width, height);
+            let blend = BlendFunction {
+                op: 0,
+                flags: 0,
+                constant_alpha: 255,
+                alpha_format: AC_SRC_ALPHA,
+            };
+            unsafe {
+                let screen = GetDC(core::ptr::null_mut());
+                let memory = CreateCompatibleDC(screen);
+                let mut bits = core::ptr::null_mut();
+                let bitmap = CreateDIBSection(memory, &header, DIB_RGB_COLORS, &mut bits, core::ptr::null_mut(), 0);
+                let result = if bitmap.is_null() || bits.is_null() {
+                    Err(format!("cannot create bitmap: {}", std::io::Error::last_os_error()))
+                } else {
+                    // Premultiplied BGRA, as UpdateLayeredWindow expects with AC_SRC_ALPHA
+                    let dst = core::slice::from_raw_parts_mut(bits as *mut u8, image.pixels.len() * 4);
+                    for (px, c) in dst.chunks_exact_mut(4).zip(&image.pixels) {
+                        px.copy_from_slice(&[c.b(), c.g(), c.r(), c.a()]);
+                    }
+                    let previous = SelectObject(memory, bitmap);
+                    let ok = UpdateLayeredWindow(
+                        self.hwnd,
+                        screen,
+                        &[x, y],
+                        &[width, height],
+                        memory,
+                        &[0, 0],
+                        0,
+                        &blend,
+                        ULW_ALPHA,
+                    );
+                    SelectObject(memory, previous);
+                    if ok == 0 {
+                        Err(format!("UpdateLayeredWindow failed: {}", std::io::Error::last_os_error()))
+                    } else {
+                        Ok(())
+                    }
+                };
+                if !bitmap.is_null() {
+                    DeleteObject(bitmap);
+                }
+                DeleteDC(memory);
+                ReleaseDC(core::ptr::null_mut(), screen);
+                if result.is_ok() && !self.shown {
+                    ShowWindow(self.hwnd, SW_SHOWNOACTIVATE);
+                    self.shown = true;
+                }
+                result
+            }
+        }
+
+        fn hide(&mut self) {
+            if std::mem::take(&mut self.shown) {
+                unsafe { ShowWindow(self.hwnd, SW_HIDE) };
+            }
+        }
+
+        fn dispatch(&mut self) {
+            unsafe {
+                let mut msg = core::mem::zeroed::<Msg>();
+                while PeekMessageW(&mut msg, core::ptr::null_mut(), 0, 0, PM_REMOVE) != 0 {
+                    TranslateMessage(&msg);
+                    DispatchMessageW(&msg);
+                }
+            }
+        }
+    }
+}
+
+mod hotkeys {
+    use crossbeam_channel::{Receiver, Sender};
+
+    use super::HWND;
+    use super::ffi::{GetMessageW, Msg, PeekMessageW};
+    use crate::hotkey::{Action, Bindings, Hotkey, Key};
+    use crate::platform::hotkey::{HotkeyGrabber, OnPress};
+
+    const WM_USER: u32 = 0x0400;
+    const WM_HOTKEY: u32 = 0x0312;
+    /// Posted to the hotkey thread when new bindings are waiting
+    const WM_APP: u32 = 0x8000;
+    const PM_NOREMOVE: u32 = 0;
+    const MOD_ALT: u32 = 0x0001;
+    const MOD_CONTROL: u32 = 0x0002;
+    const MOD_SHIFT: u32 = 0x0004;
+    const MOD_WIN: u32 = 0x0008;
+    const MOD_NOREPEAT: u32 = 0x4000;
+    const ERROR_HOTKEY_ALREADY_REGISTERED: i32 = 1409;
+
+    unsafe extern "system" {
+        fn PostThreadMessageW(thread: u32, msg: u32, wparam: usize, lparam: isize) -> i32;
+        fn GetCurrentThreadId() -> u32;
+        fn RegisterHotKey(hwnd: HWND, id: i32, modifiers: u32, vk: u32) -> i32;
+        fn UnregisterHotKey(hwnd: HWND, id: i32) -> i32;
+    }
+
+    type Request = (Bindings, Sender<Vec<(Action, String)>>);
+
+    /// `RegisterHotKey` without a window: `WM_HOTKEY` goes to the queue of the
+    /// thread that registered, so one thread owns every hotkey and runs the loop.
+    pub struct Win32Hotkeys {
+        thread: u32,
+        requests: Sender<Request>,
+    }
+
+    impl Win32Hotkeys {
+        pub fn start(on_press: OnPress) -> Option<Self> {
+            let (requests, pending) = crossbeam_channel::unbounded();
+            let (id_tx, id_rx) = crossbeam_channel::bounded(1);
+            std::thread::spawn(move || run(on_press, pending, id_tx));
+            let thread = id_rx.recv().ok()?;
+            Some(Self { thread, requests })
+        }
+    }
+
+    impl HotkeyGrabber for Win32Hotkeys {
+        fn register(&mut self, bindings: &Bindings) -> Vec<(Action, String)> {
+            let (reply_tx, reply_rx) = crossbeam_channel::bounded(1);
+            let sent = self.requests.send((bindings.clone(), reply_tx)).is_ok()
+                && unsafe { PostThreadMessageW(self.thread, WM_APP, 0, 0) } != 0;
+            match reply_rx.recv() {
+                Ok(errors) if sent => errors,
+                _ => bindings
+                    .keys()
+                    .map(|action| (*action, "the hotkey thread is not running".to_string()))
+                    .collect(),
+            }
+        }
+    }
+
+    fn run(on_press: OnPress, pending: Receiver<Request>, id_tx: Sender<u32>) {
+        let mut registered: Vec<Action> = Vec::new();
+        unsafe {
+            // Windows creates the thread's message queue on its first peek
+            let mut msg = core::mem::zeroed::<Msg>();
+            PeekMessageW(&mut msg, core::ptr::null_mut(), WM_USER, WM_USER, PM_NOREMOVE);
+            let _ = id_tx.send(GetCurrentThreadId());
+            while GetMessageW(&mut msg, core::ptr::null_mut(), 0, 0) > 0 {
+                match msg.message {
+                    WM_HOTKEY => {
+                        if let Some(action) = registered.get(msg.wparam) {
+                            on_press(*action);
+                        }
+                    }
+                    WM_APP => {
+                        while let Ok((bindings, reply)) = pending.try_recv() {
+                            for id in 0..registered.len() {
+                                UnregisterHotKey(core::ptr::null_mut(), id as i32);
+                            }
+                            registered.clear();
+                            let _ = reply.send(register_all(&bindings, &mut registered));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Register `bindings`, indexing `registered` by hotkey id.
+    fn register_all(bindings: &Bindings, registered: &mut Vec<Action>) -> Vec<(Action, String)> {
+        let mut errors = Vec::new();
+        for (action, hotkey) in bindings {
+            let id = registered.len() as i32;
+            let ok = unsafe { RegisterHotKey(core::ptr::null_mut(), id, modifiers(hotkey, *action), vk(hotkey.key)) };
+            if ok != 0 {
+                registered.push(*action);
+                continue;
+            }
+            let message = match std::io::Error::last_os_error().raw_os_error() {
+                Some(ERROR_HOTKEY_ALREADY_REGISTERED) => format!("{hotkey} is already taken by another application"),
+                code => format!("{hotkey}: RegisterHotKey failed (error {})", code.unwrap_or(0)),
+            };
+            errors.push((*action, message));
+        }
+        errors
+    }
+
+    /// Holding a nudge key keeps nudging; everything else fires once.
+    fn modifiers(hotkey: &Hotkey, action: Action) -> u32 {
+        let m = hotkey.modifiers;
+        let mut mods = if action.nudge().is_some() { 0 } else { MOD_NOREPEAT };
+        for (on, flag) in [(m.alt, MOD_ALT), (m.ctrl, MOD_CONTROL), (m.shift, MOD_SHIFT), (m.logo, MOD_WIN)] {
+            if on {
+                mods |= flag;
+            }
+        }
+        mods
+    }
+
+    /// The virtual-key code for `key`.
+    pub fn vk(key: Key) -> u32 {
+        match key {
+            Key::Letter(c) => u32::from(c.to_ascii_uppercase()),
+            Key::Digit(d) => 0x30 + u32::from(d),
+            Key::F(n) => 0x6F + u32::from(n),
+            Key::Space => 0x20,
+            Key::PageUp => 0x21,
+            Key::PageDown => 0x22,
+            Key::End => 0x23,
+            Key::Home => 0x24,
+            Key::Left => 0x25,
+            Key::Up => 0x26,
+            Key::Right => 0x27,
+            Key::Down => 0x28,
+            Key::Insert => 0x2D,
+            Key::Delete => 0x2E,
+        }
+    }
+}
+
+mod windows {
+    use core::ffi::c_void;
+
+    use super::HWND;
+    use crate::config::WindowMatch;
+    use crate::platform::window::{ClientArea, Foreground, WindowFinder, WindowState};
+
+    #[repr(C)]
+    struct Rect {
+        left: i32,
+        top: i32,
+        right: i32,
+        bottom: i32,
+    }
+
+    #[repr(C)]
+    struct MonitorInfo {
+        size: u32,
+        monitor: Rect,
+        work: Rect,
+        flags: u32,
+    }
+
+    #[repr(C)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    const PROCESS_QUERY_LIMITED_INFORMATION: u32 = 0x1000;
+    const MONITOR_DEFAULTTONEAREST: u32 = 2;
+
+    type EnumWindowsProc = unsafe extern "system" fn(HWND, isize) -> i32;
+
+    unsafe extern "system" {
+        fn EnumWindows(proc_: EnumWindowsProc, data: isize) -> i32;
+        fn IsWindowVisible(hwnd: HWND) -> i32;
+        fn IsIconic(hwnd: HWND) -> i32;
+        fn IsWindow(hwnd: HWND) -> i32;
+        fn GetWindowTextW(hwnd: HWND, text: *mut u16, max: i32) -> i32;
+        fn GetWindowThreadProcessId(hwnd: HWND, pid: *mut u32) -> u32;
+        fn GetClientRect(hwnd: HWND, rect: *mut Rect) -> i32;
+        fn ClientToScreen(hwnd: HWND, point: *mut Point) -> i32;
+        fn OpenProcess(access: u32, inherit: i32, pid: u32) -> *mut c_void;
+        fn QueryFullProcessImageNameW(process: *mut c_void, flags: u32, name: *mut u16, size: *mut u32) -> i32;
+        fn CloseHandle(handle: *mut c_void) -> i32;
+        fn GetForegroundWindow() -> HWND;
+        fn GetClassNameW(hwnd: HWND, name: *mut u16, max: i32) -> i32;
+        fn GetWindowRect(hwnd: HWND, rect: *mut Rect) -> i32;
+        fn MonitorFromWindow(hwnd: HWND, flags: u32) -> *mut c_void;
+        // Takes a `MonitorInfo`; declared untyped like the one in `monitors`,
+        // which passes the larger MONITORINFOEXW
+        fn GetMonitorInfoW(monitor: *mut c_void, info: *mut c_void) -> i32;
+    }
+
+    #[derive(Default)]
+    pub struct Win32Windows {
+        /// Window found last time, checked first so following a window
+        /// doesn't enumerate every window on each poll
+        last: Option<isize>,
+        /// Focused window and its process name, which doesn't change while it lives
+        focused: Option<(isize, String)>,
+    }
+
+    /// The desktop's own windows, which get focus when clicking the wallpaper.
+    fn is_desktop(hwnd: HWND) -> bool {
+        let mut buf = [0u16; 64];
+        let len = unsafe { GetClassNameW(hwnd, buf.as_mut_ptr(), buf.len() as i32) };
+        let class = String::from_utf16_lossy(&buf[..len.max(0) as usize]);
+        class == "Progman" || class == "WorkerW"
+    }
+
+    /// Whether `hwnd` covers the whole monitor it is on, like borderless and
+    /// exclusive fullscreen games.
+    fn covers_monitor(hwnd: HWND) -> bool {
+        let empty = || Rect { left: 0, top: 0, right: 0, bottom: 0 };
+        let mut rect = empty();
+        let mut info = MonitorInfo {
+            size: core::mem::size_of::<MonitorInfo>() as u32,
+            monitor: empty(),
+            work: empty(),
+            flags: 0,
+        };
+        unsafe {
+            let monitor = MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST);
+            if GetWindowRect(hwnd, &mut rect) == 0 || GetMonitorInfoW(monitor, (&raw mut info).cast()) == 0 {
+                return false;
+            }
+        }
+        let m = &info.monitor;
+        rect.left <= m.left && rect.top <= m.top && rect.right >= m.right && rect.bottom >= m.bottom
+    }
+
+    fn matches(target: &WindowMatch, hwnd: HWND) -> bool {
+        // Only look up what the match needs; opening the owning process is the slow part
+        match target {
+            WindowMatch::Title(_) => target.matches("", &title(hwnd)),
+            WindowMatch::Process(_) => target.matches(&process_name(hwnd), ""),
+        }
+    }
+
+    fn title(hwnd: HWND) -> String {
+        let mut buf = [0u16; 512];
+        let len = unsafe { GetWindowTextW(hwnd, buf.as_mut_ptr(), buf.len() as i32) };
+        String::from_utf16_lossy(&buf[..len.max(0) as usize])
+    }
+
+    /// File name of the executable that owns `hwnd`, e.g. `game.exe`.
+    fn process_name(hwnd: HWND) -> String {
+        let mut pid = 0;
+        unsafe { GetWindowThreadProcessId(hwnd, &mut pid) };
+        let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid) };
+        if process.is_null() {
+            return String::new();
+        }
+        let mut buf = [0u16; 1024];
+        let mut len = buf.len() as u32;
+        let ok = unsafe { QueryFullProcessImageNameW(process, 0, buf.as_mut_ptr(), &mut len) };
+        unsafe { CloseHandle(process) };
+        if ok == 0 {
+            return String::new();
+        }
+        let path = String::from_utf16_lossy(&buf[..len as usize]);
+        path.rsplit(['\\', '/']).next().unwrap_or_default().to_string()
+    }
+
+    fn state(hwnd: HWND) -> Option<WindowState> {
+        if unsafe { IsIconic(hwnd) } != 0 {
+            return Some(WindowState::Minimized);
+        }
+        let mut rect = Rect { left: 0, top: 0, right: 0, bottom: 0 };
+        let mut origin = Point { x: 0, y: 0 };
+        unsafe {
+            if GetClientRect(hwnd, &mut rect) == 0 || ClientToScreen(hwnd, &mut origin) == 0 {
+                return None;
+            }
+        }
+        Some(WindowState::Visible(ClientArea {
+            x: origin.x,
+            y: origin.y,
+            width: (rect.right - rect.left).max(0) as u32,
+            height: (rect.bottom - rect.top).max(0) as u32,
+        }))
+    }
+
+    struct Search<'a> {
+        target: &'a WindowMatch,
+        found: Option<HWND>,
+    }
+
+    unsafe extern "system" fn visit(hwnd: HWND, data: isize) -> i32 {
+        let search = unsafe { &mut *(data as *mut Search) };
+        if unsafe { IsWindowVisible(hwnd) } == 0 {
+            return 1;
+        }
+        if matches(search.target, hwnd) {
+            search.found = Some(hwnd);
+            return 0;
+        }
+        1
+    }
+
+    impl WindowFinder for Win32Windows {
+        fn find(&mut self, target: &WindowMatch) -> Option<WindowState> {
+            let cached = self.last.map(|h| h as HWND).filter(|&hwnd| {
+                let alive = unsafe { IsWindow(hwnd) != 0 && IsWindowVisible(hwnd) != 0 };
+                alive && matches(target, hwnd)
+            });
+            let hwnd = match cached {
+                Some(hwnd) => hwnd,
+                None => {
+                    let mut search = Search { target, found: None };
+                    unsafe { EnumWindows(visit, &mut search as *mut Search as isize) };
+                    self.last = search.found.map(|h| h as isize);
+                    search.found?
+                }
+            };
+            state(hwnd)
+        }
+
+        fn foreground(&mut self) -> Foreground {
+            let hwnd = unsafe { GetForegroundWindow() };
+            if hwnd.is_null() || is_desktop(hwnd) {
+                return Foreground::Nothing;
+            }
+            let process = match &self.focused {
+                Some((h, name)) if *h == hwnd as isize => name.clone(),
+                _ => {
+                    let name = process_name(hwnd);
+                    self.focused = Some((hwnd as isize, name.clone()));
+                    name
+                }
+            };
+            Foreground::Window {
+                process,
+                fullscreen: covers_monitor(hwnd),
+            }
+        }
+    }
+}
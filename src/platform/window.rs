@@ -0,0 +1,61 @@
+use crate::config::WindowMatch;
+
+/// A window's client area in physical pixels of the virtual desktop.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClientArea {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ClientArea {
+    pub fn center(&self) -> (f32, f32) {
+        (
+            self.x as f32 + self.width as f32 / 2.0,
+            self.y as f32 + self.height as f32 / 2.0,
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WindowState {
+    Visible(ClientArea),
+    Minimized,
+}
+
+/// What has keyboard focus, for visibility rules.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Foreground {
+    /// The window system can't tell
+    Unknown,
+    /// The desktop, or no window at all
+    Nothing,
+    Window {
+        /// Executable name, e.g. `game.exe`
+        process: String,
+        /// The window covers its whole monitor
+        fullscreen: bool,
+    },
+}
+
+/// Looks up top-level windows for anchoring a crosshair and visibility rules.
+pub trait WindowFinder: Send {
+    /// The first top-level window matching `target`, or `None` if there is none.
+    fn find(&mut self, target: &WindowMatch) -> Option<WindowState>;
+
+    /// The window with keyboard focus. Called every frame or so while a
+    /// visibility rule is set, so it should be cheap.
+    fn foreground(&mut self) -> Foreground {
+        Foreground::Unknown
+    }
+}
+
+/// For window systems with no way to inspect other clients' windows, like Wayland.
+pub struct NoWindows;
+
+impl WindowFinder for NoWindows {
+    fn find(&mut self, _target: &WindowMatch) -> Option<WindowState> {
+        None
+    }
+}
@@ -0,0 +1,1257 @@
+use eframe::egui;
+use log::{info, warn};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use x11rb::connection::Connection as _;
+use x11rb::protocol::Event;
+use x11rb::protocol::randr;
+use x11rb::protocol::shape::{self, SK, SO};
+use x11rb::protocol::xproto::{
+    self, AtomEnum, ChangeWindowAttributesAux, ClipOrdering, ConfigureWindowAux, ConnectionExt,
+    GetPropertyReply, PropMode, StackMode, Window,
+};
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+
+use super::hotkey::{HotkeyGrabber, NoHotkeys, OnPress};
+use super::input::{InputPoller, NoInput};
+use super::supervise::Supervisor;
+use super::surface::Surface;
+use super::window::{ClientArea, Foreground, NoWindows, WindowFinder, WindowState};
+use super::{Monitor, Platform};
+use crate::config::WindowMatch;
+use crate::hotkey::{Action, Bindings, Hotkey, Key, Trigger};
+
+/// Linux implementation of [`Platform`]: X11 windows, with monitors listed
+/// from Wayland outputs when running under a Wayland session.
+pub struct X11Platform {
+    supervisor: Supervisor,
+    /// The styled overlay window, or 0 before there is one
+    overlay: AtomicU32,
+}
+
+impl X11Platform {
+    pub fn new() -> Self {
+        Self {
+            supervisor: Supervisor::new(),
+            overlay: AtomicU32::new(0),
+        }
+    }
+}
+
+impl Default for X11Platform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Platform for X11Platform {
+    /// Override-redirect, kept on top and click-through. Fails unless eframe
+    /// picked a 32-bit visual, which transparency needs.
+    fn apply_overlay_style(&self, cc: &eframe::CreationContext) -> Result<(), String> {
+        use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+
+        let window = match cc.window_handle().map(|h| h.as_raw()) {
+            Ok(RawWindowHandle::Xlib(h)) => h.window as u32,
+            Ok(RawWindowHandle::Xcb(h)) => h.window.get(),
+            Ok(other) => return Err(format!("no overlay backend for window handle {other:?}")),
+            Err(e) => return Err(format!("cannot get overlay window handle: {e}")),
+        };
+        self.overlay.store(window, Ordering::SeqCst);
+        style_overlay(window)
+    }
+
+    /// Focuses the window, since the window manager never gives focus to
+    /// override-redirect windows, and resets the input shape set by
+    /// [`style_overlay`] alongside eframe's own passthrough switch.
+    fn set_overlay_interactive(&self, interactive: bool) {
+        let window = self.overlay.load(Ordering::SeqCst);
+        if window == 0 {
+            return;
+        }
+        if let Err(e) = set_interactive(window, interactive) {
+            warn!("cannot change overlay input handling: {e}");
+        }
+    }
+
+    fn capture_screen(&self, x: i32, y: i32, width: u32, height: u32) -> Result<egui::ColorImage, String> {
+        capture(x, y, width, height)
+    }
+
+    fn software_surface(&self) -> Result<Box<dyn Surface>, String> {
+        Ok(Box::new(X11Surface::create()?))
+    }
+
+    fn monitors(&self) -> Vec<Monitor> {
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            match super::wayland::monitors() {
+                Ok(list) if !list.is_empty() => return list,
+                Ok(_) => {}
+                Err(e) => log::debug!("cannot list Wayland outputs: {e}"),
+            }
+        }
+        super::non_empty(monitors().unwrap_or_else(|e| {
+            log::debug!("cannot list RandR monitors: {e}");
+            Vec::new()
+        }))
+    }
+
+    fn watch_displays(&self, on_change: Box<dyn Fn() + Send>) {
+        if let Err(e) = watch_displays(on_change) {
+            warn!("cannot watch for display changes: {e}");
+        }
+    }
+
+    /// Sees X11 clients, including XWayland ones under a Wayland session.
+    fn window_finder(&self) -> Box<dyn WindowFinder> {
+        match X11Windows::connect() {
+            Ok(finder) => Box::new(finder),
+            Err(e) => {
+                warn!("cannot look up windows: {e}");
+                Box::new(NoWindows)
+            }
+        }
+    }
+
+    /// Passive key grabs on the root window. Under Wayland these would only
+    /// fire while an XWayland window has focus, so they are not offered.
+    fn hotkeys(&self, on_press: OnPress) -> Box<dyn HotkeyGrabber> {
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            return Box::new(NoHotkeys(
+                "global hotkeys are not available on Wayland; bind `aimx ctl toggle` in your compositor instead",
+            ));
+        }
+        match X11Hotkeys::connect(on_press) {
+            Ok(grabber) => Box::new(grabber),
+            Err(e) => {
+                warn!("cannot grab hotkeys: {e}");
+                Box::new(NoHotkeys("cannot connect to the X server"))
+            }
+        }
+    }
+
+    /// Global pointer and keyboard state, which XWayland only keeps for its
+    /// own clients, so not under Wayland.
+    fn input_poller(&self) -> Box<dyn InputPoller> {
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            return Box::new(NoInput);
+        }
+        match X11Input::connect() {
+            Ok(poller) => Box::new(poller),
+            Err(e) => {
+                warn!("cannot read input state: {e}");
+                Box::new(NoInput)
+            }
+        }
+    }
+
+    fn spawn_supervised(&self, cmd: &mut std::process::Command) -> std::io::Result<std::process::Child> {
+        self.supervisor.spawn(cmd)
+    }
+
+    fn process_alive(&self, pid: u32) -> bool {
+        std::path::Path::new(&format!("/proc/{pid}")).exists()
+    }
+}
+
+/// What applying the overlay style found out about the window.
+#[derive(Debug)]
+pub struct OverlayReport {
+    /// A compositing manager is running; without one ARGB windows render opaque
+    pub compositor: bool,
+}
+
+/// Property marking aimx's own overlay windows, which [`keep_on_top`] leaves
+/// above them so several overlays don't take turns raising themselves
+const OVERLAY_MARK: &str = "_AIMX_OVERLAY";
+
+fn intern(conn: &RustConnection, name: &str) -> Result<xproto::Atom, String> {
+    conn.intern_atom(false, name.as_bytes())
+        .map_err(|e| e.to_string())?
+        .reply()
+        .map(|r| r.atom)
+        .map_err(|e| e.to_string())
+}
+
+/// Turn an existing window into an overlay: override-redirect so the window
+/// manager leaves it alone, kept above everything by [`keep_on_top`], and an
+/// empty input shape so every click falls through to the window below. The
+/// window must have a 32-bit visual, as a visual can't be changed afterwards.
+pub fn apply(conn: &RustConnection, screen_num: usize, window: Window) -> Result<OverlayReport, String> {
+    let err = |e: x11rb::errors::ConnectionError| e.to_string();
+
+    let depth = conn
+        .get_geometry(window)
+        .map_err(err)?
+        .reply()
+        .map_err(|e| e.to_string())?
+        .depth;
+    if depth != 32 {
+        return Err(format!(
+            "the overlay window has a {depth}-bit visual, but transparency needs a 32-bit ARGB one"
+        ));
+    }
+
+    // override-redirect only takes effect when the window is mapped, so remap it
+    let attrs = conn
+        .get_window_attributes(window)
+        .map_err(err)?
+        .reply()
+        .map_err(|e| e.to_string())?;
+    let was_mapped = attrs.map_state != xproto::MapState::UNMAPPED;
+    if was_mapped {
+        conn.unmap_window(window).map_err(err)?;
+    }
+    conn.change_window_attributes(window, &ChangeWindowAttributesAux::new().override_redirect(1))
+        .map_err(err)?;
+
+    // Ask for "above" through EWMH too, for window managers that still track it
+    let net_wm_state = intern(conn, "_NET_WM_STATE")?;
+    let above = intern(conn, "_NET_WM_STATE_ABOVE")?;
+    conn.change_property32(PropMode::REPLACE, window, net_wm_state, AtomEnum::ATOM, &[above])
+        .map_err(err)?;
+    let mark = intern(conn, OVERLAY_MARK)?;
+    conn.change_property32(PropMode::REPLACE, window, mark, AtomEnum::CARDINAL, &[1])
+        .map_err(err)?;
+
+    // Empty input region: the window never receives pointer events
+    shape::rectangles(conn, SO::SET, SK::INPUT, ClipOrdering::UNSORTED, window, 0, 0, &[])
+        .map_err(err)?;
+
+    if was_mapped {
+        conn.map_window(window).map_err(err)?;
+    }
+    conn.configure_window(window, &ConfigureWindowAux::new().stack_mode(StackMode::ABOVE))
+        .map_err(err)?;
+    conn.sync().map_err(|e| e.to_string())?;
+    keep_on_top(window)?;
+
+    let cm_selection = intern(conn, &format!("_NET_WM_CM_S{screen_num}"))?;
+    let compositor = conn
+        .get_selection_owner(cm_selection)
+        .map_err(err)?
+        .reply()
+        .map_err(|e| e.to_string())?
+        .owner
+        != x11rb::NONE;
+
+    Ok(OverlayReport { compositor })
+}
+
+/// Raise `window` again whenever another window is mapped or restacked over
+/// it, or covers it, watching from a background thread with its own
+/// connection. The thread ends with the window.
+fn keep_on_top(window: Window) -> Result<(), String> {
+    let (conn, screen_num) = x11rb::connect(None).map_err(|e| e.to_string())?;
+    let err = |e: x11rb::errors::ConnectionError| e.to_string();
+    let root = conn.setup().roots[screen_num].root;
+    let mark = intern(&conn, OVERLAY_MARK)?;
+    // Event masks are per client, so neither the window manager nor eframe loses events
+    let on = |mask| ChangeWindowAttributesAux::new().event_mask(mask);
+    conn.change_window_attributes(root, &on(xproto::EventMask::SUBSTRUCTURE_NOTIFY))
+        .map_err(err)?;
+    conn.change_window_attributes(window, &on(xproto::EventMask::VISIBILITY_CHANGE))
+        .map_err(err)?;
+    conn.sync().map_err(|e| e.to_string())?;
+
+    std::thread::spawn(move || {
+        loop {
+            let mut restack = false;
+            let mut event = conn.wait_for_event().ok();
+            // Handle a burst of events with one check
+            while let Some(e) = event {
+                match e {
+                    Event::DestroyNotify(e) if e.window == window => return,
+                    Event::MapNotify(_) | Event::ConfigureNotify(_) | Event::VisibilityNotify(_) => restack = true,
+                    _ => {}
+                }
+                event = conn.poll_for_event().ok().flatten();
+            }
+            match covered(&conn, root, window, mark) {
+                Ok(true) if restack => {
+                    log::debug!("another window covers overlay {window:#x}, raising it");
+                    let aux = ConfigureWindowAux::new().stack_mode(StackMode::ABOVE);
+                    if conn.configure_window(window, &aux).and_then(|_| conn.flush()).is_err() {
+                        return;
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    log::debug!("stopped keeping overlay {window:#x} on top: {e}");
+                    return;
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Whether a mapped window other than an aimx overlay is stacked above `window`.
+fn covered(conn: &RustConnection, root: Window, window: Window, mark: xproto::Atom) -> Result<bool, String> {
+    let tree = conn
+        .query_tree(root)
+        .map_err(|e| e.to_string())?
+        .reply()
+        .map_err(|e| e.to_string())?;
+    let Some(position) = tree.children.iter().position(|&w| w == window) else {
+        return Err("the window is gone".into());
+    };
+    for &other in &tree.children[position + 1..] {
+        // Windows may disappear meanwhile; those don't cover anything
+        let viewable = conn
+            .get_window_attributes(other)
+            .ok()
+            .and_then(|c| c.reply().ok())
+            .is_some_and(|a| a.map_state == xproto::MapState::VIEWABLE);
+        let marked = conn
+            .get_property(false, other, mark, AtomEnum::ANY, 0, 0)
+            .ok()
+            .and_then(|c| c.reply().ok())
+            .is_some_and(|p| p.type_ != x11rb::NONE);
+        if viewable && !marked {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Apply the overlay style to a window created by another connection (eframe's).
+fn style_overlay(window: Window) -> Result<(), String> {
+    let (conn, screen_num) = x11rb::connect(None).map_err(|e| format!("cannot connect to X server: {e}"))?;
+    let report = apply(&conn, screen_num, window).map_err(|e| format!("failed to apply X11 overlay style: {e}"))?;
+    log_report(window, &report);
+    Ok(())
+}
+
+/// Give the overlay window pointer input and keyboard focus, or take them away again.
+fn set_interactive(window: Window, interactive: bool) -> Result<(), String> {
+    let (conn, _) = x11rb::connect(None).map_err(|e| e.to_string())?;
+    let err = |e: x11rb::errors::ConnectionError| e.to_string();
+    if interactive {
+        // No input shape at all means the default: the whole window
+        shape::mask(&conn, SO::SET, SK::INPUT, window, 0, 0, x11rb::NONE).map_err(err)?;
+        conn.set_input_focus(xproto::InputFocus::POINTER_ROOT, window, x11rb::CURRENT_TIME)
+            .map_err(err)?;
+    } else {
+        shape::rectangles(&conn, SO::SET, SK::INPUT, ClipOrdering::UNSORTED, window, 0, 0, &[])
+            .map_err(err)?;
+    }
+    conn.sync().map_err(|e| e.to_string())
+}
+
+/// Copy part of the root window. Parts outside the screen come out black.
+fn capture(x: i32, y: i32, width: u32, height: u32) -> Result<egui::ColorImage, String> {
+    let (conn, screen_num) = x11rb::connect(None).map_err(|e| e.to_string())?;
+    let screen = &conn.setup().roots[screen_num];
+    let mut image = egui::ColorImage::filled([width as usize, height as usize], egui::Color32::BLACK);
+
+    // GetImage fails unless the rectangle lies within the root window
+    let (x0, y0) = (x.max(0), y.max(0));
+    let x1 = (x + width as i32).min(screen.width_in_pixels as i32);
+    let y1 = (y + height as i32).min(screen.height_in_pixels as i32);
+    if x1 <= x0 || y1 <= y0 {
+        return Ok(image);
+    }
+    let reply = conn
+        .get_image(
+            xproto::ImageFormat::Z_PIXMAP,
+            screen.root,
+            x0 as i16,
+            y0 as i16,
+            (x1 - x0) as u16,
+            (y1 - y0) as u16,
+            !0,
+        )
+        .map_err(|e| e.to_string())?
+        .reply()
+        .map_err(|e| format!("cannot read the screen: {e}"))?;
+    let setup = conn.setup();
+    let bits = setup
+        .pixmap_formats
+        .iter()
+        .find(|f| f.depth == reply.depth)
+        .map_or(0, |f| f.bits_per_pixel);
+    if bits != 32 {
+        return Err(format!("unsupported {bits}-bit screen format"));
+    }
+    let lsb_first = setup.image_byte_order == xproto::ImageOrder::LSB_FIRST;
+    let copied = (x1 - x0) as usize;
+    for (i, px) in reply.data.chunks_exact(4).enumerate() {
+        let (row, col) = (i / copied, i % copied);
+        let [r, g, b] = if lsb_first { [px[2], px[1], px[0]] } else { [px[1], px[2], px[3]] };
+        let (dx, dy) = (col + (x0 - x) as usize, row + (y0 - y) as usize);
+        image.pixels[dy * width as usize + dx] = egui::Color32::from_rgb(r, g, b);
+    }
+    Ok(image)
+}
+
+fn log_report(window: Window, report: &OverlayReport) {
+    info!("applied X11 overlay style to window {window:#x}: {report:?}");
+    if !report.compositor {
+        warn!("no compositing manager running, overlay transparency will not work");
+    }
+}
+
+/// An override-redirect window with a 32-bit visual that software-rendered
+/// frames are copied into with `PutImage`.
+pub struct X11Surface {
+    conn: RustConnection,
+    window: Window,
+    gc: xproto::Gcontext,
+    mapped: bool,
+}
+
+impl X11Surface {
+    pub fn create() -> Result<Self, String> {
+        let (conn, screen_num) = x11rb::connect(None).map_err(|e| e.to_string())?;
+        let err = |e: x11rb::errors::ReplyOrIdError| e.to_string();
+        let screen = &conn.setup().roots[screen_num];
+        let visual = screen
+            .allowed_depths
+            .iter()
+            .filter(|d| d.depth == 32)
+            .flat_map(|d| d.visuals.iter())
+            .find(|v| v.class == xproto::VisualClass::TRUE_COLOR)
+            .ok_or("the X server has no 32-bit visual for a transparent window")?
+            .visual_id;
+        let root = screen.root;
+
+        let colormap = conn.generate_id().map_err(err)?;
+        conn.create_colormap(xproto::ColormapAlloc::NONE, colormap, root, visual)
+            .map_err(|e| e.to_string())?;
+        let window = conn.generate_id().map_err(err)?;
+        let aux = xproto::CreateWindowAux::new()
+            .colormap(colormap)
+            .border_pixel(0)
+            .background_pixel(0);
+        conn.create_window(32, window, root, 0, 0, 1, 1, 0, xproto::WindowClass::INPUT_OUTPUT, visual, &aux)
+            .map_err(|e| e.to_string())?;
+        let gc = conn.generate_id().map_err(err)?;
+        conn.create_gc(gc, window, &xproto::CreateGCAux::new())
+            .map_err(|e| e.to_string())?;
+        log_report(window, &apply(&conn, screen_num, window)?);
+        Ok(Self {
+            conn,
+            window,
+            gc,
+            mapped: false,
+        })
+    }
+}
+
+impl super::surface::Surface for X11Surface {
+    fn present(&mut self, x: i32, y: i32, image: &egui::ColorImage) -> Result<(), String> {
+        use x11rb::connection::RequestConnection;
+
+        let err = |e: x11rb::errors::ConnectionError| e.to_string();
+        let [width, height] = image.size.map(|v| v as u16);
+        let aux = ConfigureWindowAux::new()
+            .x(x)
+            .y(y)
+            .width(u32::from(width))
+            .height(u32::from(height))
+            .stack_mode(StackMode::ABOVE);
+        self.conn.configure_window(self.window, &aux).map_err(err)?;
+
+        // Premultiplied ARGB in the server's byte order
+        let lsb_first = self.conn.setup().image_byte_order == xproto::ImageOrder::LSB_FIRST;
+        let data: Vec<u8> = image
+            .pixels
+            .iter()
+            .flat_map(|c| {
+                if lsb_first {
+                    [c.b(), c.g(), c.r(), c.a()]
+                } else {
+                    [c.a(), c.r(), c.g(), c.b()]
+                }
+            })
+            .collect();
+        // Large crosshairs may not fit one request
+        let row = usize::from(width) * 4;
+        let rows = ((self.conn.maximum_request_bytes() - 64) / row.max(1)).max(1);
+        for (i, chunk) in data.chunks(row * rows).enumerate() {
+            let top = (i * rows) as i16;
+            let count = (chunk.len() / row.max(1)) as u16;
+            self.conn
+                .put_image(xproto::ImageFormat::Z_PIXMAP, self.window, self.gc, width, count, 0, top, 0, 32, chunk)
+                .map_err(err)?;
+        }
+        if !self.mapped {
+            self.conn.map_window(self.window).map_err(err)?;
+            self.mapped = true;
+        }
+        self.conn.flush().map_err(err)
+    }
+
+    fn hide(&mut self) {
+        if std::mem::take(&mut self.mapped) {
+            let _ = self.conn.unmap_window(self.window);
+            let _ = self.conn.flush();
+        }
+    }
+
+    fn dispatch(&mut self) {
+        // Nothing is selected, but errors for unchecked requests queue up here
+        while let Ok(Some(event)) = self.conn.poll_for_event() {
+            log::debug!("software overlay: {event:?}");
+        }
+    }
+}
+
+/// Scale factor from the `Xft.dpi` entry of the root window's resource
+/// database, which desktop environments set from their scaling setting.
+fn xft_scale(resources: &str) -> Option<f32> {
+    resources.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        if key.trim() != "Xft.dpi" {
+            return None;
+        }
+        let dpi: f32 = value.trim().parse().ok()?;
+        (dpi > 0.0).then_some(dpi / 96.0)
+    })
+}
+
+/// List monitors through RandR 1.5. X11 has one global scale, read from `Xft.dpi`.
+pub fn monitors() -> Result<Vec<Monitor>, String> {
+    let (conn, screen_num) = x11rb::connect(None).map_err(|e| e.to_string())?;
+    let err = |e: x11rb::errors::ConnectionError| e.to_string();
+    let root = conn.setup().roots[screen_num].root;
+
+    let resources = conn
+        .get_property(false, root, AtomEnum::RESOURCE_MANAGER, AtomEnum::STRING, 0, u32::MAX)
+        .map_err(err)?
+        .reply()
+        .map_err(|e| e.to_string())?;
+    let scale = xft_scale(&String::from_utf8_lossy(&resources.value)).unwrap_or(1.0);
+
+    // RandR only answers GetMonitors once the client has announced version 1.5
+    randr::query_version(&conn, 1, 5)
+        .map_err(err)?
+        .reply()
+        .map_err(|e| e.to_string())?;
+    let reply = randr::get_monitors(&conn, root, true)
+        .map_err(err)?
+        .reply()
+        .map_err(|e| e.to_string())?;
+    reply
+        .monitors
+        .iter()
+        .map(|m| {
+            let name = conn
+                .get_atom_name(m.name)
+                .map_err(err)?
+                .reply()
+                .map_err(|e| e.to_string())?
+                .name;
+            Ok(Monitor {
+                name: String::from_utf8_lossy(&name).into_owned(),
+                x: m.x.into(),
+                y: m.y.into(),
+                width: m.width.into(),
+                height: m.height.into(),
+                scale,
+                primary: m.primary,
+            })
+        })
+        .collect()
+}
+
+/// Call `on_change` from a background thread whenever RandR reports a screen,
+/// CRTC or output change, or the resource database (and with it `Xft.dpi`)
+/// is rewritten.
+pub fn watch_displays(on_change: impl Fn() + Send + 'static) -> Result<(), String> {
+    let (conn, screen_num) = x11rb::connect(None).map_err(|e| e.to_string())?;
+    let err = |e: x11rb::errors::ConnectionError| e.to_string();
+    let root = conn.setup().roots[screen_num].root;
+
+    randr::query_version(&conn, 1, 5)
+        .map_err(err)?
+        .reply()
+        .map_err(|e| e.to_string())?;
+    let mask = randr::NotifyMask::SCREEN_CHANGE | randr::NotifyMask::CRTC_CHANGE | randr::NotifyMask::OUTPUT_CHANGE;
+    randr::select_input(&conn, root, mask).map_err(err)?;
+    // Other clients may also listen on the root window; event masks are per client
+    conn.change_window_attributes(root, &ChangeWindowAttributesAux::new().event_mask(xproto::EventMask::PROPERTY_CHANGE))
+        .map_err(err)?;
+    conn.flush().map_err(err)?;
+
+    std::thread::spawn(move || {
+        loop {
+            let relevant = |event: &Event| match event {
+                Event::RandrScreenChangeNotify(_) | Event::RandrNotify(_) => true,
+                Event::PropertyNotify(e) => e.atom == u32::from(AtomEnum::RESOURCE_MANAGER),
+                _ => false,
+            };
+            let mut changed = match conn.wait_for_event() {
+                Ok(event) => relevant(&event),
+                Err(e) => {
+                    warn!("display watcher lost the X connection: {e}");
+                    return;
+                }
+            };
+            // One mode switch produces a burst of events; report it once
+            while let Ok(Some(event)) = conn.poll_for_event() {
+                changed |= relevant(&event);
+            }
+            if changed {
+                on_change();
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Finds windows through the EWMH client list kept by the window manager.
+pub struct X11Windows {
+    conn: RustConnection,
+    root: Window,
+    client_list: xproto::Atom,
+    net_wm_name: xproto::Atom,
+    net_wm_pid: xproto::Atom,
+    net_wm_state: xproto::Atom,
+    hidden: xproto::Atom,
+    fullscreen: xproto::Atom,
+    active_window: xproto::Atom,
+    utf8_string: xproto::Atom,
+    /// Window found last time, checked first when following it
+    last: Option<Window>,
+    /// Focused window and its process name, which doesn't change while it lives
+    focused: Option<(Window, String)>,
+}
+
+impl X11Windows {
+    pub fn connect() -> Result<Self, String> {
+        let (conn, screen_num) = x11rb::connect(None).map_err(|e| e.to_string())?;
+        let root = conn.setup().roots[screen_num].root;
+        Ok(Self {
+            client_list: intern(&conn, "_NET_CLIENT_LIST")?,
+            net_wm_name: intern(&conn, "_NET_WM_NAME")?,
+            net_wm_pid: intern(&conn, "_NET_WM_PID")?,
+            net_wm_state: intern(&conn, "_NET_WM_STATE")?,
+            hidden: intern(&conn, "_NET_WM_STATE_HIDDEN")?,
+            fullscreen: intern(&conn, "_NET_WM_STATE_FULLSCREEN")?,
+            active_window: intern(&conn, "_NET_ACTIVE_WINDOW")?,
+            utf8_string: intern(&conn, "UTF8_STRING")?,
+            conn,
+            root,
+            last: None,
+            focused: None,
+        })
+    }
+
+    fn matches(&self, target: &WindowMatch, window: Window) -> bool {
+        match target {
+            WindowMatch::Title(_) => target.matches("", &self.title(window)),
+            WindowMatch::Process(_) => target.matches(&self.process_name(window), ""),
+        }
+    }
+
+    fn property(&self, window: Window, property: xproto::Atom, kind: impl Into<xproto::Atom>) -> Option<GetPropertyReply> {
+        self.conn
+            .get_property(false, window, property, kind, 0, u32::MAX / 4)
+            .ok()?
+            .reply()
+            .ok()
+    }
+
+    fn title(&self, window: Window) -> String {
+        let value = self
+            .property(window, self.net_wm_name, self.utf8_string)
+            .filter(|r| !r.value.is_empty())
+            .or_else(|| self.property(window, AtomEnum::WM_NAME.into(), AtomEnum::STRING))
+            .map(|r| r.value)
+            .unwrap_or_default();
+        String::from_utf8_lossy(&value).into_owned()
+    }
+
+    /// Name of the process that owns `window`, from `_NET_WM_PID` and `/proc`.
+    fn process_name(&self, window: Window) -> String {
+        let pid = self
+            .property(window, self.net_wm_pid, AtomEnum::CARDINAL)
+            .and_then(|r| r.value32()?.next());
+        pid.and_then(|pid| std::fs::read_link(format!("/proc/{pid}/exe")).ok())
+            .and_then(|exe| Some(exe.file_name()?.to_string_lossy().into_owned()))
+            .unwrap_or_default()
+    }
+
+    /// Whether `window`'s `_NET_WM_STATE` includes `state`.
+    fn has_state(&self, window: Window, state: xproto::Atom) -> bool {
+        self.property(window, self.net_wm_state, AtomEnum::ATOM)
+            .and_then(|r| r.value32().map(|mut atoms| atoms.any(|a| a == state)))
+            .unwrap_or(false)
+    }
+
+    fn state(&self, window: Window) -> Option<WindowState> {
+        if self.has_state(window, self.hidden) {
+            return Some(WindowState::Minimized);
+        }
+        let geometry = self.conn.get_geometry(window).ok()?.reply().ok()?;
+        let origin = self
+            .conn
+            .translate_coordinates(window, self.root, 0, 0)
+            .ok()?
+            .reply()
+            .ok()?;
+        Some(WindowState::Visible(ClientArea {
+            x: origin.dst_x.into(),
+            y: origin.dst_y.into(),
+            width: geometry.width.into(),
+            height: geometry.height.into(),
+        }))
+    }
+}
+
+impl WindowFinder for X11Windows {
+    fn find(&mut self, target: &WindowMatch) -> Option<WindowState> {
+        let reply = self.property(self.root, self.client_list, AtomEnum::WINDOW)?;
+        let clients: Vec<Window> = reply.value32()?.collect();
+        let cached = self.last.filter(|w| clients.contains(w) && self.matches(target, *w));
+        let window = match cached {
+            Some(w) => w,
+            None => {
+                let found = clients.into_iter().find(|&w| self.matches(target, w));
+                self.last = found;
+                found?
+            }
+        };
+        self.state(window)
+    }
+
+    fn foreground(&mut self) -> Foreground {
+        let Some(reply) = self.property(self.root, self.active_window, AtomEnum::WINDOW) else {
+            return Foreground::Unknown;
+        };
+        // Window managers without EWMH focus tracking never set the property
+        if reply.type_ == u32::from(AtomEnum::NONE) {
+            return Foreground::Unknown;
+        }
+        let window = reply.value32().and_then(|mut w| w.next()).unwrap_or(0);
+        if window == 0 {
+            return Foreground::Nothing;
+        }
+        let process = match &self.focused {
+            Some((w, name)) if *w == window => name.clone(),
+            _ => {
+                let name = self.process_name(window);
+                self.focused = Some((window, name.clone()));
+                name
+            }
+        };
+        Foreground::Window {
+            process,
+            fullscreen: self.has_state(window, self.fullscreen),
+        }
+    }
+}
+
+/// A grabbed key: keycode, modifier mask and what it does.
+type Grab = (xproto::Keycode, u16, Action);
+
+/// Modifiers that count for a hotkey; Caps Lock and Num Lock (usually Mod2) are ignored.
+const HOTKEY_MODIFIERS: u16 = 1 | 4 | 8 | 64;
+
+/// Global hotkeys through passive grabs on the root window.
+pub struct X11Hotkeys {
+    conn: Arc<RustConnection>,
+    root: Window,
+    grabs: Arc<Mutex<Vec<Grab>>>,
+}
+
+impl X11Hotkeys {
+    /// Connect and start a thread that calls `on_press` for grabbed keys.
+    pub fn connect(on_press: OnPress) -> Result<Self, String> {
+        let (conn, screen_num) = x11rb::connect(None).map_err(|e| e.to_string())?;
+        let conn = Arc::new(conn);
+        let root = conn.setup().roots[screen_num].root;
+        let grabs = Arc::new(Mutex::new(Vec::<Grab>::new()));
+
+        let (events, grabbed) = (conn.clone(), grabs.clone());
+        std::thread::spawn(move || {
+            // Auto-repeat arrives as a release and a press with the same timestamp
+            let mut released = None;
+            loop {
+                match events.wait_for_event() {
+                    Ok(Event::KeyRelease(e)) => released = Some((e.detail, e.time)),
+                    Ok(Event::KeyPress(e)) => {
+                        let repeat = released == Some((e.detail, e.time));
+                        let mods = u16::from(e.state) & HOTKEY_MODIFIERS;
+                        let action = grabbed
+                            .lock()
+                            .unwrap()
+                            .iter()
+                            .find(|(code, m, _)| *code == e.detail && *m == mods)
+                            .map(|(_, _, action)| *action);
+                        // Holding a nudge key keeps nudging; everything else fires once
+                        if let Some(action) = action.filter(|a| !repeat || a.nudge().is_some()) {
+                            on_press(action);
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        warn!("hotkey listener lost the X connection: {e}");
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(Self { conn, root, grabs })
+    }
+
+    /// Grab `keycode` with `mods` under every combination of Caps Lock and Num Lock.
+    fn grab(&self, keycode: xproto::Keycode, mods: u16) -> Result<(), String> {
+        let lock = u16::from(xproto::ModMask::LOCK);
+        let num_lock = u16::from(xproto::ModMask::M2);
+        for extra in [0, lock, num_lock, lock | num_lock] {
+            self.conn
+                .grab_key(
+                    false,
+                    self.root,
+                    xproto::ModMask::from(mods | extra),
+                    keycode,
+                    xproto::GrabMode::ASYNC,
+                    xproto::GrabMode::ASYNC,
+                )
+                .map_err(|e| e.to_string())?
+                .check()
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn ungrab(&self, keycode: xproto::Keycode) {
+        let _ = self.conn.ungrab_key(keycode, self.root, xproto::ModMask::ANY);
+    }
+}
+
+impl HotkeyGrabber for X11Hotkeys {
+    fn register(&mut self, bindings: &Bindings) -> Vec<(Action, String)> {
+        let mut grabs = self.grabs.lock().unwrap();
+        for (keycode, _, _) in grabs.drain(..) {
+            self.ungrab(keycode);
+        }
+        let mut errors = Vec::new();
+        for (action, hotkey) in bindings {
+            let Some(&keycode) = keycodes(&self.conn, keysym(hotkey.key)).first() else {
+                errors.push((*action, format!("{hotkey}: no {} key in the current keyboard layout", hotkey.key)));
+                continue;
+            };
+            let mods = modifier_mask(hotkey);
+            match self.grab(keycode, mods) {
+                Ok(()) => grabs.push((keycode, mods, *action)),
+                Err(e) => {
+                    log::debug!("grabbing {hotkey} failed: {e}");
+                    // Drop the lock variants that did succeed, keeping other grabs on this key
+                    self.ungrab(keycode);
+                    for (code, m, _) in grabs.iter().filter(|(code, _, _)| *code == keycode) {
+                        let _ = self.grab(*code, *m);
+                    }
+                    errors.push((*action, format!("{hotkey} is already taken by another application")));
+                }
+            }
+        }
+        let _ = self.conn.flush();
+        errors
+    }
+}
+
+/// Every keycode that produces `keysym` in the current keyboard layout.
+fn keycodes(conn: &RustConnection, keysym: u32) -> Vec<xproto::Keycode> {
+    let setup = conn.setup();
+    let (min, max) = (setup.min_keycode, setup.max_keycode);
+    let Some(mapping) = conn
+        .get_keyboard_mapping(min, max - min + 1)
+        .ok()
+        .and_then(|cookie| cookie.reply().ok())
+    else {
+        return Vec::new();
+    };
+    let per = usize::from(mapping.keysyms_per_keycode).max(1);
+    mapping
+        .keysyms
+        .chunks(per)
+        .enumerate()
+        .filter(|(_, syms)| syms.contains(&keysym))
+        .map(|(i, _)| min + i as u8)
+        .collect()
+}
+
+/// Held keys and buttons from the server's global keyboard and pointer state.
+pub struct X11Input {
+    conn: RustConnection,
+    root: Window,
+    /// Keycodes per trigger, looked up once
+    keycodes: HashMap<Trigger, Vec<xproto::Keycode>>,
+}
+
+impl X11Input {
+    pub fn connect() -> Result<Self, String> {
+        let (conn, screen_num) = x11rb::connect(None).map_err(|e| e.to_string())?;
+        let root = conn.setup().roots[screen_num].root;
+        Ok(Self {
+            conn,
+            root,
+            keycodes: HashMap::new(),
+        })
+    }
+}
+
+impl InputPoller for X11Input {
+    fn held(&mut self, trigger: Trigger) -> Option<bool> {
+        let button = match trigger {
+            Trigger::MouseLeft => Some(xproto::KeyButMask::BUTTON1),
+            Trigger::MouseMiddle => Some(xproto::KeyButMask::BUTTON2),
+            Trigger::MouseRight => Some(xproto::KeyButMask::BUTTON3),
+            // The core protocol only reports side buttons as events, never as state
+            Trigger::Mouse4 | Trigger::Mouse5 => return None,
+            _ => None,
+        };
+        if let Some(button) = button {
+            let pointer = self.conn.query_pointer(self.root).ok()?.reply().ok()?;
+            return Some(u16::from(pointer.mask) & u16::from(button) != 0);
+        }
+
+        let conn = &self.conn;
+        let codes = self.keycodes.entry(trigger).or_insert_with(|| {
+            let syms: &[u32] = match trigger {
+                Trigger::Shift => &[0xffe1, 0xffe2],
+                Trigger::Ctrl => &[0xffe3, 0xffe4],
+                Trigger::Alt => &[0xffe9, 0xffea, 0xfe03],
+                Trigger::Key(key) => &[keysym(key)],
+                _ => &[],
+            };
+            syms.iter().flat_map(|&sym| keycodes(conn, sym)).collect()
+        });
+        if codes.is_empty() {
+            return None;
+        }
+        let keymap = conn.query_keymap().ok()?.reply().ok()?.keys;
+        Some(
+            codes
+                .iter()
+                .any(|&code| keymap[usize::from(code) / 8] & (1 << (code % 8)) != 0),
+        )
+    }
+}
+
+fn modifier_mask(hotkey: &Hotkey) -> u16 {
+    let m = hotkey.modifiers;
+    [(m.shift, 1), (m.ctrl, 4), (m.alt, 8), (m.logo, 64)]
+        .into_iter()
+        .filter(|(on, _)| *on)
+        .fold(0, |mask, (_, bit)| mask | bit)
+}
+
+/// The X keysym for `key`, see `X11/keysymdef.h`.
+fn keysym(key: Key) -> u32 {
+    match key {
+        Key::Letter(c) => u32::from(c.to_ascii_lowercase()),
+        Key::Digit(d) => 0x30 + u32::from(d),
+        Key::F(n) => 0xffbd + u32::from(n),
+        Key::Left => 0xff51,
+        Key::Up => 0xff52,
+        Key::Right => 0xff53,
+        Key::Down => 0xff54,
+        Key::PageUp => 0xff55,
+        Key::PageDown => 0xff56,
+        Key::End => 0xff57,
+        Key::Home => 0xff50,
+        Key::Insert => 0xff63,
+        Key::Delete => 0xffff,
+        Key::Space => 0x20,
+    }
+}
+
+/// Run these under Xvfb, e.g. `xvfb-run -s "-screen 0 1280x720x24" cargo test`.
+/// They are skipped when no X display is available.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{CreateWindowAux, EventMask, WindowClass};
+
+    fn connect() -> Option<(RustConnection, usize)> {
+        if std::env::var_os("DISPLAY").is_none() {
+            eprintln!("DISPLAY not set, skipping X11 test");
+            return None;
+        }
+        x11rb::connect(None).ok()
+    }
+
+    /// Create a mapped window with a 32-bit visual if the server has one.
+    fn create_window(conn: &RustConnection, screen_num: usize) -> Window {
+        let screen = &conn.setup().roots[screen_num];
+        let argb = screen
+            .allowed_depths
+            .iter()
+            .filter(|d| d.depth == 32)
+            .flat_map(|d| d.visuals.iter())
+            .find(|v| v.class == xproto::VisualClass::TRUE_COLOR);
+
+        let window = conn.generate_id().unwrap();
+        let mut aux = CreateWindowAux::new().event_mask(EventMask::STRUCTURE_NOTIFY);
+        let (depth, visual) = match argb {
+            Some(v) => {
+                let colormap = conn.generate_id().unwrap();
+                conn.create_colormap(xproto::ColormapAlloc::NONE, colormap, screen.root, v.visual_id)
+                    .unwrap();
+                aux = aux.colormap(colormap).border_pixel(0).background_pixel(0);
+                (32, v.visual_id)
+            }
+            None => (screen.root_depth, screen.root_visual),
+        };
+        conn.create_window(
+            depth,
+            window,
+            screen.root,
+            10,
+            10,
+            64,
+            64,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            visual,
+            &aux,
+        )
+        .unwrap();
+        conn.map_window(window).unwrap();
+        conn.sync().unwrap();
+        window
+    }
+
+    #[test]
+    fn calibration_lets_input_through_and_back() {
+        let Some((conn, screen_num)) = connect() else {
+            return;
+        };
+        let window = create_window(&conn, screen_num);
+        apply(&conn, screen_num, window).unwrap();
+        let input = || {
+            shape::get_rectangles(&conn, window, SK::INPUT)
+                .unwrap()
+                .reply()
+                .unwrap()
+                .rectangles
+        };
+
+        set_interactive(window, true).unwrap();
+        assert!(!input().is_empty(), "interactive overlays take the pointer");
+        set_interactive(window, false).unwrap();
+        assert!(input().is_empty());
+    }
+
+    #[test]
+    fn captures_the_screen() {
+        if connect().is_none() {
+            return;
+        }
+        let image = capture(-4, -4, 16, 16).unwrap();
+        assert_eq!(image.size, [16, 16]);
+        assert_eq!(image.pixels[0], egui::Color32::BLACK, "off screen");
+        assert!(image.pixels.iter().all(|c| c.a() == 255));
+    }
+
+    #[test]
+    fn overlay_window_is_override_redirect_and_click_through() {
+        let Some((conn, screen_num)) = connect() else {
+            return;
+        };
+        let window = create_window(&conn, screen_num);
+
+        apply(&conn, screen_num, window).unwrap();
+
+        let attrs = conn.get_window_attributes(window).unwrap().reply().unwrap();
+        assert!(attrs.override_redirect);
+        assert_ne!(attrs.map_state, xproto::MapState::UNMAPPED);
+
+        let input = shape::get_rectangles(&conn, window, SK::INPUT)
+            .unwrap()
+            .reply()
+            .unwrap();
+        assert!(input.rectangles.is_empty(), "input shape should be empty");
+
+        let net_wm_state = intern(&conn, "_NET_WM_STATE").unwrap();
+        let above = intern(&conn, "_NET_WM_STATE_ABOVE").unwrap();
+        let state = conn
+            .get_property(false, window, net_wm_state, AtomEnum::ATOM, 0, 16)
+            .unwrap()
+            .reply()
+            .unwrap();
+        assert!(state.value32().unwrap().any(|a| a == above));
+    }
+
+    #[test]
+    fn overlay_needs_an_argb_visual() {
+        let Some((conn, screen_num)) = connect() else {
+            return;
+        };
+        let screen = &conn.setup().roots[screen_num];
+        if screen.root_depth == 32 {
+            return;
+        }
+        let window = conn.generate_id().unwrap();
+        conn.create_window(
+            screen.root_depth,
+            window,
+            screen.root,
+            0,
+            0,
+            8,
+            8,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            screen.root_visual,
+            &CreateWindowAux::new(),
+        )
+        .unwrap();
+        let error = apply(&conn, screen_num, window).unwrap_err();
+        assert!(error.contains("32-bit"), "{error}");
+        let attrs = conn.get_window_attributes(window).unwrap().reply().unwrap();
+        assert!(!attrs.override_redirect, "left alone");
+    }
+
+    #[test]
+    fn overlay_window_stays_on_top() {
+        let Some((conn, screen_num)) = connect() else {
+            return;
+        };
+        let overlay = create_window(&conn, screen_num);
+        apply(&conn, screen_num, overlay).unwrap();
+        // A window mapped afterwards goes on top, then the overlay is raised again
+        let other = create_window(&conn, screen_num);
+
+        let root = conn.setup().roots[screen_num].root;
+        let above = || {
+            let tree = conn.query_tree(root).unwrap().reply().unwrap();
+            let pos = |w| tree.children.iter().position(|&c| c == w).unwrap();
+            pos(overlay) > pos(other)
+        };
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(2);
+        while !above() && std::time::Instant::now() < deadline {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert!(above(), "overlay should be stacked above");
+    }
+
+    #[test]
+    fn finds_client_window_by_title() {
+        let Some((conn, screen_num)) = connect() else {
+            return;
+        };
+        let window = create_window(&conn, screen_num);
+        let root = conn.setup().roots[screen_num].root;
+        let title = format!("aimx-test-{window}");
+        conn.change_property8(PropMode::REPLACE, window, AtomEnum::WM_NAME, AtomEnum::STRING, title.as_bytes())
+            .unwrap();
+        // Xvfb runs no window manager, so publish the client list ourselves
+        let client_list = intern(&conn, "_NET_CLIENT_LIST").unwrap();
+        conn.change_property32(PropMode::APPEND, root, client_list, AtomEnum::WINDOW, &[window])
+            .unwrap();
+        conn.sync().unwrap();
+
+        let mut finder = X11Windows::connect().unwrap();
+        let target = WindowMatch::Title(title.to_uppercase());
+        assert_eq!(
+            finder.find(&target),
+            Some(WindowState::Visible(ClientArea {
+                x: 10,
+                y: 10,
+                width: 64,
+                height: 64
+            }))
+        );
+
+        let net_wm_state = intern(&conn, "_NET_WM_STATE").unwrap();
+        let hidden = intern(&conn, "_NET_WM_STATE_HIDDEN").unwrap();
+        conn.change_property32(PropMode::REPLACE, window, net_wm_state, AtomEnum::ATOM, &[hidden])
+            .unwrap();
+        conn.sync().unwrap();
+        assert_eq!(finder.find(&target), Some(WindowState::Minimized));
+        assert_eq!(finder.find(&WindowMatch::Title("no such window".into())), None);
+    }
+
+    #[test]
+    fn reads_focused_window() {
+        let Some((conn, screen_num)) = connect() else {
+            return;
+        };
+        let window = create_window(&conn, screen_num);
+        let root = conn.setup().roots[screen_num].root;
+        // Stand in for the window manager again
+        let atom = |name| intern(&conn, name).unwrap();
+        conn.change_property32(PropMode::REPLACE, window, atom("_NET_WM_PID"), AtomEnum::CARDINAL, &[std::process::id()])
+            .unwrap();
+        conn.change_property32(
+            PropMode::REPLACE,
+            window,
+            atom("_NET_WM_STATE"),
+            AtomEnum::ATOM,
+            &[atom("_NET_WM_STATE_FULLSCREEN")],
+        )
+        .unwrap();
+        conn.change_property32(PropMode::REPLACE, root, atom("_NET_ACTIVE_WINDOW"), AtomEnum::WINDOW, &[window])
+            .unwrap();
+        conn.sync().unwrap();
+
+        let exe = std::env::current_exe().unwrap();
+        let mut finder = X11Windows::connect().unwrap();
+        assert_eq!(
+            finder.foreground(),
+            Foreground::Window {
+                process: exe.file_name().unwrap().to_string_lossy().into_owned(),
+                fullscreen: true,
+            }
+        );
+
+        conn.change_property32(PropMode::REPLACE, root, atom("_NET_ACTIVE_WINDOW"), AtomEnum::WINDOW, &[0])
+            .unwrap();
+        conn.sync().unwrap();
+        assert_eq!(finder.foreground(), Foreground::Nothing);
+    }
+
+    #[test]
+    fn second_client_cannot_grab_the_same_hotkey() {
+        if connect().is_none() {
+            return;
+        }
+        let bindings: Bindings = [(Action::Toggle, "Ctrl+Alt+Shift+F12".parse().unwrap())].into();
+        let mut first = X11Hotkeys::connect(Box::new(|_| {})).unwrap();
+        let mut second = X11Hotkeys::connect(Box::new(|_| {})).unwrap();
+        assert!(first.register(&bindings).is_empty());
+        let errors = second.register(&bindings);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].1.contains("already taken"), "{}", errors[0].1);
+
+        first.register(&Bindings::new());
+        assert!(second.register(&bindings).is_empty());
+    }
+
+    #[test]
+    fn reads_released_input() {
+        if connect().is_none() {
+            return;
+        }
+        let mut input = X11Input::connect().unwrap();
+        assert_eq!(input.held(Trigger::MouseRight), Some(false));
+        assert_eq!(input.held(Trigger::Key(Key::Letter('Q'))), Some(false));
+        assert_eq!(input.held(Trigger::Shift), Some(false));
+        assert_eq!(input.held(Trigger::Mouse4), None);
+    }
+
+    #[test]
+    fn reads_scale_from_xft_dpi() {
+        assert_eq!(xft_scale("Xft.antialias:\t1\nXft.dpi:\t144\n"), Some(1.5));
+        assert_eq!(xft_scale("Xft.dpi: 120"), Some(1.25));
+        assert_eq!(xft_scale("Xcursor.size:\t24\n"), None);
+    }
+
+    #[test]
+    fn lists_randr_monitors() {
+        if connect().is_none() {
+            return;
+        }
+        let list = monitors().unwrap();
+        assert!(!list.is_empty(), "Xvfb exposes at least one monitor");
+        assert!(list.iter().all(|m| m.width > 0 && m.height > 0));
+    }
+}
@@ -0,0 +1,255 @@
+use eframe::egui::{self, Color32, ColorImage, TextureId, epaint};
+use std::collections::HashMap;
+
+use crate::config::Config;
+
+/// CPU renderer for egui painting. Runs an offscreen egui context and
+/// rasterizes the tessellated meshes the same way egui's GL backend does:
+/// premultiplied sRGB vertex colors times the texture, blended in gamma space.
+/// Used where there is no GL context, such as the Wayland layer-shell overlay.
+pub struct Renderer {
+    ctx: egui::Context,
+    textures: HashMap<TextureId, ColorImage>,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Renderer {
+    pub fn new() -> Self {
+        Self {
+            ctx: egui::Context::default(),
+            textures: HashMap::new(),
+        }
+    }
+
+    /// Paint onto a `size` pixel canvas filled with `background`. `paint` works in
+    /// points; `pixels_per_point` maps them to pixels as on a real display.
+    pub fn render(
+        &mut self,
+        size: [usize; 2],
+        pixels_per_point: f32,
+        background: Color32,
+        paint: impl FnOnce(&egui::Painter),
+    ) -> ColorImage {
+        let screen = egui::Rect::from_min_size(
+            egui::Pos2::ZERO,
+            egui::vec2(size[0] as f32, size[1] as f32) / pixels_per_point,
+        );
+        let mut input = egui::RawInput {
+            screen_rect: Some(screen),
+            ..Default::default()
+        };
+        input
+            .viewports
+            .entry(egui::ViewportId::ROOT)
+            .or_default()
+            .native_pixels_per_point = Some(pixels_per_point);
+
+        let mut paint = Some(paint);
+        let output = self.ctx.run(input, |ctx| {
+            if let Some(paint) = paint.take() {
+                paint(&ctx.layer_painter(egui::LayerId::background()));
+            }
+        });
+
+        for (id, delta) in &output.textures_delta.set {
+            self.apply_delta(*id, delta);
+        }
+        let primitives = self.ctx.tessellate(output.shapes, output.pixels_per_point);
+
+        let mut image = ColorImage::filled(size, background);
+        for primitive in &primitives {
+            if let epaint::Primitive::Mesh(mesh) = &primitive.primitive {
+                let clip = primitive.clip_rect * output.pixels_per_point;
+                self.draw_mesh(&mut image, mesh, clip, output.pixels_per_point);
+            }
+        }
+
+        for id in &output.textures_delta.free {
+            self.textures.remove(id);
+        }
+        image
+    }
+
+    /// Render one crosshair centered on a canvas just large enough to hold it.
+    /// The crosshair covers the same pixels at any `pixels_per_point`.
+    pub fn render_crosshair(&mut self, config: &Config, pixels_per_point: f32) -> ColorImage {
+        let side = config.window_size_px() as usize;
+        self.render([side, side], pixels_per_point, Color32::TRANSPARENT, |painter| {
+            crate::crosshair::draw(painter, painter.clip_rect().center(), config);
+        })
+    }
+
+    fn apply_delta(&mut self, id: TextureId, delta: &epaint::ImageDelta) {
+        let epaint::ImageData::Color(patch) = &delta.image;
+        match delta.pos {
+            None => {
+                self.textures.insert(id, (**patch).clone());
+            }
+            Some([x0, y0]) => {
+                let Some(texture) = self.textures.get_mut(&id) else {
+                    return;
+                };
+                let width = texture.size[0];
+                for y in 0..patch.size[1] {
+                    let src = &patch.pixels[y * patch.size[0]..(y + 1) * patch.size[0]];
+                    let start = (y0 + y) * width + x0;
+                    texture.pixels[start..start + src.len()].copy_from_slice(src);
+                }
+            }
+        }
+    }
+
+    fn draw_mesh(&self, image: &mut ColorImage, mesh: &epaint::Mesh, clip: egui::Rect, pixels_per_point: f32) {
+        let texture = self.textures.get(&mesh.texture_id);
+        for tri in mesh.indices.chunks_exact(3) {
+            let v = [
+                &mesh.vertices[tri[0] as usize],
+                &mesh.vertices[tri[1] as usize],
+                &mesh.vertices[tri[2] as usize],
+            ];
+            draw_triangle(image, v, texture, clip, pixels_per_point);
+        }
+    }
+}
+
+type Rgba = [f32; 4];
+
+fn to_rgba(c: Color32) -> Rgba {
+    [c.r(), c.g(), c.b(), c.a()].map(|v| v as f32 / 255.0)
+}
+
+fn from_rgba(c: Rgba) -> Color32 {
+    let [r, g, b, a] = c.map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8);
+    Color32::from_rgba_premultiplied(r, g, b, a)
+}
+
+/// Bilinear texture lookup with clamped edges, like GL_LINEAR + CLAMP_TO_EDGE.
+fn sample(texture: &ColorImage, uv: egui::Pos2) -> Rgba {
+    let [w, h] = texture.size;
+    let x = (uv.x * w as f32 - 0.5).clamp(0.0, (w - 1) as f32);
+    let y = (uv.y * h as f32 - 0.5).clamp(0.0, (h - 1) as f32);
+    let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+    let (x1, y1) = ((x0 + 1).min(w - 1), (y0 + 1).min(h - 1));
+    let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+    let px = |x: usize, y: usize| to_rgba(texture.pixels[y * w + x]);
+    let (a, b, c, d) = (px(x0, y0), px(x1, y0), px(x0, y1), px(x1, y1));
+    std::array::from_fn(|i| {
+        let top = a[i] + (b[i] - a[i]) * fx;
+        let bottom = c[i] + (d[i] - c[i]) * fx;
+        top + (bottom - top) * fy
+    })
+}
+
+fn edge(a: egui::Pos2, b: egui::Pos2, p: egui::Pos2) -> f32 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+/// Pixels exactly on an edge belong to only one of the two triangles sharing
+/// it, so seams between triangles aren't blended twice.
+fn owns_edge(a: egui::Pos2, b: egui::Pos2) -> bool {
+    let d = b - a;
+    d.y > 0.0 || (d.y == 0.0 && d.x > 0.0)
+}
+
+fn draw_triangle(
+    image: &mut ColorImage,
+    v: [&epaint::Vertex; 3],
+    texture: Option<&ColorImage>,
+    clip: egui::Rect,
+    pixels_per_point: f32,
+) {
+    let mut p = v.map(|v| v.pos * pixels_per_point);
+    let mut v = v;
+    let area = edge(p[0], p[1], p[2]);
+    if area == 0.0 {
+        return;
+    }
+    if area < 0.0 {
+        p.swap(1, 2);
+        v.swap(1, 2);
+    }
+    let area = area.abs();
+
+    let [w, h] = image.size;
+    let canvas = egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(w as f32, h as f32));
+    let bounds = egui::Rect::from_points(&p).intersect(clip).intersect(canvas);
+    if !bounds.is_positive() {
+        return;
+    }
+
+    let colors = v.map(|v| to_rgba(v.color));
+    let edges = [(1, 2), (2, 0), (0, 1)];
+    let owned = edges.map(|(a, b)| owns_edge(p[a], p[b]));
+
+    for y in (bounds.min.y.floor() as usize)..(bounds.max.y.ceil() as usize).min(h) {
+        for x in (bounds.min.x.floor() as usize)..(bounds.max.x.ceil() as usize).min(w) {
+            let center = egui::pos2(x as f32 + 0.5, y as f32 + 0.5);
+            if !clip.contains(center) {
+                continue;
+            }
+            let weights = edges.map(|(a, b)| edge(p[a], p[b], center));
+            let inside = weights
+                .iter()
+                .zip(owned)
+                .all(|(&e, owned)| e > 0.0 || (e == 0.0 && owned));
+            if !inside {
+                continue;
+            }
+            let bary = weights.map(|e| e / area);
+
+            let mut src: Rgba = std::array::from_fn(|i| {
+                colors[0][i] * bary[0] + colors[1][i] * bary[1] + colors[2][i] * bary[2]
+            });
+            if let Some(texture) = texture {
+                let uv = egui::pos2(
+                    v[0].uv.x * bary[0] + v[1].uv.x * bary[1] + v[2].uv.x * bary[2],
+                    v[0].uv.y * bary[0] + v[1].uv.y * bary[1] + v[2].uv.y * bary[2],
+                );
+                let t = sample(texture, uv);
+                src = std::array::from_fn(|i| src[i] * t[i]);
+            }
+
+            // Premultiplied "over": ONE, ONE_MINUS_SRC_ALPHA
+            let dst = &mut image.pixels[y * w + x];
+            let d = to_rgba(*dst);
+            *dst = from_rgba(std::array::from_fn(|i| src[i] + d[i] * (1.0 - src[3])));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Width in pixels of the row through the center that has any coverage.
+    fn covered_width(image: &ColorImage) -> usize {
+        let [w, h] = image.size;
+        image.pixels[(h / 2) * w..(h / 2 + 1) * w]
+            .iter()
+            .filter(|c| c.a() > 0)
+            .count()
+    }
+
+    #[test]
+    fn crosshair_keeps_physical_size_across_scales() {
+        let config = Config {
+            inner_radius: 6.0,
+            outer_radius: 10.0,
+            stroke_width: 2.0,
+            ..Config::default()
+        };
+        let mut renderer = Renderer::new();
+        let base = renderer.render_crosshair(&config, 1.0);
+        for scale in [1.25, 1.5, 1.75, 2.0] {
+            let image = renderer.render_crosshair(&config, scale);
+            assert_eq!(image.size, base.size, "scale {scale}");
+            let (a, b) = (covered_width(&base), covered_width(&image));
+            assert!(a.abs_diff(b) <= 1, "scale {scale}: {b}px wide, expected {a}px");
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! Crosshairs rendered through the real draw code must match the reference
+//! images in `tests/golden/`. Each `<fixture>.json` config is rendered at the
+//! scales in [`CASES`] and compared with `<fixture>@<scale>x.png`.
+//!
+//! After an intended change to the drawing code, look at the diff images the
+//! failing run wrote, then regenerate the references with
+//! `AIMX_BLESS=1 cargo test --test golden`.
+
+use eframe::egui::{Color32, ColorImage};
+use std::path::{Path, PathBuf};
+
+use aimx::config::Config;
+use aimx::export::{load_png, save_png};
+use aimx::raster::Renderer;
+
+/// Fixture names and the desktop scale factors each one is rendered at.
+const CASES: &[(&str, &[f32])] = &[
+    ("default", &[1.0, 1.5, 2.0]),
+    ("dot", &[1.0]),
+    ("fractional", &[1.0, 1.25]),
+    ("hairline", &[1.0]),
+    ("large", &[1.0]),
+    ("ring", &[1.0, 2.0]),
+    ("thick_ring", &[1.0]),
+];
+
+/// Largest difference in any channel that still counts as the same pixel.
+/// Absorbs float rounding between platforms, not a moved edge.
+const TOLERANCE: u8 = 2;
+
+/// Set to write the current renders as the new references.
+const BLESS: &str = "AIMX_BLESS";
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+/// Where failing renders and their diffs go; inside `target/`, never checked in.
+fn diff_dir() -> PathBuf {
+    Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden")
+}
+
+/// Pixels that differ by more than [`TOLERANCE`], and an image showing the
+/// reference faded to gray with every such pixel in solid magenta.
+fn compare(actual: &ColorImage, expected: &ColorImage) -> (usize, ColorImage) {
+    let mut diff = ColorImage::filled(expected.size, Color32::TRANSPARENT);
+    let mut failures = 0;
+    for (i, (a, e)) in actual.pixels.iter().zip(&expected.pixels).enumerate() {
+        let off = a.to_array().iter().zip(e.to_array()).any(|(a, e)| a.abs_diff(e) > TOLERANCE);
+        diff.pixels[i] = if off {
+            failures += 1;
+            Color32::from_rgb(255, 0, 255)
+        } else {
+            let gray = ((e.r() as u32 + e.g() as u32 + e.b() as u32) / 6) as u8;
+            Color32::from_rgba_premultiplied(gray, gray, gray, e.a() / 2)
+        };
+    }
+    (failures, diff)
+}
+
+/// Render one case and compare it, returning a description of the mismatch.
+fn check(fixture: &str, scale: f32) -> Result<(), String> {
+    let name = format!("{fixture}@{scale}x");
+    let config_path = golden_dir().join(format!("{fixture}.json"));
+    let config = Config::read(&config_path)?;
+    let actual = Renderer::new().render_crosshair(&config, scale);
+    let reference = golden_dir().join(format!("{name}.png"));
+
+    if std::env::var_os(BLESS).is_some() {
+        return save_png(&actual, &reference);
+    }
+    let expected = load_png(&reference).map_err(|e| format!("{e} (run with {BLESS}=1 to create it)"))?;
+
+    // Stored PNGs are unmultiplied, so compare after the same round trip
+    let dir = diff_dir();
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let actual_path = dir.join(format!("{name}.actual.png"));
+    save_png(&actual, &actual_path)?;
+    let actual = load_png(&actual_path)?;
+
+    if actual.size != expected.size {
+        return Err(format!(
+            "{name}: size {:?}, expected {:?}; render saved to {}",
+            actual.size,
+            expected.size,
+            actual_path.display()
+        ));
+    }
+    let (failures, diff) = compare(&actual, &expected);
+    let diff_path = dir.join(format!("{name}.diff.png"));
+    if failures == 0 {
+        // Don't leave a previous failure's output lying around
+        std::fs::remove_file(&actual_path).ok();
+        std::fs::remove_file(&diff_path).ok();
+        return Ok(());
+    }
+    save_png(&diff, &diff_path)?;
+    Err(format!(
+        "{name}: {failures} pixels differ by more than {TOLERANCE}; render saved to {}, diff to {}",
+        actual_path.display(),
+        diff_path.display()
+    ))
+}
+
+#[test]
+fn crosshairs_match_reference_images() {
+    let failures: Vec<String> = CASES
+        .iter()
+        .flat_map(|(fixture, scales)| scales.iter().map(move |scale| (fixture, *scale)))
+        .filter_map(|(fixture, scale)| check(fixture, scale).err())
+        .collect();
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn every_fixture_is_covered() {
+    let mut fixtures: Vec<String> = std::fs::read_dir(golden_dir())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .map(|path| path.file_stem().unwrap().to_string_lossy().into_owned())
+        .collect();
+    fixtures.sort();
+    let cases: Vec<&str> = CASES.iter().map(|(fixture, _)| *fixture).collect();
+    assert_eq!(fixtures, cases, "add new fixtures to CASES");
+}
+
+#[test]
+fn tolerance_catches_a_one_pixel_change() {
+    let config = Config::default();
+    let image = Renderer::new().render_crosshair(&config, 1.0);
+    let bigger = Config {
+        inner_radius: config.inner_radius + 1.0,
+        ..config.clone()
+    };
+    let grown = Renderer::new().render_crosshair(&bigger, 1.0);
+    // Compare the same-size centers so only the drawing differs
+    let grown = aimx::export::crop_center(&grown, image.size);
+    assert_eq!(compare(&image, &image).0, 0);
+    assert!(compare(&grown, &image).0 > 0);
+}
@@ -0,0 +1,85 @@
+//! A panel killed outright must not leave its overlays running.
+#![cfg(target_os = "linux")]
+
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use aimx::platform::supervise::Supervisor;
+
+/// Set to the child to spawn when this test binary re-runs itself as the panel
+const HELPER: &str = "AIMX_SUPERVISE_HELPER";
+
+/// Stands in for the panel: spawns a supervised child, prints its pid and
+/// waits to be killed. Does nothing unless run by [`killed_panel`].
+#[test]
+fn panel_helper() {
+    let Some(child) = std::env::var_os(HELPER) else {
+        return;
+    };
+    let mut cmd = if child == "overlay" {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_aimx"));
+        cmd.args(["overlay", "--stdin"]).stdin(Stdio::piped());
+        cmd
+    } else {
+        let mut cmd = Command::new("sleep");
+        cmd.arg("60");
+        cmd
+    };
+    let mut child = Supervisor::new().spawn(cmd.stdout(Stdio::null())).unwrap();
+    println!("child {}", child.id());
+    let _ = child.wait();
+}
+
+/// Run [`panel_helper`] with `child`, SIGKILL it and return the child's pid.
+fn killed_panel(child: &str) -> u32 {
+    let mut panel = Command::new(std::env::current_exe().unwrap())
+        .args(["panel_helper", "--exact", "--nocapture"])
+        .env(HELPER, child)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let stdout = BufReader::new(panel.stdout.take().unwrap());
+    let pid = stdout
+        .lines()
+        .map_while(Result::ok)
+        // The harness may have printed the test name on the same line
+        .find_map(|line| line.rsplit_once("child ").and_then(|(_, pid)| pid.trim().parse().ok()))
+        .expect("helper did not report its child");
+    panel.kill().unwrap();
+    panel.wait().unwrap();
+    pid
+}
+
+/// Alive and not a zombie waiting for a reaper.
+fn running(pid: u32) -> bool {
+    std::fs::read_to_string(format!("/proc/{pid}/stat"))
+        .ok()
+        .and_then(|stat| stat.rsplit(") ").next().map(|rest| !rest.starts_with('Z')))
+        .unwrap_or(false)
+}
+
+fn assert_exits(pid: u32) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while running(pid) {
+        if Instant::now() > deadline {
+            unsafe { libc::kill(pid as i32, libc::SIGKILL) };
+            panic!("child {pid} outlived the panel");
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn child_dies_with_killed_panel() {
+    assert_exits(killed_panel("sleep"));
+}
+
+#[test]
+fn overlay_dies_with_killed_panel() {
+    if std::env::var_os("DISPLAY").is_none() && std::env::var_os("WAYLAND_DISPLAY").is_none() {
+        eprintln!("no display, skipping overlay test");
+        return;
+    }
+    assert_exits(killed_panel("overlay"));
+}